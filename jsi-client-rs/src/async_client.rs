@@ -0,0 +1,161 @@
+//! Async client for the jsi daemon (with the `tokio` feature), to fan out many
+//! requests from a single runtime instead of using a thread per request.
+//!
+//! Each request has its own connection. Dropping the future of a request closes
+//! the connection, and the daemon then kills the solvers working on it.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+use crate::client::{check_response, Client, Error};
+use crate::protocol::{read_reply, Options, Reply, Request, Response};
+
+/// Same as `Client`, with async requests.
+#[derive(Debug, Clone)]
+pub struct AsyncClient {
+    client: Client,
+}
+
+impl From<Client> for AsyncClient {
+    fn from(client: Client) -> Self {
+        AsyncClient { client }
+    }
+}
+
+impl AsyncClient {
+    pub fn new(socket_path: impl Into<PathBuf>) -> Self {
+        Client::new(socket_path).into()
+    }
+
+    /// Client for the daemon of the current user (~/.jsi/daemon/server.sock),
+    /// or None if $HOME is not set.
+    pub fn from_env() -> Option<Self> {
+        Client::from_env().map(AsyncClient::from)
+    }
+
+    /// Sets the options sent with every request.
+    pub fn with_options(self, options: Options) -> Self {
+        self.client.with_options(options).into()
+    }
+
+    pub fn options(&self) -> &Options {
+        self.client.options()
+    }
+
+    pub fn options_mut(&mut self) -> &mut Options {
+        self.client.options_mut()
+    }
+
+    /// Gives up on requests that get no response within `deadline` (see `Client`,
+    /// except that here the deadline covers the whole request, queueing included).
+    pub fn with_deadline(self, deadline: Option<Duration>) -> Self {
+        self.client.with_deadline(deadline).into()
+    }
+
+    pub fn deadline(&self) -> Option<Duration> {
+        self.client.deadline()
+    }
+
+    pub fn socket_path(&self) -> &Path {
+        self.client.socket_path()
+    }
+
+    /// Solves the smt2 file at `path`.
+    pub async fn solve_path(&self, path: impl AsRef<Path>) -> Result<Response, Error> {
+        self.solve(&self.client.path_request(path)?).await
+    }
+
+    /// Solves an SMT-LIB script sent inline, without going through a file.
+    pub async fn solve_text(&self, script: impl Into<String>) -> Result<Response, Error> {
+        self.solve(&self.client.text_request(script)).await
+    }
+
+    /// Sends `request` as is, ignoring the options of the client.
+    pub async fn solve(&self, request: &Request) -> Result<Response, Error> {
+        match self.deadline() {
+            Some(deadline) => tokio::time::timeout(deadline, self.send(request))
+                .await
+                .unwrap_or(Err(Error::Timeout)),
+            None => self.send(request).await,
+        }
+    }
+
+    async fn send(&self, request: &Request) -> Result<Response, Error> {
+        let mut stream = UnixStream::connect(self.socket_path()).await?;
+
+        let mut message = serde_json::to_vec(request).map_err(io::Error::from)?;
+        message.push(b'\n');
+        stream.write_all(&message).await?;
+
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).await? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "connection closed before a response was received",
+                ))?;
+            }
+
+            // queue updates, for requests with `queue_updates` set
+            if let Reply::Done(response) = read_reply(&mut line.as_bytes())? {
+                return check_response(response);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::UnixListener;
+
+    #[tokio::test]
+    async fn dropped_request_closes_connection() {
+        let dir = env::temp_dir().join(format!("jsif-async-client-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("server.sock");
+
+        // a daemon that reads the request, answers the first one and stalls on the next
+        let listener = UnixListener::bind(&socket).unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            let reply = b"{\"version\":1,\"result\":\"unsat\",\"solver\":\"z3\"}\n";
+            reader.get_mut().write_all(reply).await.unwrap();
+
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut reader = BufReader::new(stream);
+            line.clear();
+            reader.read_line(&mut line).await.unwrap();
+
+            // returns once the client is gone
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).await.unwrap();
+            line
+        });
+
+        let client = AsyncClient::new(&socket);
+        let response = client.solve_text("(check-sat)\n").await.unwrap();
+        assert_eq!(response.solver.as_deref(), Some("z3"));
+
+        let pending = client.solve_text("(check-sat)\n");
+        let timeout = tokio::time::timeout(Duration::from_millis(50), pending).await;
+        assert!(timeout.is_err());
+
+        let request: Request = serde_json::from_str(&server.await.unwrap()).unwrap();
+        assert_eq!(request.input.as_deref(), Some("(check-sat)\n"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
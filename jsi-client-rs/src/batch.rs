@@ -0,0 +1,263 @@
+//! Batch mode: solve many files in one invocation, with a bounded number of
+//! concurrent requests, and summarize the results.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::protocol::Response;
+use crate::result::{SolveResult, EXIT_FAILURE};
+
+/// Extension of the files picked up when walking a directory.
+const SMT2_EXTENSION: &str = "smt2";
+
+#[derive(Debug)]
+pub struct FileResult {
+    pub path: PathBuf,
+    pub response: io::Result<Response>,
+    pub elapsed: Duration,
+}
+
+fn is_pattern(input: &str) -> bool {
+    input.contains(['*', '?', '['])
+}
+
+/// True if the inputs should be handled in batch mode (anything other than a
+/// single plain file).
+pub fn is_batch(inputs: &[String]) -> bool {
+    match inputs {
+        [input] => !Path::new(input).is_file(),
+        _ => true,
+    }
+}
+
+/// Collects the .smt2 files under `dir`, recursively.
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<_>>()?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == SMT2_EXTENSION) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn expand_path(path: PathBuf, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if path.is_dir() {
+        walk(&path, files)
+    } else {
+        files.push(path);
+        Ok(())
+    }
+}
+
+/// Expands files, directories (all .smt2 files below them) and glob patterns into
+/// a list of files, in order and without duplicates.
+pub fn expand_inputs(inputs: &[String]) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for input in inputs {
+        let path = PathBuf::from(input);
+        if path.exists() {
+            expand_path(path, &mut files)?;
+            continue;
+        }
+
+        if !is_pattern(input) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("file not found: {}", input),
+            ));
+        }
+
+        let matches = glob::glob(input)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+
+        let before = files.len();
+        for path in matches {
+            expand_path(path.map_err(io::Error::from)?, &mut files)?;
+        }
+
+        if files.len() == before {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no files match: {}", input),
+            ));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    files.retain(|path| seen.insert(path.clone()));
+    Ok(files)
+}
+
+/// Solves every file with `solve`, running at most `jobs` at the same time.
+///
+/// Progress is reported on stderr as files finish, results are returned in the
+/// same order as `files`.
+pub fn run<F>(files: &[PathBuf], jobs: usize, solve: F) -> Vec<FileResult>
+where
+    F: Fn(&Path) -> io::Result<Response> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<FileResult>>> = Mutex::new(files.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, files.len().max(1)) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let Some(path) = files.get(i) else {
+                    break;
+                };
+
+                let start = Instant::now();
+                let response = solve(path);
+                let elapsed = start.elapsed();
+
+                let n = done.fetch_add(1, Ordering::SeqCst) + 1;
+                let status = match &response {
+                    Ok(response) => describe(response),
+                    Err(err) => format!("failed ({})", err),
+                };
+                eprintln!(
+                    "[{}/{}] {}: {} in {:.2}s",
+                    n,
+                    files.len(),
+                    path.display(),
+                    status,
+                    elapsed.as_secs_f64()
+                );
+
+                results.lock().unwrap()[i] = Some(FileResult {
+                    path: path.clone(),
+                    response,
+                    elapsed,
+                });
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+fn describe(response: &Response) -> String {
+    match (&response.error, &response.solver) {
+        (Some(error), _) => format!("failed ({})", error),
+        (None, Some(solver)) => format!("{} (from {})", response.result, solver),
+        (None, None) => response.result.to_string(),
+    }
+}
+
+/// Prints one row per file, followed by a count of each result.
+pub fn print_summary(results: &[FileResult]) {
+    let width = results
+        .iter()
+        .map(|r| r.path.display().to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max("file".len());
+
+    println!(
+        "{:width$}  {:8}  {:20}  {:>8}",
+        "file", "result", "solver", "time"
+    );
+
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for file_result in results {
+        let (result, solver) = match &file_result.response {
+            Ok(response) if response.error.is_none() => (
+                response.result.to_string(),
+                response.solver.clone().unwrap_or_default(),
+            ),
+            _ => ("failed".to_string(), String::new()),
+        };
+
+        println!(
+            "{:width$}  {:8}  {:20}  {:>7.2}s",
+            file_result.path.display(),
+            result,
+            solver,
+            file_result.elapsed.as_secs_f64()
+        );
+        *counts.entry(result).or_default() += 1;
+    }
+
+    let totals: Vec<String> = counts
+        .iter()
+        .map(|(result, count)| format!("{}: {}", result, count))
+        .collect();
+    println!("; {} files ({})", results.len(), totals.join(", "));
+}
+
+/// 0 if every file was solved (sat or unsat), 1 if any request failed, otherwise
+/// the exit code of the first unsolved file.
+pub fn exit_code(results: &[FileResult]) -> i32 {
+    let mut first_unsolved: Option<SolveResult> = None;
+
+    for file_result in results {
+        match &file_result.response {
+            Ok(response) if response.error.is_none() => {
+                if !response.result.is_ok() {
+                    first_unsolved.get_or_insert(response.result);
+                }
+            }
+            _ => return EXIT_FAILURE,
+        }
+    }
+
+    first_unsolved.map_or(0, |result| result.exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn expand_dirs_and_globs() {
+        let dir = env::temp_dir().join(format!("jsif-batch-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        for name in ["b.smt2", "a.smt2", "notes.txt", "sub/c.smt2"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let root = dir.to_string_lossy().into_owned();
+        let files = expand_inputs(std::slice::from_ref(&root)).unwrap();
+        let names: Vec<_> = files
+            .iter()
+            .map(|p| p.strip_prefix(&dir).unwrap())
+            .collect();
+        assert_eq!(names, ["a.smt2", "b.smt2", "sub/c.smt2"].map(Path::new));
+
+        // duplicates are dropped, order of first appearance is kept
+        let inputs = [format!("{}/b.smt2", root), format!("{}/*.smt2", root)];
+        let files = expand_inputs(&inputs).unwrap();
+        assert_eq!(files, [dir.join("b.smt2"), dir.join("a.smt2")]);
+
+        let missing = expand_inputs(&[format!("{}/*.smt3", root)]).unwrap_err();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+
+        assert!(is_batch(std::slice::from_ref(&root)));
+        assert!(!is_batch(&[format!("{}/a.smt2", root)]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
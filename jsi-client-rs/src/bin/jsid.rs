@@ -0,0 +1,159 @@
+//! jsid: native daemon for jsif, a drop-in replacement for `python -m jsi.server`.
+
+use std::error::Error;
+use std::fs;
+use std::os::unix::net::UnixListener;
+use std::{env, process, thread};
+
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+
+use jsif::config::Config;
+use jsif::daemon::{self, Status};
+use jsif::definitions::load_definitions;
+use jsif::reaper;
+use jsif::scheduler::Scheduler;
+use jsif::server::Server;
+use jsif::solvers::find_available_solvers;
+
+const USAGE: &str = "\
+jsid: native daemon for jsif, runs in the foreground until SIGINT/SIGTERM
+
+Usage: jsid [--slots N] [--pool N] [--help]
+
+Options:
+  --slots N           number of solvers that can run at the same time, across all
+                      requests (default: one per CPU). Requests that don't fit
+                      wait in a queue, by priority then arrival order. The
+                      slots are shared with `jsif run` (~/.jsi/slots)
+  --pool N            keep N processes of each enabled solver that has
+                      interactive arguments running, and answer queries with them
+                      instead of starting solvers for each query (default: 0)
+
+Listens on ~/.jsi/daemon/server.sock and writes its pid to ~/.jsi/daemon/server.pid,
+like the python daemon, so `jsif daemon status` and `jsif daemon stop` work with it.
+Solver definitions and paths are loaded once at startup (restart jsid after
+`jsif solvers --refresh`).";
+
+/// Parsed command line.
+struct Args {
+    slots: usize,
+    pool: usize,
+}
+
+fn serve(config: &Config, args: &Args) -> Result<(), Box<dyn Error>> {
+    match daemon::status(config) {
+        Status::Running(pid) | Status::Unresponsive(pid) => {
+            return Err(format!(
+                "a daemon is already running (pid {}), stop it with `jsif daemon stop`",
+                pid
+            )
+            .into())
+        }
+        Status::Stale(_) | Status::NotRunning => daemon::clean_up(config)?,
+    }
+
+    fs::create_dir_all(&config.server_home)?;
+    for leftover in reaper::reap(config)? {
+        println!(
+            "killed {} (process group {}) left by pid {}",
+            leftover.solver.name, leftover.solver.pgid, leftover.owner
+        );
+    }
+
+    reaper::enable(config)?;
+    let definitions = load_definitions(config)?;
+    let available_solvers = find_available_solvers(&definitions, config, false)?;
+
+    let socket = config.socket_path();
+    let listener = UnixListener::bind(&socket)?;
+    fs::write(config.pid_path(), process::id().to_string())?;
+    println!(
+        "jsid (pid {}) listening on {} with {} solver(s) and {} slot(s)",
+        process::id(),
+        socket.display(),
+        available_solvers.len(),
+        args.slots
+    );
+
+    let mut server = Server::new(definitions, available_solvers, args.slots)
+        .with_shared_slots(config.solver_slots.clone());
+    if args.pool > 0 {
+        server = server.with_pool(args.pool);
+        println!(
+            "keeping {} process(es) of: {}",
+            args.pool,
+            server.pooled_solvers().join(", ")
+        );
+    }
+
+    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+    thread::scope(|scope| {
+        scope.spawn(|| server.maintain_pool());
+        scope.spawn(|| {
+            if let Some(signal) = signals.forever().next() {
+                println!("received signal {}, shutting down", signal);
+                server.shutdown();
+                let _ = daemon::clean_up(config);
+                process::exit(0);
+            }
+        });
+
+        server.serve(&listener);
+    });
+
+    Ok(())
+}
+
+fn parse_args(args: &[String]) -> Result<Option<Args>, String> {
+    let mut slots = Scheduler::default_slots();
+    let mut pool = 0;
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--slots" => {
+                let value = args_iter.next().ok_or("missing value after --slots")?;
+                slots = value
+                    .parse()
+                    .ok()
+                    .filter(|&slots| slots > 0)
+                    .ok_or_else(|| format!("invalid number of slots: {}", value))?;
+            }
+            "--pool" => {
+                let value = args_iter.next().ok_or("missing value after --pool")?;
+                pool = value
+                    .parse()
+                    .map_err(|_| format!("invalid pool size: {}", value))?;
+            }
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+
+    Ok(Some(Args { slots, pool }))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args = match parse_args(&args) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(msg) => {
+            eprintln!("error: {}\n\n{}", msg, USAGE);
+            process::exit(1);
+        }
+    };
+
+    let Some(config) = Config::from_env() else {
+        eprintln!("Error: HOME is not set");
+        process::exit(1);
+    };
+
+    if let Err(e) = serve(&config, &args) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
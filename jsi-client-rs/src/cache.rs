@@ -0,0 +1,392 @@
+//! On-disk cache of sat/unsat results (~/.jsi/results), so that solving the same
+//! query again doesn't start any solver.
+//!
+//! Entries are content-addressed: the key is a SHA-256 of the normalized script
+//! (comments and formatting don't matter), of the definitions of the solvers that
+//! would run (a changed command line doesn't hit old results) and of the model flag.
+//! Only sat and unsat results are stored, which don't depend on the timeout or the
+//! other options. Each entry is a small JSON file, touched on every hit; when the
+//! cache grows over its size limit, the least recently used entries are evicted. The
+//! size is tracked from the first store of the process on, so the directory is only
+//! scanned when it may be over the limit.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::definitions::Definitions;
+use crate::protocol::{Options, Response, PROTOCOL_VERSION};
+use crate::result::SolveResult;
+use crate::runner::solver_names;
+use crate::session::split_commands;
+
+/// Default size limit of the cache (64 MiB).
+pub const DEFAULT_MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Bumped when the key or the entry format changes, so that old entries are ignored.
+const CACHE_VERSION: u32 = 2;
+
+const ENTRY_EXTENSION: &str = "json";
+const TEMP_EXTENSION: &str = "tmp";
+
+/// Temporary files older than this were left by a process that died while storing
+/// an entry.
+const STALE_TEMP_AGE: Duration = Duration::from_secs(60);
+
+/// Numbers the temporary files of the process, threads storing the same key
+/// don't share one.
+static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    version: u32,
+    result: SolveResult,
+    solver: String,
+
+    /// stdout of the solver, with the model if one was asked for
+    output: String,
+}
+
+/// The cache key for `script` solved with these options: the solvers that would
+/// run (in any order) with their executable and arguments, and the model flag.
+pub fn key(script: &str, definitions: &Definitions, options: &Options) -> String {
+    // malformed scripts are keyed on their text, solvers will complain about them
+    let script = match split_commands(script) {
+        Ok(commands) => commands.join("\n"),
+        Err(_) => script.to_string(),
+    };
+
+    let mut solvers = solver_names(options, definitions);
+    solvers.sort();
+    solvers.dedup();
+
+    let mut material = format!("jsif-cache-v{}\nmodel: {}\n", CACHE_VERSION, options.model);
+    for name in solvers {
+        let command = definitions
+            .get(&name)
+            .map(|definition| (&definition.executable, &definition.args, &definition.model));
+        material.push_str(&format!(
+            "solver: {:?} {}\n",
+            name,
+            serde_json::to_string(&command).expect("strings serialize")
+        ));
+    }
+    material.push_str(&script);
+    format!("{:x}", Sha256::digest(material.as_bytes()))
+}
+
+/// A directory of cached results, with a size limit in bytes.
+#[derive(Debug)]
+pub struct ResultCache {
+    dir: PathBuf,
+    max_size: u64,
+
+    /// size of the entries as of the last scan, plus what was stored since (None
+    /// until the first scan)
+    size: Mutex<Option<u64>>,
+}
+
+impl ResultCache {
+    pub fn new(dir: impl Into<PathBuf>, max_size: u64) -> Self {
+        ResultCache {
+            dir: dir.into(),
+            max_size,
+            size: Mutex::new(None),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(key).with_extension(ENTRY_EXTENSION)
+    }
+
+    /// The cached response for `key`, if any. Marks the entry as recently used.
+    pub fn get(&self, key: &str) -> Option<Response> {
+        let path = self.entry_path(key);
+        let entry: Entry = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+        if entry.version != CACHE_VERSION {
+            return None;
+        }
+
+        // best effort, a read-only cache still works
+        if let Ok(file) = File::options().append(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+
+        Some(Response {
+            version: PROTOCOL_VERSION,
+            result: entry.result,
+            solver: Some(entry.solver),
+            output: entry.output,
+            error: None,
+            elapsed: None,
+            queue_time: None,
+            cancelled: false,
+            solvers: Vec::new(),
+        })
+    }
+
+    /// Stores `response` under `key` if it is a sat or unsat result, then evicts
+    /// entries if the cache may be over its size limit. Returns true if it was stored.
+    pub fn put(&self, key: &str, response: &Response) -> io::Result<bool> {
+        let (SolveResult::Sat | SolveResult::Unsat, Some(solver)) =
+            (response.result, &response.solver)
+        else {
+            return Ok(false);
+        };
+
+        let entry = Entry {
+            version: CACHE_VERSION,
+            result: response.result,
+            solver: solver.clone(),
+            output: response.output.clone(),
+        };
+
+        let data = serde_json::to_vec(&entry)?;
+        if data.len() as u64 > self.max_size {
+            return Ok(false);
+        }
+
+        // written to a temporary file first, concurrent readers never see half an entry
+        fs::create_dir_all(&self.dir)?;
+        let path = self.entry_path(key);
+        let temp_path = path.with_extension(format!(
+            "{}.{}.{}",
+            std::process::id(),
+            NEXT_TEMP.fetch_add(1, Ordering::Relaxed),
+            TEMP_EXTENSION
+        ));
+        let replaced = fs::metadata(&path).map_or(0, |metadata| metadata.len());
+        fs::write(&temp_path, &data)?;
+        fs::rename(&temp_path, &path)?;
+
+        // the first store of the process scans the directory, the next ones only if
+        // the cache may have grown over its limit
+        let mut size = self.size.lock().unwrap();
+        let grown = size
+            .map(|total| (total + data.len() as u64).saturating_sub(replaced))
+            .filter(|&total| total <= self.max_size);
+        *size = Some(match grown {
+            Some(total) => total,
+            None => self.evict_entries()?,
+        });
+
+        Ok(true)
+    }
+
+    /// Removes the least recently used entries until the cache fits its size limit,
+    /// and the temporary files left by processes that died while storing an entry.
+    pub fn evict(&self) -> io::Result<()> {
+        let total = self.evict_entries()?;
+        *self.size.lock().unwrap() = Some(total);
+        Ok(())
+    }
+
+    /// `evict`, returns the size of the remaining entries.
+    fn evict_entries(&self) -> io::Result<u64> {
+        let mut entries = Vec::new();
+        let mut total = 0;
+        for dir_entry in fs::read_dir(&self.dir)? {
+            let path = dir_entry?.path();
+
+            // entries can disappear under our feet (another jsif evicting them)
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+
+            let used = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            match path.extension() {
+                Some(ext) if ext == ENTRY_EXTENSION => {}
+                Some(ext) if ext == TEMP_EXTENSION => {
+                    if used.elapsed().is_ok_and(|age| age > STALE_TEMP_AGE) {
+                        let _ = fs::remove_file(&path);
+                    }
+                    continue;
+                }
+                _ => continue,
+            }
+
+            total += metadata.len();
+            entries.push((used, metadata.len(), path));
+        }
+
+        entries.sort();
+        for (_, size, path) in entries {
+            if total <= self.max_size {
+                break;
+            }
+
+            if fs::remove_file(&path).is_ok() {
+                total -= size;
+            }
+        }
+
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::parse_definitions;
+    use std::env;
+
+    #[test]
+    fn key_ignores_formatting_and_solver_order() {
+        let definitions = parse_definitions(
+            r#"{"a": {"executable": "a", "model": null, "args": []},
+                "b": {"executable": "b", "model": null, "args": []}}"#,
+        )
+        .unwrap();
+        let options = Options::default();
+
+        let base = key("(assert true)\n(check-sat)\n", &definitions, &options);
+        assert_eq!(base.len(), 64);
+        assert_eq!(
+            key(
+                "; comment\n(assert\n  true) (check-sat)",
+                &definitions,
+                &options
+            ),
+            base
+        );
+
+        let sequence = |names: &[&str]| Options {
+            sequence: names.iter().map(|name| name.to_string()).collect(),
+            ..Options::default()
+        };
+        assert_eq!(
+            key("(check-sat)", &definitions, &sequence(&["b", "a"])),
+            key("(check-sat)", &definitions, &options)
+        );
+        assert_ne!(
+            key("(check-sat)", &definitions, &sequence(&["a"])),
+            key("(check-sat)", &definitions, &options)
+        );
+
+        let model = Options {
+            model: true,
+            ..Options::default()
+        };
+        assert_ne!(
+            key("(check-sat)", &definitions, &model),
+            key("(check-sat)", &definitions, &options)
+        );
+
+        // as well as the definitions of the solvers
+        let mut changed = definitions.clone();
+        changed[1].args.push("-smt2".to_string());
+        assert_ne!(
+            key("(check-sat)", &changed, &options),
+            key("(check-sat)", &definitions, &options)
+        );
+        changed[1].meta = Some("notes".to_string());
+        changed[1].args.pop();
+        assert_eq!(
+            key("(check-sat)", &changed, &options),
+            key("(check-sat)", &definitions, &options)
+        );
+    }
+
+    fn response(result: SolveResult, output: &str) -> Response {
+        Response {
+            version: PROTOCOL_VERSION,
+            result,
+            solver: Some("z3".to_string()),
+            output: output.to_string(),
+            error: None,
+            elapsed: Some(1.0),
+            queue_time: None,
+            cancelled: false,
+            solvers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn stores_definite_results_and_evicts_the_oldest() {
+        let dir = env::temp_dir().join(format!("jsif-cache-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let sat = response(SolveResult::Sat, "sat\n((x 1))\n");
+        let entry_size = fs::metadata({
+            let cache = ResultCache::new(&dir, DEFAULT_MAX_SIZE);
+            assert!(cache.put("a", &sat).unwrap());
+            cache.entry_path("a")
+        })
+        .unwrap()
+        .len();
+
+        // room for two entries
+        let cache = ResultCache::new(&dir, entry_size * 2);
+        assert!(!cache.put("t", &response(SolveResult::Timeout, "")).unwrap());
+        assert!(cache.get("t").is_none());
+
+        let hit = cache.get("a").unwrap();
+        assert_eq!(hit.result, SolveResult::Sat);
+        assert_eq!(hit.solver.as_deref(), Some("z3"));
+        assert_eq!(hit.output, sat.output);
+        assert_eq!(hit.elapsed, None);
+
+        // `a` is older than `b`, until it is used again
+        let old = SystemTime::now() - Duration::from_secs(60);
+        cache.put("b", &sat).unwrap();
+        File::options()
+            .append(true)
+            .open(cache.entry_path("b"))
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        assert!(cache.get("a").is_some());
+
+        cache.put("c", &sat).unwrap();
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some() && cache.get("c").is_some());
+
+        // only the temporary files of dead writers are removed
+        let stale = dir.join("d.1.0.tmp");
+        let fresh = dir.join("d.1.1.tmp");
+        fs::write(&stale, "{").unwrap();
+        fs::write(&fresh, "{").unwrap();
+        File::options()
+            .append(true)
+            .open(&stale)
+            .unwrap()
+            .set_modified(SystemTime::now() - STALE_TEMP_AGE * 2)
+            .unwrap();
+        cache.evict().unwrap();
+        assert!(!stale.exists() && fresh.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn threads_store_the_same_key() {
+        let dir = env::temp_dir().join(format!("jsif-cache-race-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let cache = ResultCache::new(&dir, DEFAULT_MAX_SIZE);
+        let sat = response(SolveResult::Sat, "sat\n");
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..20 {
+                        assert!(cache.put("k", &sat).unwrap());
+                    }
+                });
+            }
+        });
+
+        assert_eq!(cache.get("k").unwrap().result, SolveResult::Sat);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,593 @@
+//! Command line parsing for jsif, following the conventions of the jsi cli.
+
+use crate::model::ModelFormat;
+use crate::protocol::{Options, MAX_TIME};
+use crate::report::ResultsFormat;
+
+pub const USAGE: &str = "Usage: jsif [OPTIONS] <path/to/file.smt2>...
+       jsif run [OPTIONS] <path/to/file.smt2>...
+       jsif solvers [--refresh]
+       jsif reap
+       jsif daemon start|stop|status|restart
+
+Inputs can be files, directories (all .smt2 files below them, recursively) or
+glob patterns (e.g. 'tests/**/*.smt2'). With more than one file, jsif solves them
+in batch mode: progress is reported on stderr and a summary table on stdout.
+Use - to read a single SMT-LIB script from stdin.
+
+Commands:
+  run                 run the solvers locally, without going through the daemon
+                      (uses ~/.jsi/solvers.json and ~/.jsi/cache.json)
+  solvers             list and validate the solver definitions, and show where
+                      each solver was found (--refresh to scan the PATH again)
+  reap                kill the solvers left running by jsif and jsid processes
+                      that died (recorded in ~/.jsi/running)
+  daemon              manage the jsi daemon (started with `python -m jsi.server`,
+                      set JSI_PYTHON to use a different interpreter)
+
+Options (forwarded to the daemon for this request only, or used by `run`):
+  --timeout FLOAT     timeout in seconds (can also use unit suffixes: \"ms\", \"s\")
+                      (jsif also gives up on the daemon shortly after that)
+  --interval FLOAT    interval in seconds between starting solvers (default: 0s)
+  --grace-period FLOAT
+                      time between SIGTERM and SIGKILL when stopping solvers,
+                      for the whole process group of each solver (default: 1s)
+  --full-run          run all solvers to completion (don't stop on first result)
+  --sequence CSV      run only specified solvers, in the given order (e.g. a,c,b)
+  --model             generate a model for satisfiable instances
+  --model-format FMT  print the model as json or smt2, in the same shape whichever
+                      solver produced it (implies --model, single input only)
+  --json              print a single JSON object on stdout (result, solver, model,
+                      per-solver results and timings) instead of the solver output
+                      (single input only)
+  --results FORMAT    print the outcome of every solver to stderr, as a table, csv
+                      or json (single input only)
+  -j, --jobs N        number of files to solve concurrently in batch mode (default: 1)
+  --priority N        requests with a higher priority get solver slots first, when
+                      the daemon has to queue them (default: 0, jsid only)
+  --slots N           number of solvers that can run at the same time (default: one
+                      per CPU, `run` only). The slots are shared with the other
+                      `jsif run` processes and jsid (~/.jsi/slots)
+  --no-cache          don't use the cache of sat/unsat results (~/.jsi/results),
+                      which jsif checks before contacting the daemon or starting
+                      solvers (except with --full-run or --results)
+  --cache-size SIZE   size limit of the result cache, least recently used results
+                      are evicted past it (e.g. 500M or 2G, default: 64M)
+  --no-check          don't check the SMT-LIB syntax of the inputs before solving
+                      them
+  --help              show this message and exit
+
+Daemon connection options:
+  --auto-start        start the daemon if it is not running, then send the request
+  --fallback-local    run the solvers locally if the daemon is not reachable
+
+Exit codes:
+  10 sat, 20 unsat, 30 unknown, 31 timeout, 32 killed, 33 not started, 40 error
+  (if no solver succeeded: the best of timeout, error and unknown)
+  1 if jsif itself failed (bad arguments, daemon not reachable, bad request)
+  in batch mode: 0 if every file was solved, 1 if any request failed, otherwise
+  the exit code of the first file that was not solved";
+
+/// Input argument that means "read the script from stdin".
+pub const STDIN_INPUT: &str = "-";
+
+pub fn parse_time(arg: &str) -> Result<f64, String> {
+    let parsed = if let Some(ms) = arg.strip_suffix("ms") {
+        ms.parse::<f64>().map(|v| v / 1000.0)
+    } else if let Some(s) = arg.strip_suffix('s') {
+        s.parse::<f64>()
+    } else if let Some(m) = arg.strip_suffix('m') {
+        m.parse::<f64>().map(|v| v * 60.0)
+    } else {
+        arg.parse::<f64>()
+    };
+
+    match parsed {
+        Ok(value) if (0.0..=MAX_TIME).contains(&value) => Ok(value),
+        _ => Err(format!("invalid time value: {}", arg)),
+    }
+}
+
+/// Parses a size in bytes, with an optional K, M or G suffix (powers of 1024).
+pub fn parse_size(arg: &str) -> Result<u64, String> {
+    let (digits, unit) = match arg.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&arg[..arg.len() - 1], 1 << 10),
+        Some('M') => (&arg[..arg.len() - 1], 1 << 20),
+        Some('G') => (&arg[..arg.len() - 1], 1 << 30),
+        _ => (arg, 1),
+    };
+
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|size| size.checked_mul(unit))
+        .ok_or_else(|| format!("invalid size: {}", arg))
+}
+
+pub enum ArgsError {
+    /// --help was requested, not an actual error
+    Help,
+    BadParameter(String),
+}
+
+impl From<String> for ArgsError {
+    fn from(msg: String) -> Self {
+        ArgsError::BadParameter(msg)
+    }
+}
+
+/// Options that change how jsif talks to the daemon (not sent to the daemon).
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    /// start the daemon if it is not running
+    pub auto_start: bool,
+
+    /// run the solvers locally if the daemon is not reachable
+    pub fallback_local: bool,
+
+    /// number of files solved concurrently in batch mode
+    pub jobs: usize,
+
+    /// print the per-solver breakdown in this format
+    pub results: Option<ResultsFormat>,
+
+    /// print a single JSON object instead of the solver output
+    pub json: bool,
+
+    /// normalize the model to this format
+    pub model_format: Option<ModelFormat>,
+
+    /// solver slots of a local run, shared with other processes (default: one per CPU)
+    pub slots: Option<usize>,
+
+    /// don't look up or store results in the result cache
+    pub no_cache: bool,
+
+    /// size limit of the result cache in bytes
+    pub cache_size: Option<u64>,
+
+    /// don't check the syntax of the inputs before solving them
+    pub no_check: bool,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        ClientOptions {
+            auto_start: false,
+            fallback_local: false,
+            jobs: 1,
+            results: None,
+            json: false,
+            model_format: None,
+            slots: None,
+            no_cache: false,
+            cache_size: None,
+            no_check: false,
+        }
+    }
+}
+
+pub enum Command {
+    /// send the files to the daemon
+    Solve(Vec<String>, Options, ClientOptions),
+
+    /// run the solvers locally
+    Run(Vec<String>, Options, ClientOptions),
+
+    /// list and validate the solver definitions, optionally rebuilding the cache
+    Solvers { refresh: bool },
+
+    /// kill the solvers left behind by previous runs
+    Reap,
+
+    /// manage the daemon
+    Daemon(DaemonAction),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DaemonAction {
+    Start,
+    Stop,
+    Status,
+    Restart,
+}
+
+pub fn parse_args(args: &[String]) -> Result<Command, ArgsError> {
+    match args.first().map(String::as_str) {
+        Some("run") => {
+            let (inputs, options, client) = parse_solve_args(&args[1..])?;
+            if client.auto_start || client.fallback_local {
+                Err("--auto-start and --fallback-local only apply to the daemon".to_string())?;
+            }
+            Ok(Command::Run(inputs, options, client))
+        }
+        Some("solvers") => {
+            let mut refresh = false;
+            for arg in &args[1..] {
+                match arg.as_str() {
+                    "--refresh" => refresh = true,
+                    "--help" => return Err(ArgsError::Help),
+                    _ => Err(format!("unknown argument: {}", arg))?,
+                }
+            }
+            Ok(Command::Solvers { refresh })
+        }
+        Some("reap") => match args.get(1).map(String::as_str) {
+            Some("--help") => Err(ArgsError::Help),
+            Some(arg) => Err(format!("unknown argument: {}", arg))?,
+            None => Ok(Command::Reap),
+        },
+        Some("daemon") => {
+            let action = match args.get(1).map(String::as_str) {
+                Some("start") => DaemonAction::Start,
+                Some("stop") => DaemonAction::Stop,
+                Some("status") => DaemonAction::Status,
+                Some("restart") => DaemonAction::Restart,
+                Some("--help") => return Err(ArgsError::Help),
+                Some(arg) => Err(format!("unknown daemon command: {}", arg))?,
+                None => Err("missing daemon command (start, stop, status, restart)".to_string())?,
+            };
+
+            if let Some(arg) = args.get(2) {
+                Err(format!("unknown argument: {}", arg))?;
+            }
+
+            Ok(Command::Daemon(action))
+        }
+        _ => {
+            let (inputs, options, client) = parse_solve_args(args)?;
+            if client.slots.is_some() {
+                Err("--slots only applies to `jsif run` (see `jsid --slots`)".to_string())?;
+            }
+            Ok(Command::Solve(inputs, options, client))
+        }
+    }
+}
+
+fn parse_solve_args(args: &[String]) -> Result<(Vec<String>, Options, ClientOptions), ArgsError> {
+    let mut options = Options::default();
+    let mut client = ClientOptions::default();
+    let mut inputs: Vec<String> = Vec::new();
+    let mut args_iter = args.iter();
+
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--help" => return Err(ArgsError::Help),
+            "--full-run" => options.full_run = true,
+            "--model" => options.model = true,
+            "--auto-start" => client.auto_start = true,
+            "--fallback-local" => client.fallback_local = true,
+            "--no-cache" => client.no_cache = true,
+            "--no-check" => client.no_check = true,
+            "--json" => {
+                client.json = true;
+                options.details = true;
+            }
+            flag @ ("--timeout" | "--interval" | "--grace-period" | "--priority" | "--slots"
+            | "--sequence" | "--jobs" | "-j" | "--results" | "--model-format"
+            | "--cache-size") => {
+                let value = args_iter
+                    .next()
+                    .ok_or_else(|| format!("missing value after {}", flag))?;
+
+                match flag {
+                    "--timeout" => options.timeout = Some(parse_time(value)?),
+                    "--interval" => options.interval = Some(parse_time(value)?),
+                    "--grace-period" => options.grace_period = Some(parse_time(value)?),
+                    "--priority" => {
+                        options.priority = value
+                            .parse()
+                            .map_err(|_| format!("invalid priority: {}", value))?
+                    }
+                    "--slots" => {
+                        client.slots = Some(
+                            value
+                                .parse()
+                                .ok()
+                                .filter(|&slots| slots > 0)
+                                .ok_or_else(|| format!("invalid number of slots: {}", value))?,
+                        )
+                    }
+                    "--cache-size" => client.cache_size = Some(parse_size(value)?),
+                    "--model-format" => {
+                        client.model_format = Some(value.parse()?);
+                        options.model = true;
+                    }
+                    "--results" => {
+                        client.results = Some(value.parse()?);
+                        options.details = true;
+                    }
+                    "--jobs" | "-j" => {
+                        client.jobs = value
+                            .parse()
+                            .ok()
+                            .filter(|&jobs| jobs > 0)
+                            .ok_or_else(|| format!("invalid number of jobs: {}", value))?
+                    }
+                    _ => options.sequence = value.split(',').map(String::from).collect(),
+                }
+            }
+            _ if arg.starts_with("--") => Err(format!("unknown argument: {}", arg))?,
+            _ => inputs.push(arg.clone()),
+        }
+    }
+
+    if inputs.is_empty() {
+        Err("no input file provided".to_string())?;
+    }
+
+    if inputs.len() > 1 && inputs.iter().any(|input| input == STDIN_INPUT) {
+        Err("stdin (-) can't be combined with other inputs".to_string())?;
+    }
+
+    Ok((inputs, options, client))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, ArgsError> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_args(&args)
+    }
+
+    fn error(args: &[&str]) -> String {
+        match parse(args) {
+            Err(ArgsError::BadParameter(message)) => message,
+            Err(ArgsError::Help) => "help".to_string(),
+            Ok(_) => panic!("{:?} should not parse", args),
+        }
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(parse_time("2"), Ok(2.0));
+        assert_eq!(parse_time("1.5s"), Ok(1.5));
+        assert_eq!(parse_time("500ms"), Ok(0.5));
+        assert_eq!(parse_time("2m"), Ok(120.0));
+        assert_eq!(parse_time("0"), Ok(0.0));
+        assert_eq!(parse_time("1e6"), Ok(1e6));
+        for bad in [
+            "", "s", "ms", "-1", "abc", "5h", "1.5 s", "inf", "NaN", "1e20", "1e20ms",
+        ] {
+            assert_eq!(parse_time(bad), Err(format!("invalid time value: {}", bad)));
+        }
+    }
+
+    #[test]
+    fn solve_and_run_options() {
+        let Ok(Command::Solve(inputs, options, _)) = parse(&[
+            "--timeout",
+            "2s",
+            "--interval",
+            "100ms",
+            "--sequence",
+            "z3,cvc5",
+            "--model",
+            "a.smt2",
+            "tests/",
+        ]) else {
+            panic!("not a solve command");
+        };
+        assert_eq!(inputs, ["a.smt2", "tests/"]);
+        assert_eq!(options.timeout, Some(2.0));
+        assert_eq!(options.interval, Some(0.1));
+        assert_eq!(options.sequence, ["z3", "cvc5"]);
+        assert!(options.model && !options.details && !options.full_run);
+
+        let Ok(Command::Run(inputs, options, _)) = parse(&["run", "--full-run", "a.smt2"]) else {
+            panic!("not a run command");
+        };
+        assert_eq!(inputs, ["a.smt2"]);
+        assert!(options.full_run);
+
+        assert!(matches!(parse(&["--help"]), Err(ArgsError::Help)));
+        assert!(matches!(parse(&["run", "--help"]), Err(ArgsError::Help)));
+    }
+
+    #[test]
+    fn bad_arguments() {
+        assert_eq!(error(&[]), "no input file provided");
+        assert_eq!(error(&["--model"]), "no input file provided");
+        assert_eq!(
+            error(&["a.smt2", "--timeout"]),
+            "missing value after --timeout"
+        );
+        assert_eq!(
+            error(&["--timeout", "soon", "a.smt2"]),
+            "invalid time value: soon"
+        );
+        assert_eq!(
+            error(&["--interval", "infs", "a.smt2"]),
+            "invalid time value: infs"
+        );
+        assert_eq!(error(&["--bogus", "a.smt2"]), "unknown argument: --bogus");
+    }
+
+    #[test]
+    fn solvers_command() {
+        assert!(matches!(
+            parse(&["solvers"]),
+            Ok(Command::Solvers { refresh: false })
+        ));
+        assert!(matches!(
+            parse(&["solvers", "--refresh"]),
+            Ok(Command::Solvers { refresh: true })
+        ));
+        assert!(matches!(
+            parse(&["solvers", "--help"]),
+            Err(ArgsError::Help)
+        ));
+        assert_eq!(error(&["solvers", "--all"]), "unknown argument: --all");
+    }
+
+    #[test]
+    fn daemon_commands() {
+        assert!(matches!(
+            parse(&["daemon", "start"]),
+            Ok(Command::Daemon(DaemonAction::Start))
+        ));
+        assert!(matches!(
+            parse(&["daemon", "restart"]),
+            Ok(Command::Daemon(DaemonAction::Restart))
+        ));
+        assert!(matches!(parse(&["daemon", "--help"]), Err(ArgsError::Help)));
+        assert_eq!(error(&["daemon", "kill"]), "unknown daemon command: kill");
+        assert_eq!(
+            error(&["daemon"]),
+            "missing daemon command (start, stop, status, restart)"
+        );
+        assert_eq!(error(&["daemon", "stop", "now"]), "unknown argument: now");
+    }
+
+    #[test]
+    fn daemon_connection_options() {
+        let Ok(Command::Solve(_, _, client)) =
+            parse(&["--auto-start", "--fallback-local", "a.smt2"])
+        else {
+            panic!("not a solve command");
+        };
+        assert!(client.auto_start && client.fallback_local);
+
+        assert_eq!(
+            error(&["run", "--auto-start", "a.smt2"]),
+            "--auto-start and --fallback-local only apply to the daemon"
+        );
+    }
+
+    #[test]
+    fn batch_options() {
+        let Ok(Command::Solve(inputs, _, client)) = parse(&["-j", "4", "a.smt2", "tests/"]) else {
+            panic!("not a solve command");
+        };
+        assert_eq!(inputs, ["a.smt2", "tests/"]);
+        assert_eq!(client.jobs, 4);
+
+        assert_eq!(error(&["-j", "0", "a.smt2"]), "invalid number of jobs: 0");
+        assert_eq!(error(&["--jobs"]), "missing value after --jobs");
+    }
+
+    #[test]
+    fn stdin_input() {
+        let Ok(Command::Solve(inputs, _, _)) = parse(&["-"]) else {
+            panic!("not a solve command");
+        };
+        assert_eq!(inputs, [STDIN_INPUT]);
+
+        assert_eq!(
+            error(&["-", "a.smt2"]),
+            "stdin (-) can't be combined with other inputs"
+        );
+    }
+
+    #[test]
+    fn results_option() {
+        let Ok(Command::Run(_, options, client)) = parse(&["run", "--results", "csv", "a.smt2"])
+        else {
+            panic!("not a run command");
+        };
+        assert!(options.details);
+        assert_eq!(client.results, Some(ResultsFormat::Csv));
+
+        assert_eq!(
+            error(&["--results", "xml", "a.smt2"]),
+            "invalid results format: xml (table, csv, json)"
+        );
+    }
+
+    #[test]
+    fn json_option() {
+        // the per-solver details are requested for the outputs that show them
+        let Ok(Command::Solve(_, options, client)) = parse(&["--json", "-"]) else {
+            panic!("not a solve command");
+        };
+        assert!(options.details && client.json);
+    }
+
+    #[test]
+    fn model_format_option() {
+        let Ok(Command::Solve(_, options, client)) = parse(&["--model-format", "json", "a.smt2"])
+        else {
+            panic!("not a solve command");
+        };
+        assert!(options.model);
+        assert_eq!(client.model_format, Some(ModelFormat::Json));
+
+        assert_eq!(
+            error(&["--model-format", "xml", "a.smt2"]),
+            "invalid model format: xml (json, smt2)"
+        );
+    }
+
+    #[test]
+    fn reap_command() {
+        assert!(matches!(parse(&["reap"]), Ok(Command::Reap)));
+        assert!(matches!(parse(&["reap", "--help"]), Err(ArgsError::Help)));
+        assert_eq!(error(&["reap", "now"]), "unknown argument: now");
+    }
+
+    #[test]
+    fn scheduling_options() {
+        let Ok(Command::Run(_, options, client)) =
+            parse(&["run", "--slots", "2", "--priority", "-3", "a.smt2"])
+        else {
+            panic!("not a run command");
+        };
+        assert_eq!(client.slots, Some(2));
+        assert_eq!(options.priority, -3);
+
+        assert_eq!(
+            error(&["--priority", "high", "a.smt2"]),
+            "invalid priority: high"
+        );
+        assert_eq!(
+            error(&["run", "--slots", "0", "a.smt2"]),
+            "invalid number of slots: 0"
+        );
+        assert_eq!(
+            error(&["--slots", "2", "a.smt2"]),
+            "--slots only applies to `jsif run` (see `jsid --slots`)"
+        );
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("64K"), Ok(64 << 10));
+        assert_eq!(parse_size("500M"), Ok(500 << 20));
+        assert_eq!(parse_size("2g"), Ok(2 << 30));
+        for bad in ["", "M", "1.5G", "-1", "12T", "99999999999999G"] {
+            assert_eq!(parse_size(bad), Err(format!("invalid size: {}", bad)));
+        }
+    }
+
+    #[test]
+    fn cache_options() {
+        let Ok(Command::Solve(_, _, client)) =
+            parse(&["--no-cache", "--cache-size", "2G", "a.smt2"])
+        else {
+            panic!("not a solve command");
+        };
+        assert!(client.no_cache);
+        assert_eq!(client.cache_size, Some(2 << 30));
+
+        assert_eq!(
+            error(&["--cache-size", "lots", "a.smt2"]),
+            "invalid size: lots"
+        );
+    }
+
+    #[test]
+    fn no_check_option() {
+        let Ok(Command::Solve(_, _, client)) = parse(&["a.smt2"]) else {
+            panic!("not a solve command");
+        };
+        assert!(!client.no_check);
+
+        let Ok(Command::Run(_, _, client)) = parse(&["run", "--no-check", "a.smt2"]) else {
+            panic!("not a run command");
+        };
+        assert!(client.no_check);
+    }
+}
@@ -0,0 +1,394 @@
+//! Blocking client for the jsi daemon, for programs that want to solve queries
+//! in-process instead of running the `jsif` binary.
+
+use std::fmt;
+use std::io::{self, BufReader};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::protocol::{
+    read_message, read_reply, write_message, Cancel, CommandReply, Commands, Options, Reply,
+    Request, Response,
+};
+
+/// Why a request did not produce a result.
+#[derive(Debug)]
+pub enum Error {
+    /// the daemon could not be reached, or the connection failed
+    Io(io::Error),
+
+    /// the daemon received the request but could not process it (e.g. unknown solver)
+    Daemon(String),
+
+    /// no response before the deadline of the client
+    Timeout,
+}
+
+impl Error {
+    /// True if nobody is listening on the daemon socket.
+    pub fn is_unreachable(&self) -> bool {
+        matches!(self, Error::Io(err) if is_unreachable(err))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => err.fmt(f),
+            Error::Daemon(message) => f.write_str(message),
+            Error::Timeout => f.write_str("no response from the daemon before the deadline"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Daemon(_) | Error::Timeout => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            // what reads and writes fail with once the socket timeouts expire
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Error::Timeout,
+            _ => Error::Io(err),
+        }
+    }
+}
+
+/// True if the error means that nobody is listening on the daemon socket.
+pub fn is_unreachable(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+    )
+}
+
+/// Handle on a daemon socket, along with the options sent with every request.
+///
+/// The daemon answers a single request per connection, so a `Client` holds no
+/// connection of its own and can be shared between threads (sessions have their
+/// own connection, see `session`).
+#[derive(Debug, Clone)]
+pub struct Client {
+    socket_path: PathBuf,
+    options: Options,
+    deadline: Option<Duration>,
+}
+
+impl Client {
+    pub fn new(socket_path: impl Into<PathBuf>) -> Self {
+        Client {
+            socket_path: socket_path.into(),
+            options: Options::default(),
+            deadline: None,
+        }
+    }
+
+    /// Client for the daemon of the current user (~/.jsi/daemon/server.sock),
+    /// or None if $HOME is not set.
+    pub fn from_env() -> Option<Self> {
+        Config::from_env().map(|config| Client::new(config.socket_path()))
+    }
+
+    /// Sets the options sent with every request.
+    pub fn with_options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut Options {
+        &mut self.options
+    }
+
+    /// Gives up on requests that get no response within `deadline`, with
+    /// `Error::Timeout`. Closing the connection makes the daemon kill the solvers.
+    ///
+    /// This is independent of the `timeout` option, which the daemon applies to
+    /// each solver, and should leave some slack on top of it. Each queue update
+    /// (see `Connection::send_with_updates`) restarts the deadline.
+    pub fn with_deadline(mut self, deadline: Option<Duration>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    pub fn deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// Opens a connection for a single request.
+    pub fn connect(&self) -> io::Result<Connection> {
+        let stream = UnixStream::connect(&self.socket_path)?;
+        stream.set_read_timeout(self.deadline)?;
+        stream.set_write_timeout(self.deadline)?;
+        Ok(Connection { stream })
+    }
+
+    /// A request for the smt2 file at `path` (relative paths are resolved against
+    /// the current directory, since the daemon has its own).
+    pub fn path_request(&self, path: impl AsRef<Path>) -> io::Result<Request> {
+        let path = path.as_ref();
+        let abspath = path.canonicalize().map_err(|err| {
+            io::Error::new(err.kind(), format!("file not found: {}", path.display()))
+        })?;
+
+        Ok(Request::new(
+            abspath.to_string_lossy(),
+            self.options.clone(),
+        ))
+    }
+
+    /// A request that carries `script` inline, without going through a file.
+    pub fn text_request(&self, script: impl Into<String>) -> Request {
+        Request::inline(script, self.options.clone())
+    }
+
+    /// Solves the smt2 file at `path`.
+    pub fn solve_path(&self, path: impl AsRef<Path>) -> Result<Response, Error> {
+        self.solve(&self.path_request(path)?)
+    }
+
+    /// Solves an SMT-LIB script sent inline, without going through a file.
+    pub fn solve_text(&self, script: impl Into<String>) -> Result<Response, Error> {
+        self.solve(&self.text_request(script))
+    }
+
+    /// Sends `request` as is, ignoring the options of the client.
+    pub fn solve(&self, request: &Request) -> Result<Response, Error> {
+        self.connect()?.send(request)
+    }
+
+    /// Opens an incremental session with the options of the client (only jsid
+    /// supports sessions). The deadline of the client applies to each reply.
+    pub fn session(&self) -> Result<Session, Error> {
+        self.connect()?
+            .open_session(&Request::session(self.options.clone()))
+    }
+}
+
+/// An open connection to the daemon, consumed by the request it carries.
+#[derive(Debug)]
+pub struct Connection {
+    stream: UnixStream,
+}
+
+impl Connection {
+    /// A handle to cancel the request sent on this connection, e.g. from a signal
+    /// handler thread while `send` is waiting for the response.
+    pub fn canceller(&self) -> io::Result<Canceller> {
+        self.stream.try_clone().map(|stream| Canceller { stream })
+    }
+
+    /// Sends `request` and waits for the response. Responses that carry an error
+    /// are turned into `Error::Daemon`.
+    pub fn send(self, request: &Request) -> Result<Response, Error> {
+        self.send_with_updates(request, |_| {})
+    }
+
+    /// Same as `send`, and calls `on_queued` with the position of the request in the
+    /// queue while it waits for solver slots (0 once its solvers start). Only jsid
+    /// queues requests, and only reports it if the request has `queue_updates` set.
+    pub fn send_with_updates(
+        mut self,
+        request: &Request,
+        mut on_queued: impl FnMut(usize),
+    ) -> Result<Response, Error> {
+        write_message(&mut self.stream, request)?;
+        let mut reader = BufReader::new(self.stream);
+        loop {
+            match read_reply(&mut reader)? {
+                Reply::Queued(update) => on_queued(update.queued),
+                Reply::Done(response) => return check_response(response),
+            }
+        }
+    }
+
+    /// Sends `request` (with `session` set) and waits for the solvers of the session
+    /// to start.
+    pub fn open_session(mut self, request: &Request) -> Result<Session, Error> {
+        write_message(&mut self.stream, request)?;
+        let mut reader = BufReader::new(self.stream.try_clone()?);
+
+        // the request may be queued first
+        let reply = loop {
+            let message: serde_json::Value = read_message(&mut reader)?;
+            if message.get("queued").is_none() {
+                break serde_json::from_value(message).map_err(io::Error::from)?;
+            }
+        };
+
+        let reply = check_reply(reply)?;
+        Ok(Session {
+            stream: self.stream,
+            reader,
+            solvers: reply.solvers,
+        })
+    }
+}
+
+/// An incremental session: a connection to jsid with a portfolio of solvers that
+/// stay alive between queries. Commands are sent to every solver of the session,
+/// and each check-sat is raced across them.
+///
+/// Dropping the session (or `close`) kills its solvers.
+#[derive(Debug)]
+pub struct Session {
+    stream: UnixStream,
+    reader: BufReader<UnixStream>,
+    solvers: Vec<String>,
+}
+
+impl Session {
+    /// Names of the solvers of the session.
+    pub fn solvers(&self) -> &[String] {
+        &self.solvers
+    }
+
+    /// A handle to cancel the query in progress, e.g. from another thread while
+    /// `run` is waiting for the reply.
+    pub fn canceller(&self) -> io::Result<Canceller> {
+        self.stream.try_clone().map(|stream| Canceller { stream })
+    }
+
+    /// Runs any number of SMT-LIB commands, and returns what the solvers printed for
+    /// the queries among them. Replies that carry an error are turned into
+    /// `Error::Daemon`.
+    pub fn run(&mut self, commands: &str) -> Result<CommandReply, Error> {
+        write_message(&mut self.stream, &Commands::new(commands))?;
+        check_reply(read_message(&mut self.reader)?)
+    }
+
+    pub fn assert(&mut self, term: &str) -> Result<(), Error> {
+        self.run(&format!("(assert {})", term)).map(drop)
+    }
+
+    pub fn push(&mut self, levels: u32) -> Result<(), Error> {
+        self.run(&format!("(push {})", levels)).map(drop)
+    }
+
+    pub fn pop(&mut self, levels: u32) -> Result<(), Error> {
+        self.run(&format!("(pop {})", levels)).map(drop)
+    }
+
+    /// Races a check-sat across the solvers; the result is in `result`, and the
+    /// solver that answered in `solver`.
+    pub fn check_sat(&mut self) -> Result<CommandReply, Error> {
+        self.run("(check-sat)")
+    }
+
+    pub fn check_sat_assuming(&mut self, literals: &[&str]) -> Result<CommandReply, Error> {
+        self.run(&format!("(check-sat-assuming ({}))", literals.join(" ")))
+    }
+
+    /// Ends the session.
+    pub fn close(mut self) -> Result<(), Error> {
+        self.run("(exit)").map(drop)
+    }
+}
+
+/// Cancels the request in progress on a connection (or the query in progress in a
+/// session).
+#[derive(Debug)]
+pub struct Canceller {
+    stream: UnixStream,
+}
+
+impl Canceller {
+    /// Asks the daemon to kill the solvers. `send` then returns a response with
+    /// `cancelled` set, unless the request finished first.
+    pub fn cancel(&self) -> io::Result<()> {
+        write_message(&mut &self.stream, &Cancel::new())
+    }
+}
+
+fn check_reply(reply: CommandReply) -> Result<CommandReply, Error> {
+    match reply.error {
+        Some(error) => Err(Error::Daemon(error)),
+        None => Ok(reply),
+    }
+}
+
+pub(crate) fn check_response(response: Response) -> Result<Response, Error> {
+    match response.error {
+        Some(error) => Err(Error::Daemon(error)),
+        None => Ok(response),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::io::{BufRead, Write};
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    /// Serves a single connection, answering with `reply` and returning the request.
+    fn serve_once(socket: &Path, reply: &'static str) -> thread::JoinHandle<Request> {
+        let listener = UnixListener::bind(socket).unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            writeln!(reader.get_mut(), "{}", reply).unwrap();
+            serde_json::from_str(&line).unwrap()
+        })
+    }
+
+    #[test]
+    fn solve_text_round_trip() {
+        let dir = env::temp_dir().join(format!("jsif-client-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("server.sock");
+
+        let client = Client::new(&socket).with_options(Options {
+            model: true,
+            ..Options::default()
+        });
+
+        let server = serve_once(&socket, r#"{"version":1,"result":"sat","solver":"z3"}"#);
+        let response = client.solve_text("(check-sat)\n").unwrap();
+        let request = server.join().unwrap();
+        assert_eq!(request.input.as_deref(), Some("(check-sat)\n"));
+        assert!(request.options.model);
+        assert_eq!(response.solver.as_deref(), Some("z3"));
+
+        fs::remove_file(&socket).unwrap();
+        let server = serve_once(&socket, r#"{"version":1,"result":"error","error":"boom"}"#);
+        let err = client.solve_text("(check-sat)\n").unwrap_err();
+        server.join().unwrap();
+        assert!(matches!(err, Error::Daemon(ref message) if message == "boom"));
+
+        // a daemon that never answers
+        fs::remove_file(&socket).unwrap();
+        let silent = UnixListener::bind(&socket).unwrap();
+        let client = client.with_deadline(Some(Duration::from_millis(50)));
+        let err = client.solve_text("(check-sat)\n").unwrap_err();
+        assert!(matches!(err, Error::Timeout));
+        drop(silent);
+
+        fs::remove_file(&socket).unwrap();
+        assert!(client
+            .solve_text("(check-sat)\n")
+            .unwrap_err()
+            .is_unreachable());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
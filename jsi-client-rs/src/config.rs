@@ -0,0 +1,59 @@
+//! Locations of the files shared with jsi (definitions, solver cache, daemon state).
+
+use std::env;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub jsi_home: PathBuf,
+
+    /// user solver definitions (~/.jsi/solvers.json)
+    pub definitions_file: PathBuf,
+
+    /// solver executable paths (~/.jsi/cache.json)
+    pub path_cache: PathBuf,
+
+    /// fingerprints of the cached executables, used to detect stale cache entries
+    /// (kept separate so that cache.json stays readable by jsi)
+    pub path_stamps: PathBuf,
+
+    /// daemon state: pid file, socket and logs (~/.jsi/daemon)
+    pub server_home: PathBuf,
+
+    /// solvers started by jsif and jsid, for `jsif reap` (~/.jsi/running)
+    pub running_solvers: PathBuf,
+
+    /// sat/unsat results of earlier runs (~/.jsi/results)
+    pub result_cache: PathBuf,
+
+    /// solver slots shared by the local runs of jsif and jsid (~/.jsi/slots)
+    pub solver_slots: PathBuf,
+}
+
+impl Config {
+    pub fn new(jsi_home: PathBuf) -> Self {
+        Config {
+            definitions_file: jsi_home.join("solvers.json"),
+            path_cache: jsi_home.join("cache.json"),
+            path_stamps: jsi_home.join("cache.stamps.json"),
+            server_home: jsi_home.join("daemon"),
+            running_solvers: jsi_home.join("running"),
+            result_cache: jsi_home.join("results"),
+            solver_slots: jsi_home.join("slots"),
+            jsi_home,
+        }
+    }
+
+    pub fn socket_path(&self) -> PathBuf {
+        self.server_home.join("server.sock")
+    }
+
+    pub fn pid_path(&self) -> PathBuf {
+        self.server_home.join("server.pid")
+    }
+
+    /// Default config rooted at ~/.jsi, or None if $HOME is not set.
+    pub fn from_env() -> Option<Self> {
+        env::var_os("HOME").map(|home| Config::new(PathBuf::from(home).join(".jsi")))
+    }
+}
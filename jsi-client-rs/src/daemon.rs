@@ -0,0 +1,296 @@
+//! Lifecycle management for the jsi daemon (start, stop, status), based on the
+//! files it keeps in ~/.jsi/daemon (see `jsi.server`).
+
+use std::env;
+use std::fs;
+use std::io;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::wait::wait_until;
+
+/// How long the daemon gets to exit after SIGTERM before it is sent SIGKILL.
+pub const STOP_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// How long we wait for a freshly spawned daemon to start listening.
+pub const START_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Environment variable to pick the python interpreter used to start the daemon.
+const PYTHON_ENV: &str = "JSI_PYTHON";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// the daemon is alive and accepting connections
+    Running(i32),
+
+    /// the daemon process is alive but not accepting connections (yet)
+    Unresponsive(i32),
+
+    /// the pid file points to a process that no longer exists (or is not jsi)
+    Stale(i32),
+
+    NotRunning,
+}
+
+/// True if a process with this pid exists (equivalent of `pid_exists` in jsi).
+pub fn pid_exists(pid: i32) -> bool {
+    // SAFETY: signal 0 performs error checking only, nothing is sent
+    let ret = unsafe { libc::kill(pid, 0) };
+    ret == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Best effort check that `pid` is a jsi process, in case the pid was reused.
+fn is_jsi_process(pid: i32) -> bool {
+    Command::new("ps")
+        .args(["-o", "command=", "-p", &pid.to_string()])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains("jsi"))
+        .unwrap_or(true)
+}
+
+pub fn read_pid(config: &Config) -> Option<i32> {
+    let pid = fs::read_to_string(config.pid_path()).ok()?;
+    pid.trim().parse().ok().filter(|&pid| pid > 0)
+}
+
+pub fn status(config: &Config) -> Status {
+    match read_pid(config) {
+        None => Status::NotRunning,
+        Some(pid) if !pid_exists(pid) => Status::Stale(pid),
+        Some(pid) if UnixStream::connect(config.socket_path()).is_ok() => Status::Running(pid),
+        Some(pid) if is_jsi_process(pid) => Status::Unresponsive(pid),
+        Some(pid) => Status::Stale(pid),
+    }
+}
+
+/// Removes the pid file and socket left behind by a daemon that is gone.
+pub fn clean_up(config: &Config) -> io::Result<()> {
+    for path in [config.pid_path(), config.socket_path()] {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Waits until the daemon accepts connections on `socket_path`.
+pub fn wait_for_socket(socket_path: &Path, timeout: Duration) -> bool {
+    wait_until(timeout, || UnixStream::connect(socket_path).is_ok())
+}
+
+/// Stops the daemon: SIGTERM first, then SIGKILL if it is still around after
+/// `grace_period`. Returns the pid of the daemon that was stopped, if any.
+pub fn stop(config: &Config, grace_period: Duration) -> io::Result<Option<i32>> {
+    let pid = match status(config) {
+        Status::Running(pid) | Status::Unresponsive(pid) => pid,
+        Status::Stale(_) | Status::NotRunning => {
+            clean_up(config)?;
+            return Ok(None);
+        }
+    };
+
+    // SAFETY: plain syscall, worst case the pid is gone and we get ESRCH
+    unsafe { libc::kill(pid, libc::SIGTERM) };
+
+    if !wait_until(grace_period, || !pid_exists(pid)) {
+        // SAFETY: as above
+        unsafe { libc::kill(pid, libc::SIGKILL) };
+
+        if !wait_until(grace_period, || !pid_exists(pid)) {
+            return Err(io::Error::other(format!(
+                "daemon (pid {}) still running after SIGKILL",
+                pid
+            )));
+        }
+    }
+
+    clean_up(config)?;
+    Ok(Some(pid))
+}
+
+/// Starts the daemon with `python -m jsi.server` (the interpreter can be overridden
+/// with $JSI_PYTHON) and waits for it to accept connections.
+///
+/// Does nothing if the daemon is already running. Returns the pid of the daemon.
+pub fn start(config: &Config) -> io::Result<i32> {
+    let python = env::var(PYTHON_ENV).unwrap_or_else(|_| "python3".to_string());
+    start_with(config, &python)
+}
+
+/// Same as `start`, with `python` as the interpreter instead of $JSI_PYTHON.
+pub fn start_with(config: &Config, python: &str) -> io::Result<i32> {
+    match status(config) {
+        Status::Running(pid) => return Ok(pid),
+        Status::Unresponsive(pid) => {
+            // give a daemon that is still starting up a chance before giving up on it
+            if wait_for_socket(&config.socket_path(), START_TIMEOUT) {
+                return Ok(pid);
+            }
+            stop(config, STOP_GRACE_PERIOD)?;
+        }
+        Status::Stale(_) | Status::NotRunning => clean_up(config)?,
+    }
+
+    // the daemon writes its logs there, but doesn't create it
+    fs::create_dir_all(&config.server_home)?;
+
+    // this returns quickly: the daemon detaches itself and the launcher exits
+    let launcher = Command::new(python)
+        .args(["-m", "jsi.server"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|err| io::Error::new(err.kind(), format!("could not run {}: {}", python, err)))?;
+
+    if !launcher.status.success() {
+        let stderr = String::from_utf8_lossy(&launcher.stderr);
+        let last_line = stderr.lines().last().unwrap_or_default();
+        return Err(io::Error::other(format!(
+            "{} -m jsi.server failed ({}): {}",
+            python, launcher.status, last_line
+        )));
+    }
+
+    if !wait_for_socket(&config.socket_path(), START_TIMEOUT) {
+        return Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!(
+                "daemon did not start listening within {:?} (see {})",
+                START_TIMEOUT,
+                config.server_home.join("server.err").display()
+            ),
+        ));
+    }
+
+    read_pid(config).ok_or_else(|| io::Error::other("daemon started without a pid file"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_config;
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixListener;
+    use std::process::{self, Child};
+    use std::thread;
+
+    /// A pid that is not in use anymore.
+    fn dead_pid() -> i32 {
+        let mut child = Command::new("true").spawn().unwrap();
+        child.wait().unwrap();
+        child.id() as i32
+    }
+
+    /// Pretends that the process with this pid is the daemon, listening on the socket
+    /// if `listening`.
+    fn fake_daemon(config: &Config, pid: i32, listening: bool) -> Option<UnixListener> {
+        fs::create_dir_all(&config.server_home).unwrap();
+        fs::write(config.pid_path(), format!("{}\n", pid)).unwrap();
+        listening.then(|| UnixListener::bind(config.socket_path()).unwrap())
+    }
+
+    /// Reaps `child` as soon as it exits, so that it doesn't linger as a zombie.
+    fn reap_in_background(mut child: Child) -> i32 {
+        let pid = child.id() as i32;
+        thread::spawn(move || child.wait());
+        pid
+    }
+
+    #[test]
+    fn status_from_the_pid_file_and_socket() {
+        let config = temp_config("daemon-status");
+        assert_eq!(status(&config), Status::NotRunning);
+
+        let dead = dead_pid();
+        fake_daemon(&config, dead, false);
+        assert_eq!(status(&config), Status::Stale(dead));
+
+        // the test binary has jsi in its name
+        let pid = process::id() as i32;
+        fake_daemon(&config, pid, false);
+        assert_eq!(status(&config), Status::Unresponsive(pid));
+
+        let _listener = fake_daemon(&config, pid, true);
+        assert_eq!(status(&config), Status::Running(pid));
+
+        clean_up(&config).unwrap();
+        assert!(!config.pid_path().exists() && !config.socket_path().exists());
+        assert_eq!(status(&config), Status::NotRunning);
+        clean_up(&config).unwrap();
+
+        fs::remove_dir_all(&config.jsi_home).unwrap();
+    }
+
+    #[test]
+    fn stop_terminates_the_daemon_and_cleans_up() {
+        let config = temp_config("daemon-stop");
+        assert_eq!(stop(&config, STOP_GRACE_PERIOD).unwrap(), None);
+
+        // a stale pid file is only cleaned up
+        fake_daemon(&config, dead_pid(), false);
+        assert_eq!(stop(&config, STOP_GRACE_PERIOD).unwrap(), None);
+        assert!(!config.pid_path().exists());
+
+        let daemon = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = reap_in_background(daemon);
+        let _listener = fake_daemon(&config, pid, true);
+        assert_eq!(stop(&config, STOP_GRACE_PERIOD).unwrap(), Some(pid));
+        assert!(!pid_exists(pid));
+        assert!(!config.pid_path().exists() && !config.socket_path().exists());
+
+        // SIGKILL for a daemon that ignores SIGTERM
+        let mut daemon = Command::new("sh")
+            .args(["-c", "trap '' TERM; echo ready; exec sleep 30"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut ready = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut ready)
+            .unwrap();
+        let pid = reap_in_background(daemon);
+        let _listener = fake_daemon(&config, pid, true);
+        assert_eq!(
+            stop(&config, Duration::from_millis(200)).unwrap(),
+            Some(pid)
+        );
+        assert!(!pid_exists(pid));
+
+        fs::remove_dir_all(&config.jsi_home).unwrap();
+    }
+
+    #[test]
+    fn start_reports_launcher_failures() {
+        let config = temp_config("daemon-start");
+
+        // a running daemon is left alone
+        let pid = process::id() as i32;
+        let listener = fake_daemon(&config, pid, true);
+        assert_eq!(start(&config).unwrap(), pid);
+        drop(listener);
+        clean_up(&config).unwrap();
+
+        let launcher = config.jsi_home.join("python");
+        fs::write(
+            &launcher,
+            "#!/bin/sh\necho starting\necho \"no module $2\" >&2\nexit 1\n",
+        )
+        .unwrap();
+        fs::set_permissions(&launcher, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let error = start_with(&config, launcher.to_str().unwrap()).unwrap_err();
+
+        assert!(error.to_string().contains("-m jsi.server failed"));
+        assert!(error.to_string().ends_with("no module jsi.server"));
+        assert!(config.server_home.is_dir());
+
+        fs::remove_dir_all(&config.jsi_home).unwrap();
+    }
+}
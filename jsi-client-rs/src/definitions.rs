@@ -0,0 +1,211 @@
+//! Solver definitions and solver paths, compatible with the files used by jsi.
+
+use std::fs;
+use std::io;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+/// The definitions shipped with jsi, used when there is no ~/.jsi/solvers.json
+/// (a copy of src/jsi/config/solvers.json, so that the crate builds on its own)
+const DEFAULT_DEFINITIONS: &str = include_str!("../solvers.json");
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// A single entry in solvers.json.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SolverDefinition {
+    /// name of the executable to look for on the PATH
+    pub executable: String,
+
+    /// extra argument that enables model generation, if the solver has one (required,
+    /// null if there is none: jsi doesn't accept definitions without it)
+    #[serde(deserialize_with = "Option::deserialize")]
+    pub model: Option<String>,
+
+    /// arguments always passed to the solver
+    pub args: Vec<String>,
+
+    /// extra arguments that make the solver read commands from stdin, for incremental
+    /// sessions (solvers without them are left out of sessions)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interactive: Option<Vec<String>>,
+
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+
+    /// free-form notes about the solver
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<String>,
+}
+
+/// Maps solver names to their definitions, in the order of the definitions file.
+pub type Definitions = IndexMap<String, SolverDefinition>;
+
+pub(crate) fn invalid_data(path: &std::path::Path, err: serde_json::Error) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("error loading {}: {}", path.display(), err),
+    )
+}
+
+/// Loads the user definitions from ~/.jsi/solvers.json if that file exists,
+/// otherwise falls back to the definitions shipped with jsi.
+///
+/// Like jsi, the user file replaces the defaults entirely (no merging).
+pub fn load_definitions(config: &Config) -> io::Result<Definitions> {
+    let path = &config.definitions_file;
+    if path.exists() {
+        let data = fs::read_to_string(path)?;
+        return serde_json::from_str(&data).map_err(|err| invalid_data(path, err));
+    }
+
+    Ok(parse_definitions(DEFAULT_DEFINITIONS).expect("invalid default definitions"))
+}
+
+pub fn parse_definitions(data: &str) -> serde_json::Result<Definitions> {
+    serde_json::from_str(data)
+}
+
+/// Returns a description of each problem found in the definitions (empty if none).
+pub fn validate_definitions(definitions: &Definitions) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if definitions.is_empty() {
+        problems.push("no solver definitions found".to_string());
+    }
+
+    for (name, definition) in definitions {
+        if name.is_empty() || name.contains(',') {
+            problems.push(format!("invalid solver name: {:?}", name));
+        }
+
+        if definition.executable.trim().is_empty() {
+            problems.push(format!("{}: executable is empty", name));
+        }
+
+        if definition
+            .model
+            .as_deref()
+            .is_some_and(|m| m.trim().is_empty())
+        {
+            problems.push(format!(
+                "{}: model option is empty (use null instead)",
+                name
+            ));
+        }
+    }
+
+    if !definitions.values().any(|definition| definition.enabled) {
+        problems.push("no solver is enabled".to_string());
+    }
+
+    problems
+}
+
+/// Fails with the first solver name in `sequence` that has no definition.
+pub fn check_sequence(definitions: &Definitions, sequence: &[String]) -> Result<(), String> {
+    match sequence
+        .iter()
+        .find(|name| !definitions.contains_key(*name))
+    {
+        Some(name) => Err(format!("unknown solver: {}", name)),
+        None => Ok(()),
+    }
+}
+
+/// The names of the enabled solvers, in definition order.
+pub fn enabled_solvers(definitions: &Definitions) -> Vec<String> {
+    definitions
+        .iter()
+        .filter(|(_, definition)| definition.enabled)
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn default_definitions() {
+        let definitions = parse_definitions(DEFAULT_DEFINITIONS).unwrap();
+        for name in [
+            "z3",
+            "bitwuzla",
+            "cvc4",
+            "stp",
+            "yices",
+            "boolector",
+            "cvc5",
+        ] {
+            assert!(definitions.contains_key(name), "missing {}", name);
+        }
+
+        assert_eq!(definitions["yices"].executable, "yices-smt2");
+        assert_eq!(definitions["yices"].model, None);
+        assert_eq!(definitions["z3"].model.as_deref(), Some("--model"));
+        assert!(definitions["bitwuzla"].meta.is_some());
+        assert_eq!(
+            definitions["z3"].interactive.as_deref(),
+            Some(&["-in".to_string()][..])
+        );
+        assert_eq!(definitions["stp"].interactive, None);
+        assert!(!definitions["always-sat"].enabled);
+        assert!(validate_definitions(&definitions).is_empty());
+
+        // definition order is preserved, disabled solvers are skipped
+        let enabled = enabled_solvers(&definitions);
+        assert_eq!(enabled.first().map(String::as_str), Some("bitwuzla"));
+        assert_eq!(enabled.last().map(String::as_str), Some("z3"));
+    }
+
+    #[test]
+    fn default_definitions_match_jsi() {
+        let jsi_definitions =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../src/jsi/config/solvers.json");
+
+        // only checked from the jsi repo, not from a packaged crate
+        if let Ok(jsi_definitions) = fs::read_to_string(jsi_definitions) {
+            assert_eq!(
+                DEFAULT_DEFINITIONS, jsi_definitions,
+                "solvers.json is out of date, copy it from src/jsi/config"
+            );
+        }
+    }
+
+    #[test]
+    fn check_unknown_solver() {
+        let definitions = parse_definitions(DEFAULT_DEFINITIONS).unwrap();
+        assert!(check_sequence(&definitions, &["z3".into(), "always-sat".into()]).is_ok());
+        assert_eq!(
+            check_sequence(&definitions, &["z3".into(), "z4".into()]),
+            Err("unknown solver: z4".to_string())
+        );
+    }
+
+    #[test]
+    fn model_is_required() {
+        let error = parse_definitions(r#"{"a": {"executable": "a", "args": []}}"#).unwrap_err();
+        assert!(error.to_string().contains("missing field `model`"));
+        assert!(
+            parse_definitions(r#"{"a": {"executable": "a", "model": null, "args": []}}"#).is_ok()
+        );
+    }
+
+    #[test]
+    fn validate_problems() {
+        let data = r#"{
+            "a": {"executable": "", "model": "", "args": [], "enabled": false},
+            "b,c": {"executable": "/usr/bin/b", "model": null, "args": [], "enabled": false}
+        }"#;
+
+        let problems = validate_definitions(&parse_definitions(data).unwrap());
+        assert_eq!(problems.len(), 4, "{:?}", problems);
+    }
+}
@@ -0,0 +1,48 @@
+//! Scripts received inline (from stdin, or in the `input` field of a request).
+//!
+//! Solvers read their input from a path and write their output next to it, so an
+//! inline script is written to a private temporary directory rather than to a
+//! shared location.
+
+use std::env;
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Tells apart the directories of concurrent requests in the same process.
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A script written to a private temporary directory, removed on drop.
+pub struct TempInput {
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl TempInput {
+    /// Writes `script` to `<tmp>/jsif-<pid>-<n>/<name>`.
+    pub fn new(name: &str, script: &str) -> io::Result<TempInput> {
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let dir = env::temp_dir().join(format!("jsif-{}-{}", std::process::id(), n));
+        DirBuilder::new().mode(0o700).create(&dir)?;
+
+        let input = TempInput {
+            path: dir.join(name),
+            dir,
+        };
+
+        fs::write(&input.path, script)?;
+        Ok(input)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempInput {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
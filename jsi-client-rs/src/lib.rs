@@ -0,0 +1,70 @@
+//! Client for the jsi daemon, to embed in programs that solve many queries.
+//!
+//! ```no_run
+//! use jsif::{Client, Options, SolveResult};
+//!
+//! let client = jsif::Client::from_env()
+//!     .expect("HOME is not set")
+//!     .with_options(Options {
+//!         timeout: Some(5.0),
+//!         model: true,
+//!         ..Options::default()
+//!     });
+//!
+//! let response = client.solve_text("(declare-const x Int)\n(assert (> x 1))\n(check-sat)\n")?;
+//! if response.result == SolveResult::Sat {
+//!     println!("{}", response.output);
+//! }
+//! # Ok::<(), jsif::Error>(())
+//! ```
+//!
+//! `Client::session` opens an incremental session instead, where the solvers stay
+//! alive between queries (with jsid, the native daemon):
+//!
+//! ```no_run
+//! # let client = jsif::Client::from_env().expect("HOME is not set");
+//! let mut session = client.session()?;
+//! session.run("(declare-const x Int)")?;
+//! session.push(1)?;
+//! session.assert("(> x 1)")?;
+//! let reply = session.check_sat()?;
+//! println!("{:?} from {:?}", reply.result, reply.solver);
+//! session.pop(1)?;
+//! # Ok::<(), jsif::Error>(())
+//! ```
+//!
+//! With the `tokio` feature, `AsyncClient` has the same API with async requests.
+//!
+//! The remaining modules are what the `jsif` binary is built from: managing the
+//! daemon, reading the solver definitions shared with jsi, and running solvers
+//! locally without a daemon.
+
+#[cfg(feature = "tokio")]
+pub mod async_client;
+pub mod cache;
+pub mod client;
+pub mod config;
+pub mod daemon;
+pub mod definitions;
+pub mod input;
+pub mod model;
+pub mod pool;
+pub mod protocol;
+pub mod reaper;
+pub mod result;
+pub mod runner;
+pub mod scheduler;
+pub mod server;
+pub mod session;
+pub mod smtlib;
+pub mod solvers;
+pub mod supervisor;
+#[cfg(test)]
+mod testing;
+pub mod wait;
+
+#[cfg(feature = "tokio")]
+pub use async_client::AsyncClient;
+pub use client::{Canceller, Client, Connection, Error, Session};
+pub use protocol::{CommandReply, Options, Request, Response, SolverReport};
+pub use result::SolveResult;
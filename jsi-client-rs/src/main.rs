@@ -1,56 +1,887 @@
+mod batch;
+mod cli;
+mod output;
+mod report;
+mod stdin;
+
 use std::env;
-use std::io::{Read, Write};
-use std::os::unix::net::UnixStream;
-use std::path::PathBuf;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::process;
-use std::time::Instant;
+use std::sync::atomic::AtomicBool;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+
+use jsif::client::{self, Client, Connection};
+use jsif::input::TempInput;
+use jsif::{
+    cache, config, daemon, definitions, model, protocol, reaper, result, runner, scheduler, smtlib,
+    solvers,
+};
+
+use cache::ResultCache;
+use cli::{parse_args, ArgsError, ClientOptions, Command, DaemonAction, STDIN_INPUT, USAGE};
+use config::Config;
+use definitions::{check_sequence, load_definitions, validate_definitions, Definitions};
+use model::ModelFormat;
+use output::JsonOutput;
+use protocol::{Options, Request, Response, SolverReport, PROTOCOL_VERSION};
+use report::ResultsFormat;
+use result::{SolveResult, EXIT_FAILURE};
+use runner::RunOutcome;
+use scheduler::Scheduler;
+use solvers::{find_available_solvers, SolverPaths};
+
+/// How long to wait for the daemon past the solver timeout, to leave it time to
+/// kill the solvers and answer.
+const DEADLINE_GRACE: Duration = Duration::from_secs(2);
+
+/// Client for the daemon, which gives up shortly after the last solver would time
+/// out. Without a timeout (or with 0, which means no timeout), it waits for as long
+/// as the daemon takes.
+fn daemon_client(config: &Config, options: &Options) -> Client {
+    Client::new(config.socket_path()).with_deadline(request_deadline(config, options))
+}
+
+/// How long the daemon can take on a request: solver n starts at interval * n, and
+/// each solver gets the timeout from its start. None if there is no timeout, or if
+/// the number of solvers is unknown with an interval, or if the deadline is too large
+/// for a Duration.
+fn request_deadline(config: &Config, options: &Options) -> Option<Duration> {
+    let timeout = options.timeout.filter(|&t| t > 0.0)?;
+    let interval = options.interval.filter(|&t| t > 0.0).unwrap_or_default();
+    let staggering = if interval > 0.0 {
+        interval * daemon_solver_count(config, options)? as f64
+    } else {
+        0.0
+    };
+
+    Duration::try_from_secs_f64(timeout + staggering)
+        .ok()?
+        .checked_add(DEADLINE_GRACE)
+}
+
+/// The number of solvers the daemon starts for a request: the sequence, or else the
+/// enabled solvers (None if the definitions can't be loaded).
+fn daemon_solver_count(config: &Config, options: &Options) -> Option<usize> {
+    if !options.sequence.is_empty() {
+        return Some(options.sequence.len());
+    }
+
+    let definitions = load_definitions(config).ok()?;
+    Some(definitions.values().filter(|d| d.enabled).count())
+}
+
+/// What we report when the daemon did not answer before the deadline.
+fn deadline_response() -> Response {
+    Response {
+        version: PROTOCOL_VERSION,
+        result: SolveResult::Timeout,
+        solver: None,
+        output: String::new(),
+        error: None,
+        elapsed: None,
+        queue_time: None,
+        cancelled: false,
+        solvers: Vec::new(),
+    }
+}
+
+/// Connects to the daemon, optionally starting it first if it is not running.
+fn connect(config: &Config, client: &Client, auto_start: bool) -> io::Result<Connection> {
+    match client.connect() {
+        Err(err) if auto_start && client::is_unreachable(&err) => {
+            eprintln!("daemon not reachable ({}), starting it", err);
+            daemon::start(config)?;
+            client.connect()
+        }
+        result => result,
+    }
+}
+
+/// Sends `request`, cancelling it on SIGINT/SIGTERM so that the daemon kills the
+/// solvers before we exit (a second signal exits right away).
+///
+/// Returns the response along with the signal that was caught, if any.
+fn send_cancellable(
+    connection: Connection,
+    request: &Request,
+) -> Result<(Response, Option<i32>), client::Error> {
+    let canceller = connection.canceller()?;
+    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+    let handle = signals.handle();
+
+    let watcher = thread::spawn(move || {
+        let mut caught = None;
+        for signal in signals.forever() {
+            if caught.is_some() {
+                process::exit(128 + signal);
+            }
+
+            eprintln!("interrupted, cancelling the request (again to exit now)");
+            if let Err(e) = canceller.cancel() {
+                eprintln!("warning: could not cancel the request ({})", e);
+            }
+            caught = Some(signal);
+        }
+        caught
+    });
+
+    let response = connection.send_with_updates(request, |position| {
+        if position > 0 {
+            eprintln!(
+                "waiting for solver slots (position {} in the queue)",
+                position
+            );
+        }
+    });
+    handle.close();
+    let signal = watcher.join().unwrap_or(None);
+    Ok((response?, signal))
+}
+
+fn resolve_input(input_file: &str) -> io::Result<PathBuf> {
+    PathBuf::from(input_file)
+        .canonicalize()
+        .map_err(|err| io::Error::new(err.kind(), format!("file not found: {}", input_file)))
+}
+
+/// Checks the syntax of a single input (the script read from stdin, or else the
+/// file), returns the rendered diagnostic if it doesn't parse. Files that can't be
+/// read are left for solving to report.
+fn check_input(input: &str, script: Option<&str>) -> Result<(), String> {
+    let (name, text) = match script {
+        Some(script) => ("<stdin>", script.to_string()),
+        None => match fs::read(input) {
+            Ok(bytes) => (input, String::from_utf8_lossy(&bytes).into_owned()),
+            Err(_) => return Ok(()),
+        },
+    };
+
+    smtlib::check(&text).map_err(|diagnostic| diagnostic.render(name, &text))
+}
+
+/// Checks the syntax of a file of a batch.
+fn check_file(path: &Path) -> io::Result<()> {
+    let text = String::from_utf8_lossy(&fs::read(path)?).into_owned();
+    smtlib::check(&text)
+        .map_err(|diagnostic| io::Error::other(diagnostic.summary(&path.to_string_lossy(), &text)))
+}
+
+/// The result cache, along with the definitions its keys depend on.
+struct Cache {
+    results: ResultCache,
+    definitions: Definitions,
+}
+
+impl Cache {
+    /// The cache of the user, unless --no-cache (or the definitions can't be loaded,
+    /// which solving will report).
+    fn open(config: &Config, client: &ClientOptions) -> Option<Cache> {
+        if client.no_cache {
+            return None;
+        }
+
+        let results = ResultCache::new(
+            &config.result_cache,
+            client.cache_size.unwrap_or(cache::DEFAULT_MAX_SIZE),
+        );
+        let definitions = load_definitions(config).ok()?;
+        Some(Cache {
+            results,
+            definitions,
+        })
+    }
+
+    fn key(&self, script: &str, options: &Options) -> String {
+        cache::key(script, &self.definitions, options)
+    }
+
+    /// The key of the file at `path`, or None if it can't be read (solving it will
+    /// report that).
+    fn file_key(&self, path: &Path, options: &Options) -> Option<String> {
+        let script = fs::read(path).ok()?;
+        Some(self.key(&String::from_utf8_lossy(&script), options))
+    }
+
+    /// The key of a single input: the script read from stdin, or else the file.
+    fn input_key(&self, input: &str, script: Option<&str>, options: &Options) -> Option<String> {
+        match script {
+            Some(script) => Some(self.key(script, options)),
+            None => self.file_key(Path::new(input), options),
+        }
+    }
+
+    /// The cached response for `key`, unless the options ask for all the solvers to run.
+    fn get(&self, key: &str, options: &Options) -> Option<Response> {
+        if options.full_run {
+            return None;
+        }
+
+        self.results.get(key)
+    }
+
+    fn put(&self, key: &str, response: &Response) {
+        if let Err(e) = self.results.put(key, response) {
+            eprintln!("warning: could not cache the result ({})", e);
+        }
+    }
+}
+
+/// Reports an error that prevented us from getting a result, returns the exit code.
+fn fail(client: &ClientOptions, start: Instant, message: impl ToString) -> i32 {
+    let message = message.to_string();
+    eprintln!("Error: {}", message);
+
+    if client.json {
+        JsonOutput::error(message, start.elapsed()).print();
+    }
+
+    EXIT_FAILURE
+}
+
+/// Where the files of a batch are sent.
+enum Backend<'a> {
+    Daemon(&'a Client),
+    Local {
+        definitions: &'a Definitions,
+        available_solvers: &'a SolverPaths,
+
+        /// shared by the jobs, so that they don't start more solvers than there are slots
+        scheduler: &'a Scheduler,
+    },
+}
+
+impl Backend<'_> {
+    fn solve(&self, input: &Path, options: &Options) -> io::Result<Response> {
+        match self {
+            Backend::Daemon(client) => {
+                let request =
+                    Request::new(input.canonicalize()?.to_string_lossy(), options.clone());
+                match client.solve(&request) {
+                    Ok(response) => Ok(response),
+                    Err(client::Error::Timeout) => Ok(deadline_response()),
+                    Err(client::Error::Io(err)) => Err(err),
+                    Err(client::Error::Daemon(message)) => Err(io::Error::other(message)),
+                }
+            }
+            Backend::Local {
+                definitions,
+                available_solvers,
+                scheduler,
+            } => {
+                let input = input.canonicalize()?;
+                let wanted = runner::solver_count(options, definitions, available_solvers);
+                let never = AtomicBool::new(false);
+                let permit = scheduler
+                    .acquire(wanted, options.priority, &never, |_| {})
+                    .expect("never cancelled");
+
+                runner::run_until(
+                    &input,
+                    options,
+                    definitions,
+                    available_solvers,
+                    &never,
+                    permit.slots(),
+                    |_, _| {},
+                )
+                .map(RunOutcome::into_response)
+            }
+        }
+    }
+}
+
+fn solve_batch(
+    inputs: &[String],
+    options: &Options,
+    client: &ClientOptions,
+    backend: Backend,
+    cache: Option<&Cache>,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let files = batch::expand_inputs(inputs)?;
+    if client.results.is_some() || client.json || client.model_format.is_some() {
+        Err("--results, --json and --model-format only apply to a single input")?;
+    }
+
+    let results = batch::run(&files, client.jobs, |path| {
+        if !client.no_check {
+            check_file(path)?;
+        }
+
+        let cached = cache.and_then(|cache| Some((cache, cache.file_key(path, options)?)));
+        if let Some(response) = cached
+            .as_ref()
+            .and_then(|(cache, key)| cache.get(key, options))
+        {
+            return Ok(response);
+        }
+
+        let response = backend.solve(path, options)?;
+        if let Some((cache, key)) = &cached {
+            cache.put(key, &response);
+        }
+        Ok(response)
+    });
+    batch::print_summary(&results);
+    Ok(batch::exit_code(&results))
+}
+
+fn print_results(reports: &[SolverReport], format: ResultsFormat) {
+    if reports.is_empty() {
+        eprintln!("warning: no per-solver results in the response (is the daemon up to date?)");
+        return;
+    }
+
+    eprint!("{}", report::render(reports, format));
+}
+
+/// Shows what went wrong when no solver produced a result: the outcome of each
+/// solver (unless the local runner already printed it) and the end of its stderr.
+fn print_failures(reports: &[SolverReport], show_results: bool) {
+    for report in reports {
+        if show_results {
+            let exit = report
+                .exit
+                .map_or("N/A".to_string(), |exit| exit.to_string());
+            eprintln!(
+                "{} returned {} (exit code: {})",
+                report.name, report.result, exit
+            );
+        }
+
+        if let Some(stderr) = &report.stderr {
+            eprintln!("{} stderr:", report.name);
+            for line in stderr.trim_end().lines() {
+                eprintln!("  {}", line);
+            }
+        }
+    }
+}
+
+/// The model of a sat response in `format`, or None if there is no model (or if it
+/// can't be parsed, with a warning).
+fn normalize_model(response: &Response, format: ModelFormat) -> Option<serde_json::Value> {
+    let text = output::model_text(response.result, &response.output)?;
+    match model::parse(&text) {
+        Ok(model) => Some(match format {
+            ModelFormat::Json => model::to_json(&model),
+            ModelFormat::Smt2 => serde_json::Value::String(model::to_smt2(&model)),
+        }),
+        Err(e) => {
+            eprintln!(
+                "warning: could not parse the model ({}), leaving it as is",
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Where a response comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Daemon,
+    Local,
+    Cache,
+}
+
+/// Prints a response in the format requested on the command line, returns the
+/// exit code.
+fn print_response(
+    response: Response,
+    client: &ClientOptions,
+    start: Instant,
+    source: Source,
+) -> i32 {
+    let from_daemon = source == Source::Daemon;
+    let exit_code = response.result.exit_code();
+    let model = client
+        .model_format
+        .and_then(|format| normalize_model(&response, format));
+
+    if client.json {
+        let mut output = JsonOutput::new(response, start.elapsed());
+        output.cached = source == Source::Cache;
+        if model.is_some() {
+            output.model = model;
+        }
+        output.print();
+        return exit_code;
+    }
 
-fn get_server_home() -> Option<PathBuf> {
-    env::var_os("HOME").map(|home| {
-        let mut path = PathBuf::from(home);
-        path.push(".jsi");
-        path.push("daemon");
-        path
-    })
+    match model {
+        Some(serde_json::Value::String(smt2)) => println!("{}\n{}", response.result, smt2),
+        Some(json) => println!("{}\n{:#}", response.result, json),
+        None if !response.output.trim().is_empty() => println!("{}", response.output.trim()),
+        None => println!("{}", response.result),
+    }
+
+    match &response.solver {
+        Some(solver) if source == Source::Cache => println!("; (cached result from {})", solver),
+        Some(solver) => println!("; (result from {})", solver),
+        None if !response.solvers.is_empty() => {
+            println!("; (no solver succeeded)");
+            if client.results.is_none() {
+                print_failures(&response.solvers, from_daemon);
+            }
+        }
+        None => {}
+    }
+
+    if from_daemon {
+        println!("; response time: {:?}", start.elapsed());
+    }
+
+    if let Some(format) = client.results {
+        print_results(&response.solvers, format);
+    }
+
+    exit_code
 }
 
-fn send_command(command: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let socket_path = get_server_home().unwrap().join("server.sock");
-    let mut stream = UnixStream::connect(socket_path)?;
+fn solve(config: &Config, inputs: &[String], options: Options, client: ClientOptions) -> i32 {
+    let start = Instant::now();
+
+    // catch typos in the sequence before bothering the daemon
+    if !options.sequence.is_empty() {
+        let check = load_definitions(config)
+            .map_err(|e| e.to_string())
+            .and_then(|definitions| check_sequence(&definitions, &options.sequence));
+
+        if let Err(e) = check {
+            return fail(&client, start, e);
+        }
+    }
+
+    // a single input is read up front, to look it up before bothering the daemon
+    let script = if inputs[0] == STDIN_INPUT {
+        match stdin::read_script() {
+            Ok(script) => Some(script),
+            Err(e) => return fail(&client, start, e),
+        }
+    } else {
+        None
+    };
+
+    let is_batch = script.is_none() && batch::is_batch(inputs);
+    if !is_batch && !client.no_check {
+        if let Err(message) = check_input(&inputs[0], script.as_deref()) {
+            return fail(&client, start, message);
+        }
+    }
+
+    let cache = Cache::open(config, &client);
+    let cached = cache.as_ref().filter(|_| !is_batch).and_then(|cache| {
+        let key = cache.input_key(&inputs[0], script.as_deref(), &options)?;
+        Some((cache, key))
+    });
+
+    if client.results.is_none() {
+        if let Some(response) = cached
+            .as_ref()
+            .and_then(|(cache, key)| cache.get(key, &options))
+        {
+            return print_response(response, &client, start, Source::Cache);
+        }
+    }
+
+    let daemon = daemon_client(config, &options);
+    let connection = match connect(config, &daemon, client.auto_start) {
+        Ok(connection) => connection,
+        Err(e) if client.fallback_local => {
+            eprintln!("daemon not available ({}), running solvers locally", e);
+            // the input was already checked
+            let local_client = ClientOptions {
+                no_check: true,
+                ..client.clone()
+            };
+            return run_local(config, inputs, options, local_client, script)
+                .unwrap_or_else(|e| fail(&client, start, e));
+        }
+        Err(e) if client::is_unreachable(&e) => {
+            let message = format!(
+                "daemon not reachable at {} ({})",
+                config.socket_path().display(),
+                e
+            );
+            let exit_code = fail(&client, start, message);
+            eprintln!("start it with `jsif daemon start`, or use --auto-start or --fallback-local");
+            return exit_code;
+        }
+        Err(e) => return fail(&client, start, e),
+    };
 
-    // Send the command
-    stream.write_all(command.as_bytes())?;
-    stream.flush()?;
+    let mut request = if let Some(script) = script {
+        Request::inline(script, options)
+    } else if is_batch {
+        // we only needed to know that the daemon is up
+        drop(connection);
+        return solve_batch(
+            inputs,
+            &options,
+            &client,
+            Backend::Daemon(&daemon),
+            cache.as_ref(),
+        )
+        .unwrap_or_else(|e| fail(&client, start, e));
+    } else {
+        match resolve_input(&inputs[0]) {
+            Ok(abspath) => Request::new(abspath.to_string_lossy(), options),
+            Err(e) => return fail(&client, start, e),
+        }
+    };
+
+    // ignored by the python daemon, which doesn't queue requests
+    request.options.queue_updates = true;
 
-    // Read the response
+    let response = match send_cancellable(connection, &request) {
+        Ok((response, Some(signal))) if response.cancelled => {
+            fail(&client, start, "request cancelled, solvers killed");
+            return 128 + signal;
+        }
+        // the daemon answered before it got the cancel: the answer still counts
+        Ok((response, _)) => response,
+        Err(client::Error::Timeout) => {
+            eprintln!("no response from the daemon in time, giving up");
+            deadline_response()
+        }
+        Err(e) => return fail(&client, start, e),
+    };
+
+    if let Some((cache, key)) = &cached {
+        cache.put(key, &response);
+    }
+
+    print_response(response, &client, start, Source::Daemon)
+}
+
+/// Runs the solvers locally. `script` is the input if it was already read from stdin.
+fn run_local(
+    config: &Config,
+    inputs: &[String],
+    options: Options,
+    client: ClientOptions,
+    script: Option<String>,
+) -> Result<i32, Box<dyn std::error::Error>> {
     let start = Instant::now();
-    let mut response = String::new();
-    stream.read_to_string(&mut response)?;
+    let script = match script {
+        None if inputs[0] == STDIN_INPUT => Some(stdin::read_script()?),
+        script => script,
+    };
+
+    let is_batch = script.is_none() && batch::is_batch(inputs);
+    if !is_batch && !client.no_check {
+        check_input(&inputs[0], script.as_deref())?;
+    }
+
+    let cache = Cache::open(config, &client);
+    let cached = cache.as_ref().filter(|_| !is_batch).and_then(|cache| {
+        let key = cache.input_key(&inputs[0], script.as_deref(), &options)?;
+        Some((cache, key))
+    });
+
+    if client.results.is_none() {
+        if let Some(response) = cached
+            .as_ref()
+            .and_then(|(cache, key)| cache.get(key, &options))
+        {
+            return Ok(print_response(response, &client, start, Source::Cache));
+        }
+    }
+
+    let definitions = load_definitions(config)?;
+    check_sequence(&definitions, &options.sequence)?;
+    let available_solvers = find_available_solvers(&definitions, config, false)?;
+    reaper::enable(config)?;
+
+    // solver slots shared with the other local runs and jsid
+    let scheduler = Scheduler::new(client.slots.unwrap_or_else(Scheduler::default_slots))
+        .with_shared_slots(config.solver_slots.clone());
+
+    // keeps the script read from stdin around until the solvers are done with it
+    let mut stdin_input = None;
+    let abspath = if let Some(script) = &script {
+        stdin_input
+            .insert(TempInput::new(stdin::STDIN_FILE_NAME, script)?)
+            .path()
+            .to_path_buf()
+    } else if is_batch {
+        let backend = Backend::Local {
+            definitions: &definitions,
+            available_solvers: &available_solvers,
+            scheduler: &scheduler,
+        };
+        return solve_batch(inputs, &options, &client, backend, cache.as_ref());
+    } else {
+        resolve_input(&inputs[0])?
+    };
 
-    println!("{}", response);
-    println!("; response time: {:?}", start.elapsed());
-    Ok(())
+    let wanted = runner::solver_count(&options, &definitions, &available_solvers);
+    let never = AtomicBool::new(false);
+    let permit = scheduler
+        .acquire(wanted, options.priority, &never, |_| {
+            eprintln!("waiting for free solver slots (taken by other runs)")
+        })
+        .expect("never cancelled");
+
+    let outcome = runner::run_until(
+        &abspath,
+        &options,
+        &definitions,
+        &available_solvers,
+        &never,
+        permit.slots(),
+        |name, result| eprintln!("{} returned {}", name, result),
+    )?;
+
+    let mut response = outcome.into_response();
+    response.elapsed = Some(start.elapsed().as_secs_f64());
+    if let Some((cache, key)) = &cached {
+        cache.put(key, &response);
+    }
+
+    Ok(print_response(response, &client, start, Source::Local))
+}
+
+fn reap_solvers(config: &Config) -> Result<i32, Box<dyn std::error::Error>> {
+    let leftovers = reaper::reap(config)?;
+    for leftover in &leftovers {
+        eprintln!(
+            "killed {} (process group {}, {} process(es)) left by pid {}",
+            leftover.solver.name, leftover.solver.pgid, leftover.processes, leftover.owner
+        );
+    }
+
+    if leftovers.is_empty() {
+        eprintln!("no leftover solvers");
+    }
+
+    Ok(0)
+}
+
+fn list_solvers(config: &Config, refresh: bool) -> Result<i32, Box<dyn std::error::Error>> {
+    let definitions = load_definitions(config)?;
+    let available_solvers = find_available_solvers(&definitions, config, refresh)?;
+
+    if config.definitions_file.exists() {
+        eprintln!("definitions: {}", config.definitions_file.display());
+    } else {
+        eprintln!("definitions: built-in defaults");
+    }
+
+    let width = definitions.keys().map(String::len).max().unwrap_or(0);
+    for (name, definition) in &definitions {
+        let status = if definition.enabled {
+            "enabled"
+        } else {
+            "disabled"
+        };
+        let mut command = vec![definition.executable.clone()];
+        for arg in &definition.args {
+            // quote args that would be ambiguous when printed
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                command.push(format!("{:?}", arg));
+            } else {
+                command.push(arg.clone());
+            }
+        }
+
+        let model = match &definition.model {
+            Some(model_arg) => format!("  (model: {})", model_arg),
+            None => String::new(),
+        };
+
+        let path = available_solvers
+            .get(&definition.executable)
+            .map_or("N/A", String::as_str);
+
+        println!(
+            "{:width$}  {:8}  {}{}",
+            name,
+            status,
+            command.join(" "),
+            model
+        );
+        println!("{:width$}  {:8}  -> {}", "", "", path);
+    }
+
+    let problems = validate_definitions(&definitions);
+    for problem in &problems {
+        eprintln!("error: {}", problem);
+    }
+
+    Ok(if problems.is_empty() { 0 } else { EXIT_FAILURE })
+}
+
+/// Exit code for `jsif daemon status` when the daemon is not running (as in LSB init scripts).
+const EXIT_NOT_RUNNING: i32 = 3;
+
+fn manage_daemon(config: &Config, action: DaemonAction) -> Result<i32, Box<dyn std::error::Error>> {
+    let socket = config.socket_path();
+
+    match action {
+        DaemonAction::Status => {
+            let (message, exit_code) = match daemon::status(config) {
+                daemon::Status::Running(pid) => (format!("running (pid {})", pid), 0),
+                daemon::Status::Unresponsive(pid) => (
+                    format!("not accepting connections (pid {})", pid),
+                    EXIT_NOT_RUNNING,
+                ),
+                daemon::Status::Stale(pid) => (
+                    format!("not running (stale pid file for pid {})", pid),
+                    EXIT_NOT_RUNNING,
+                ),
+                daemon::Status::NotRunning => ("not running".to_string(), EXIT_NOT_RUNNING),
+            };
+
+            println!("daemon {}", message);
+            println!("socket: {}", socket.display());
+            Ok(exit_code)
+        }
+        DaemonAction::Stop => {
+            match daemon::stop(config, daemon::STOP_GRACE_PERIOD)? {
+                Some(pid) => eprintln!("daemon stopped (pid {})", pid),
+                None => eprintln!("daemon not running"),
+            }
+            Ok(0)
+        }
+        DaemonAction::Start | DaemonAction::Restart => {
+            if action == DaemonAction::Restart {
+                if let Some(pid) = daemon::stop(config, daemon::STOP_GRACE_PERIOD)? {
+                    eprintln!("daemon stopped (pid {})", pid);
+                }
+            }
+
+            let pid = daemon::start(config)?;
+            eprintln!(
+                "daemon running (pid {}), listening on {}",
+                pid,
+                socket.display()
+            );
+            Ok(0)
+        }
+    }
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    if args.len() < 2 {
-        eprintln!("Usage: {} <path/to/file.smt2>", args[0]);
-        process::exit(1);
+    if args.is_empty() {
+        eprintln!("{}", USAGE);
+        process::exit(EXIT_FAILURE);
     }
 
-    let command = args[1..].join(" ");
-    let abspath = match PathBuf::from(&command).canonicalize() {
-        Ok(path) => path,
-        Err(_) => {
-            eprintln!("Error: file not found: {}", command);
-            process::exit(1);
+    let command = match parse_args(&args) {
+        Ok(command) => command,
+        Err(ArgsError::Help) => {
+            println!("{}", USAGE);
+            process::exit(0);
+        }
+        Err(ArgsError::BadParameter(msg)) => {
+            eprintln!("error: {}", msg);
+            process::exit(EXIT_FAILURE);
         }
     };
 
-    match send_command(abspath.to_str().unwrap()) {
-        Ok(_) => (),
-        Err(e) => eprintln!("Error: {}", e),
+    let Some(config) = Config::from_env() else {
+        eprintln!("Error: HOME is not set");
+        process::exit(EXIT_FAILURE);
+    };
+
+    let result = match command {
+        Command::Solve(inputs, options, client) => Ok(solve(&config, &inputs, options, client)),
+        Command::Run(inputs, options, client) => {
+            let start = Instant::now();
+            Ok(run_local(&config, &inputs, options, client.clone(), None)
+                .unwrap_or_else(|e| fail(&client, start, e)))
+        }
+        Command::Solvers { refresh } => list_solvers(&config, refresh),
+        Command::Reap => reap_solvers(&config),
+        Command::Daemon(action) => manage_daemon(&config, action),
+    };
+
+    let exit_code = result.unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        EXIT_FAILURE
+    });
+
+    process::exit(exit_code);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn request_deadline_covers_the_staggered_solvers() {
+        let config = Config::new(PathBuf::from("/nonexistent/.jsi"));
+        let deadline = |timeout, interval| {
+            let options = Options {
+                timeout,
+                interval,
+                sequence: vec!["a".into(), "b".into(), "c".into()],
+                ..Options::default()
+            };
+            request_deadline(&config, &options)
+        };
+
+        assert_eq!(deadline(None, None), None);
+        assert_eq!(deadline(Some(0.0), Some(1.0)), None);
+        assert_eq!(deadline(Some(5.0), None), Some(Duration::from_secs(7)));
+        assert_eq!(
+            deadline(Some(5.0), Some(1.0)),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(deadline(Some(1e20), None), None);
+        assert_eq!(deadline(Some(5.0), Some(f64::MAX)), None);
+    }
+
+    #[test]
+    fn answer_sent_before_the_cancel_is_kept() {
+        let dir = env::temp_dir().join(format!("jsif-main-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = Config::new(dir.clone());
+        fs::create_dir_all(&config.server_home).unwrap();
+        let input = dir.join("query.smt2");
+        fs::write(&input, "(check-sat)\n").unwrap();
+
+        // a daemon that gets SIGINT while solving, and finishes before it reads the
+        // cancel message
+        let listener = UnixListener::bind(config.socket_path()).unwrap();
+        let daemon = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            signal_hook::low_level::raise(SIGINT).unwrap();
+
+            let mut cancel = String::new();
+            reader.read_line(&mut cancel).unwrap();
+            writeln!(
+                reader.get_mut(),
+                r#"{{"version":1,"result":"sat","solver":"z3","output":"sat\n"}}"#
+            )
+            .unwrap();
+            cancel
+        });
+
+        let client = ClientOptions {
+            no_cache: true,
+            ..ClientOptions::default()
+        };
+        let inputs = [input.to_string_lossy().to_string()];
+        let exit_code = solve(&config, &inputs, Options::default(), client);
+        let cancel = daemon.join().unwrap();
+
+        assert!(cancel.contains(r#""cancel":true"#), "{}", cancel);
+        assert_eq!(exit_code, SolveResult::Sat.exit_code());
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -0,0 +1,906 @@
+//! Parsing of the models printed by solvers for sat results, normalized into a single
+//! shape regardless of which solver won:
+//! - SMT-LIB models (`(model (define-fun ...) ...)` or `((define-fun ...) ...)`), as
+//!   printed by z3, cvc4/cvc5, bitwuzla and yices (with `--smt2-model-format`),
+//!   including `(_ bvN w)` constants (yices with `--bvconst-in-decimal`), arrays as
+//!   `store`/`as const` terms, `ite` chains or `(_ as-array f)` references
+//! - `get-value` style pairs (`((x #x01) (y true))`) and yices' `(= x 0b01)` lines
+//! - boolector models with `--output-number-format=hex` (`<id> <value> <symbol>`,
+//!   `<id>[<index>] <value> <symbol>` for array entries)
+//! - stp counterexamples (`ASSERT( x = 0x01 );`, `ASSERT( a[0x00] = 0x01 );`)
+
+use std::fmt;
+use std::str::FromStr;
+
+use indexmap::IndexMap;
+use serde_json::json;
+
+use crate::smtlib::{Reader, SExpr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelFormat {
+    Json,
+    Smt2,
+}
+
+impl FromStr for ModelFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ModelFormat::Json),
+            "smt2" => Ok(ModelFormat::Smt2),
+            _ => Err(format!("invalid model format: {} (json, smt2)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sort {
+    Bool,
+    Int,
+    BitVec(u32),
+    Array(Box<Sort>, Box<Sort>),
+}
+
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sort::Bool => write!(f, "Bool"),
+            Sort::Int => write!(f, "Int"),
+            Sort::BitVec(width) => write!(f, "(_ BitVec {})", width),
+            Sort::Array(index, element) => write!(f, "(Array {} {})", index, element),
+        }
+    }
+}
+
+/// A bitvector of arbitrary width, stored as little-endian 64-bit words.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitVec {
+    width: u32,
+    words: Vec<u64>,
+}
+
+impl BitVec {
+    fn zero(width: u32) -> BitVec {
+        BitVec {
+            width,
+            words: vec![0; width.div_ceil(64) as usize],
+        }
+    }
+
+    /// Parses digits in base 2, 10 or 16, failing if the value does not fit in `width` bits.
+    fn from_digits(digits: &str, radix: u32, width: u32) -> Result<BitVec, String> {
+        let invalid = || format!("invalid bitvector literal: {}", digits);
+        if digits.is_empty() || width == 0 {
+            return Err(invalid());
+        }
+
+        let mut bv = BitVec::zero(width);
+        for c in digits.chars() {
+            let digit = c.to_digit(radix).ok_or_else(invalid)?;
+
+            // words = words * radix + digit
+            let mut carry = digit as u128;
+            for word in bv.words.iter_mut() {
+                let v = (*word as u128) * (radix as u128) + carry;
+                *word = v as u64;
+                carry = v >> 64;
+            }
+
+            if carry != 0 || !bv.fits() {
+                return Err(format!(
+                    "bitvector literal too large for {} bits: {}",
+                    width, digits
+                ));
+            }
+        }
+
+        Ok(bv)
+    }
+
+    fn fits(&self) -> bool {
+        match (self.width % 64, self.words.last()) {
+            (0, _) | (_, None) => true,
+            (bits, Some(&last)) => last >> bits == 0,
+        }
+    }
+
+    fn bit(&self, i: u32) -> bool {
+        (self.words[(i / 64) as usize] >> (i % 64)) & 1 == 1
+    }
+
+    /// Hex digits (one per 4 bits, rounded up), most significant first.
+    pub fn to_hex(&self) -> String {
+        let digits = self.width.div_ceil(4);
+        (0..digits)
+            .rev()
+            .map(|d| {
+                let nibble = (0..4)
+                    .filter(|&b| d * 4 + b < self.width && self.bit(d * 4 + b))
+                    .fold(0, |acc, b| acc | (1 << b));
+                char::from_digit(nibble, 16).unwrap()
+            })
+            .collect()
+    }
+
+    pub fn to_bin(&self) -> String {
+        (0..self.width)
+            .rev()
+            .map(|i| if self.bit(i) { '1' } else { '0' })
+            .collect()
+    }
+
+    /// SMT-LIB literal: #x when the width is a multiple of 4, #b otherwise.
+    pub fn to_smt2(&self) -> String {
+        if self.width.is_multiple_of(4) {
+            format!("#x{}", self.to_hex())
+        } else {
+            format!("#b{}", self.to_bin())
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Bool(bool),
+    Int(i128),
+    BitVec(BitVec),
+    Array {
+        index: Sort,
+        element: Sort,
+        entries: Vec<(Value, Value)>,
+
+        /// value of the indices that are not in `entries`, if the solver reported it
+        default: Option<Box<Value>>,
+    },
+
+    /// a value we can't interpret (e.g. reals, floating point, datatypes, functions
+    /// of several arguments), as printed by the solver
+    Other {
+        sort: String,
+        value: String,
+    },
+}
+
+impl Value {
+    pub fn sort(&self) -> String {
+        match self {
+            Value::Bool(_) => Sort::Bool.to_string(),
+            Value::Int(_) => Sort::Int.to_string(),
+            Value::BitVec(bv) => Sort::BitVec(bv.width).to_string(),
+            Value::Array { index, element, .. } => {
+                Sort::Array(Box::new(index.clone()), Box::new(element.clone())).to_string()
+            }
+            Value::Other { sort, .. } => sort.clone(),
+        }
+    }
+
+    /// A value of `sort` to complete arrays without a default.
+    fn zero(sort: &Sort) -> Value {
+        match sort {
+            Sort::Bool => Value::Bool(false),
+            Sort::Int => Value::Int(0),
+            Sort::BitVec(width) => Value::BitVec(BitVec::zero(*width)),
+            Sort::Array(index, element) => Value::Array {
+                index: (**index).clone(),
+                element: (**element).clone(),
+                entries: Vec::new(),
+                default: Some(Box::new(Value::zero(element))),
+            },
+        }
+    }
+
+    pub fn to_smt2(&self) -> String {
+        match self {
+            Value::Bool(b) => b.to_string(),
+            Value::Int(n) if *n < 0 => format!("(- {})", n.unsigned_abs()),
+            Value::Int(n) => n.to_string(),
+            Value::BitVec(bv) => bv.to_smt2(),
+            Value::Array {
+                index,
+                element,
+                entries,
+                default,
+            } => {
+                let default = match default {
+                    Some(value) => value.to_smt2(),
+                    None => Value::zero(element).to_smt2(),
+                };
+                let sort = Sort::Array(Box::new(index.clone()), Box::new(element.clone()));
+                entries.iter().fold(
+                    format!("((as const {}) {})", sort, default),
+                    |array, (i, v)| format!("(store {} {} {})", array, i.to_smt2(), v.to_smt2()),
+                )
+            }
+            Value::Other { value, .. } => value.clone(),
+        }
+    }
+
+    /// The value without its sort: bitvectors as "0x..." strings, arrays as objects.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::Bool(b) => json!(b),
+            // serde_json can't write integers that don't fit in 64 bits
+            Value::Int(n) => match (i64::try_from(*n), u64::try_from(*n)) {
+                (Ok(n), _) => json!(n),
+                (_, Ok(n)) => json!(n),
+                _ => json!(n.to_string()),
+            },
+            Value::BitVec(bv) => json!(format!("0x{}", bv.to_hex())),
+            Value::Array {
+                entries, default, ..
+            } => json!({
+                "entries": entries
+                    .iter()
+                    .map(|(i, v)| json!([i.to_json(), v.to_json()]))
+                    .collect::<Vec<_>>(),
+                "default": default.as_ref().map(|v| v.to_json()),
+            }),
+            Value::Other { value, .. } => json!(value),
+        }
+    }
+}
+
+/// Maps symbols to their value, in the order the solver printed them.
+pub type Model = IndexMap<String, Value>;
+
+pub fn to_json(model: &Model) -> serde_json::Value {
+    let entries = model.iter().map(|(name, value)| {
+        let entry = json!({"sort": value.sort(), "value": value.to_json()});
+        (name.clone(), entry)
+    });
+    serde_json::Value::Object(entries.collect())
+}
+
+pub fn to_smt2(model: &Model) -> String {
+    let mut out = String::from("(\n");
+    for (name, value) in model {
+        out.push_str(&format!(
+            "  (define-fun {} () {} {})\n",
+            name,
+            value.sort(),
+            value.to_smt2()
+        ));
+    }
+    out.push(')');
+    out
+}
+
+/// Parses the model printed by a solver (the output after the sat line).
+pub fn parse(text: &str) -> Result<Model, String> {
+    // stp prints sat after the counterexample
+    let text: String = text
+        .lines()
+        .filter(|line| !matches!(line.trim(), "sat" | "unsat" | "unknown"))
+        .map(|line| format!("{}\n", line))
+        .collect();
+
+    let trimmed = text.trim_start();
+    if trimmed.starts_with('(') {
+        parse_smt2(&text)
+    } else if trimmed.starts_with("ASSERT") {
+        parse_stp(&text)
+    } else if trimmed.is_empty() {
+        Err("no model in the output".to_string())
+    } else {
+        parse_btor(&text)
+    }
+}
+
+//
+// SMT-LIB models
+//
+
+#[derive(Debug, Clone, PartialEq)]
+enum Sexp {
+    Atom(String),
+    List(Vec<Sexp>),
+}
+
+impl fmt::Display for Sexp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sexp::Atom(atom) => write!(f, "{}", atom),
+            Sexp::List(items) => {
+                let items: Vec<String> = items.iter().map(Sexp::to_string).collect();
+                write!(f, "({})", items.join(" "))
+            }
+        }
+    }
+}
+
+impl Sexp {
+    fn atom(&self) -> Option<&str> {
+        match self {
+            Sexp::Atom(atom) => Some(atom),
+            Sexp::List(_) => None,
+        }
+    }
+
+    fn list(&self) -> Option<&[Sexp]> {
+        match self {
+            Sexp::Atom(_) => None,
+            Sexp::List(items) => Some(items),
+        }
+    }
+
+    /// The list items if this is a list starting with `head`.
+    fn app(&self, head: &str) -> Option<&[Sexp]> {
+        match self.list()? {
+            [first, rest @ ..] if first.atom() == Some(head) => Some(rest),
+            _ => None,
+        }
+    }
+}
+
+/// Converts an s-expression read from `text`, with its atoms as they are written
+/// there (quotes included).
+fn to_sexp(sexpr: SExpr, text: &str) -> Sexp {
+    match sexpr {
+        SExpr::Atom(token) => Sexp::Atom(text[token.span.start..token.span.end].to_string()),
+        SExpr::List(items, _) => {
+            Sexp::List(items.into_iter().map(|item| to_sexp(item, text)).collect())
+        }
+    }
+}
+
+/// Reads all the s-expressions in `text`, skipping comments (with a lenient
+/// `smtlib::Reader`, solvers don't all print standard literals).
+fn read_sexps(text: &str) -> Result<Vec<Sexp>, String> {
+    Reader::lenient(text)
+        .map(|sexpr| match sexpr {
+            Ok(sexpr) => Ok(to_sexp(sexpr, text)),
+            Err(error) => Err(format!("invalid model: {}", error)),
+        })
+        .collect()
+}
+
+fn parse_sort(sexp: &Sexp) -> Option<Sort> {
+    match sexp {
+        Sexp::Atom(atom) if atom == "Bool" => Some(Sort::Bool),
+        Sexp::Atom(atom) if atom == "Int" => Some(Sort::Int),
+        Sexp::List(items) => match items.as_slice() {
+            [under, bitvec, width]
+                if under.atom() == Some("_") && bitvec.atom() == Some("BitVec") =>
+            {
+                Some(Sort::BitVec(width.atom()?.parse().ok()?))
+            }
+            [array, index, element] if array.atom() == Some("Array") => Some(Sort::Array(
+                Box::new(parse_sort(index)?),
+                Box::new(parse_sort(element)?),
+            )),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// A function of one argument from the model, that arrays can refer to.
+struct Function<'a> {
+    param: &'a str,
+    index: Sort,
+    element: Sort,
+    body: &'a Sexp,
+}
+
+fn is_numeral(atom: &str) -> bool {
+    !atom.is_empty() && atom.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// An integer too large for `Value::Int`, kept as printed by the solver.
+fn big_int(sexp: &Sexp) -> Value {
+    Value::Other {
+        sort: Sort::Int.to_string(),
+        value: sexp.to_string(),
+    }
+}
+
+struct Smt2Model<'a> {
+    functions: IndexMap<&'a str, Function<'a>>,
+}
+
+impl<'a> Smt2Model<'a> {
+    /// Parses a literal whose sort we don't know (get-value pairs, yices `(= x v)`).
+    fn infer(&self, sexp: &Sexp) -> Result<Value, String> {
+        let sort = match sexp {
+            Sexp::Atom(atom) if atom == "true" || atom == "false" => Sort::Bool,
+            Sexp::Atom(atom) if atom.starts_with("#x") => Sort::BitVec(4 * (atom.len() as u32 - 2)),
+            Sexp::Atom(atom) if atom.starts_with("#b") || atom.starts_with("0b") => {
+                Sort::BitVec(atom.len() as u32 - 2)
+            }
+            Sexp::Atom(atom) if atom.starts_with("0x") => Sort::BitVec(4 * (atom.len() as u32 - 2)),
+            Sexp::Atom(atom) if is_numeral(atom) || atom.parse::<i128>().is_ok() => Sort::Int,
+            Sexp::List(items) => match items.as_slice() {
+                [under, bv, width]
+                    if under.atom() == Some("_")
+                        && bv.atom().is_some_and(|bv| bv.starts_with("bv")) =>
+                {
+                    Sort::BitVec(width.atom().and_then(|w| w.parse().ok()).unwrap_or(0))
+                }
+                [minus, _] if minus.atom() == Some("-") => Sort::Int,
+                _ => return Err(format!("can't infer the sort of {}", sexp)),
+            },
+            _ => return Err(format!("can't infer the sort of {}", sexp)),
+        };
+
+        self.value(sexp, &sort)
+    }
+
+    fn value(&self, sexp: &Sexp, sort: &Sort) -> Result<Value, String> {
+        let invalid = || format!("invalid {} value: {}", sort, sexp);
+
+        match (sort, sexp) {
+            (Sort::Bool, Sexp::Atom(atom)) => atom.parse().map(Value::Bool).map_err(|_| invalid()),
+            (Sort::Int, Sexp::Atom(atom)) if is_numeral(atom) => {
+                Ok(atom.parse().map_or_else(|_| big_int(sexp), Value::Int))
+            }
+            (Sort::Int, Sexp::Atom(atom)) => atom.parse().map(Value::Int).map_err(|_| invalid()),
+            (Sort::Int, _) => match sexp.app("-") {
+                Some([n]) => match self.value(n, sort)? {
+                    Value::Int(n) => Ok(n.checked_neg().map_or_else(|| big_int(sexp), Value::Int)),
+                    Value::Other { .. } => Ok(big_int(sexp)),
+                    _ => Err(invalid()),
+                },
+                _ => Err(invalid()),
+            },
+            (Sort::BitVec(width), Sexp::Atom(atom)) => {
+                let (digits, radix) = match atom.get(..2) {
+                    Some("#x" | "0x") => (&atom[2..], 16),
+                    Some("#b" | "0b") => (&atom[2..], 2),
+                    _ => return Err(invalid()),
+                };
+                BitVec::from_digits(digits, radix, *width).map(Value::BitVec)
+            }
+            (Sort::BitVec(width), Sexp::List(items)) => match items.as_slice() {
+                // (_ bvN w)
+                [under, bv, _] if under.atom() == Some("_") => {
+                    let digits = bv.atom().and_then(|bv| bv.strip_prefix("bv"));
+                    let digits = digits.ok_or_else(invalid)?;
+                    BitVec::from_digits(digits, 10, *width).map(Value::BitVec)
+                }
+                _ => Err(invalid()),
+            },
+            (Sort::Array(index, element), _) => self.array(sexp, index, element),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn array(&self, sexp: &Sexp, index: &Sort, element: &Sort) -> Result<Value, String> {
+        let invalid = || format!("unsupported array value: {}", sexp);
+        let items = sexp.list().ok_or_else(invalid)?;
+
+        // ((as const (Array I E)) v)
+        if let [constant, default] = items {
+            if constant
+                .app("as")
+                .is_some_and(|as_| as_.first().and_then(Sexp::atom) == Some("const"))
+            {
+                return Ok(Value::Array {
+                    index: index.clone(),
+                    element: element.clone(),
+                    entries: Vec::new(),
+                    default: Some(Box::new(self.value(default, element)?)),
+                });
+            }
+        }
+
+        // (store a i v)
+        if let Some([array, i, v]) = sexp.app("store") {
+            let mut array = self.array(array, index, element)?;
+            if let Value::Array { entries, .. } = &mut array {
+                let i = self.value(i, index)?;
+                let v = self.value(v, element)?;
+                entries.retain(|(j, _)| *j != i);
+                entries.push((i, v));
+            }
+            return Ok(array);
+        }
+
+        // (_ as-array f)
+        if let [under, as_array, name] = items {
+            if under.atom() == Some("_") && as_array.atom() == Some("as-array") {
+                let function = name.atom().and_then(|name| self.functions.get(name));
+                let function = function.ok_or_else(invalid)?;
+                return self.function_array(function);
+            }
+        }
+
+        // (lambda ((x I)) body)
+        if let Some([params, body]) = sexp.app("lambda") {
+            if let Some([param]) = params.list() {
+                if let Some([name, _]) = param.list() {
+                    let param = name.atom().ok_or_else(invalid)?;
+                    return self.function_array(&Function {
+                        param,
+                        index: index.clone(),
+                        element: element.clone(),
+                        body,
+                    });
+                }
+            }
+        }
+
+        Err(invalid())
+    }
+
+    /// Converts a function of one argument whose body is an ite chain (or a constant)
+    /// into an array.
+    fn function_array(&self, function: &Function) -> Result<Value, String> {
+        let mut entries = Vec::new();
+        let mut body = function.body;
+
+        while let Some([condition, then, otherwise]) = body.app("ite") {
+            let index = match condition.app("=") {
+                Some([a, b]) if a.atom() == Some(function.param) => b,
+                Some([a, b]) if b.atom() == Some(function.param) => a,
+                _ => return Err(format!("unsupported array value: {}", function.body)),
+            };
+
+            let index = self.value(index, &function.index)?;
+            if !entries.iter().any(|(i, _)| *i == index) {
+                entries.push((index, self.value(then, &function.element)?));
+            }
+            body = otherwise;
+        }
+
+        Ok(Value::Array {
+            index: function.index.clone(),
+            element: function.element.clone(),
+            entries,
+            default: Some(Box::new(self.value(body, &function.element)?)),
+        })
+    }
+
+    /// A definition of a function of several arguments, as printed by the solver.
+    fn other(params: &Sexp, sort: &Sexp, body: &Sexp) -> Value {
+        let param_sorts: Vec<String> = params
+            .list()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|param| Some(param.list()?.get(1)?.to_string()))
+            .collect();
+
+        Value::Other {
+            sort: format!("(-> {} {})", param_sorts.join(" "), sort),
+            value: body.to_string(),
+        }
+    }
+}
+
+fn parse_smt2(text: &str) -> Result<Model, String> {
+    let sexps = read_sexps(text)?;
+
+    // unwrap (model ...) and ((define-fun ...) ...)
+    let mut items: Vec<&Sexp> = Vec::new();
+    for sexp in &sexps {
+        match sexp.app("model") {
+            Some(defs) => items.extend(defs),
+            None if sexp.app("define-fun").is_some() || sexp.app("=").is_some() => items.push(sexp),
+            None => items.extend(sexp.list().unwrap_or_default()),
+        }
+    }
+
+    // first pass: collect functions of one argument, for (_ as-array f)
+    let mut smt2 = Smt2Model {
+        functions: IndexMap::new(),
+    };
+    for item in &items {
+        if let Some([name, params, sort, body]) = item.app("define-fun") {
+            let (Some(name), Some([param]), Some(element)) =
+                (name.atom(), params.list(), parse_sort(sort))
+            else {
+                continue;
+            };
+
+            if let Some([param, index]) = param.list() {
+                if let (Some(param), Some(index)) = (param.atom(), parse_sort(index)) {
+                    let function = Function {
+                        param,
+                        index,
+                        element,
+                        body,
+                    };
+                    smt2.functions.insert(name, function);
+                }
+            }
+        }
+    }
+
+    let mut model = Model::new();
+    for item in items {
+        let (name, value) = if let Some([name, params, sort, body]) = item.app("define-fun") {
+            let name = name
+                .atom()
+                .ok_or_else(|| format!("invalid definition: {}", item))?;
+            let value = match (params.list(), parse_sort(sort)) {
+                (Some([]), Some(sort)) => smt2.value(body, &sort)?,
+                (Some([]), None) => Value::Other {
+                    sort: sort.to_string(),
+                    value: body.to_string(),
+                },
+                (Some([_]), Some(_)) if smt2.functions.contains_key(name) => {
+                    smt2.function_array(&smt2.functions[name])?
+                }
+                _ => Smt2Model::other(params, sort, body),
+            };
+            (name, value)
+        } else if let Some([name, value]) = item.app("=") {
+            let name = name
+                .atom()
+                .ok_or_else(|| format!("unsupported model entry: {}", item))?;
+            (name, smt2.infer(value)?)
+        } else if let Some([name, value]) = item.list() {
+            // get-value pairs
+            let name = name
+                .atom()
+                .ok_or_else(|| format!("unsupported model entry: {}", item))?;
+            (name, smt2.infer(value)?)
+        } else if item.app("declare-fun").is_some() || item.app("declare-sort").is_some() {
+            continue;
+        } else {
+            return Err(format!("unsupported model entry: {}", item));
+        };
+
+        model.insert(name.to_string(), value);
+    }
+
+    Ok(model)
+}
+
+//
+// solver specific formats
+//
+
+/// Adds `value` at `index` in the array `name`, creating the array if needed.
+fn insert_entry(model: &mut Model, name: &str, index: Value, value: Value) -> Result<(), String> {
+    let sort_of = |value: &Value| match value {
+        Value::BitVec(bv) => Sort::BitVec(bv.width),
+        Value::Bool(_) => Sort::Bool,
+        _ => Sort::Int,
+    };
+
+    let array = model
+        .entry(name.to_string())
+        .or_insert_with(|| Value::Array {
+            index: sort_of(&index),
+            element: sort_of(&value),
+            entries: Vec::new(),
+            default: None,
+        });
+
+    match array {
+        Value::Array { entries, .. } => {
+            entries.retain(|(i, _)| *i != index);
+            entries.push((index, value));
+            Ok(())
+        }
+        _ => Err(format!("{} is both a constant and an array", name)),
+    }
+}
+
+/// boolector with --output-number-format=hex: one hex digit per 4 bits, so the widths
+/// are rounded up to a multiple of 4.
+fn parse_btor(text: &str) -> Result<Model, String> {
+    let hex = |digits: &str| BitVec::from_digits(digits, 16, 4 * digits.len() as u32);
+    let mut model = Model::new();
+
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let invalid = || format!("unsupported model line: {}", line);
+        let [id, value, name] = <[&str; 3]>::try_from(line.split_whitespace().collect::<Vec<_>>())
+            .map_err(|_| invalid())?;
+
+        let value = Value::BitVec(hex(value)?);
+        match id.split_once('[') {
+            Some((_, index)) => {
+                let index = index.strip_suffix(']').ok_or_else(invalid)?;
+                insert_entry(&mut model, name, Value::BitVec(hex(index)?), value)?;
+            }
+            None => {
+                id.parse::<u64>().map_err(|_| invalid())?;
+                model.insert(name.to_string(), value);
+            }
+        }
+    }
+
+    Ok(model)
+}
+
+fn parse_stp_value(value: &str) -> Result<Value, String> {
+    let value = value.trim();
+    match value {
+        "TRUE" | "true" => return Ok(Value::Bool(true)),
+        "FALSE" | "false" => return Ok(Value::Bool(false)),
+        _ => {}
+    }
+
+    let (digits, radix, bits) = match value.get(..2) {
+        Some("0x" | "0X") => (&value[2..], 16, 4),
+        Some("0b" | "0B") => (&value[2..], 2, 1),
+        _ => return Err(format!("unsupported counterexample value: {}", value)),
+    };
+
+    BitVec::from_digits(digits, radix, bits * digits.len() as u32).map(Value::BitVec)
+}
+
+fn parse_stp(text: &str) -> Result<Model, String> {
+    let mut model = Model::new();
+
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let invalid = || format!("unsupported counterexample line: {}", line);
+        let assertion = line
+            .strip_prefix("ASSERT(")
+            .and_then(|rest| rest.trim_end().strip_suffix(';'))
+            .and_then(|rest| rest.trim_end().strip_suffix(')'))
+            .ok_or_else(invalid)?
+            .trim();
+
+        let Some((lhs, rhs)) = assertion.split_once('=') else {
+            // boolean variables: ASSERT( b ); or ASSERT( NOT(b) );
+            let (name, value) = match assertion.strip_prefix("NOT(") {
+                Some(rest) => (rest.strip_suffix(')').ok_or_else(invalid)?, false),
+                None => (assertion, true),
+            };
+            model.insert(name.trim().to_string(), Value::Bool(value));
+            continue;
+        };
+
+        let value = parse_stp_value(rhs)?;
+        match lhs.trim().split_once('[') {
+            Some((name, index)) => {
+                let index = index.strip_suffix(']').ok_or_else(invalid)?;
+                insert_entry(&mut model, name.trim(), parse_stp_value(index)?, value)?;
+            }
+            None => {
+                model.insert(lhs.trim().to_string(), value);
+            }
+        }
+    }
+
+    Ok(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bv(width: u32, hex: &str) -> Value {
+        Value::BitVec(BitVec::from_digits(hex, 16, width).unwrap())
+    }
+
+    #[test]
+    fn bitvec_literals() {
+        let big = BitVec::from_digits(
+            "115792089237316195423570985008687907853269984665640564039457584007913129639935",
+            10,
+            256,
+        )
+        .unwrap();
+        assert_eq!(big.to_hex(), "f".repeat(64));
+        assert!(BitVec::from_digits("256", 10, 8).is_err());
+        assert_eq!(BitVec::from_digits("101", 2, 3).unwrap().to_smt2(), "#b101");
+        assert_eq!(BitVec::from_digits("1", 16, 8).unwrap().to_smt2(), "#x01");
+    }
+
+    #[test]
+    fn z3_model() {
+        let output = "(\n  (define-fun x () (_ BitVec 8)\n    #x01)\n  (define-fun b () Bool\n    true)\n  \
+                      (define-fun i () Int\n    (- 5))\n  (define-fun a () (Array (_ BitVec 8) (_ BitVec 8))\n    \
+                      (store ((as const (Array (_ BitVec 8) (_ BitVec 8))) #x00) #x02 #x03))\n)";
+        let model = parse(output).unwrap();
+        assert_eq!(model["x"], bv(8, "01"));
+        assert_eq!(model["b"], Value::Bool(true));
+        assert_eq!(model["i"], Value::Int(-5));
+        assert_eq!(
+            model["a"].to_smt2(),
+            "(store ((as const (Array (_ BitVec 8) (_ BitVec 8))) #x00) #x02 #x03)"
+        );
+
+        let json = to_json(&model);
+        assert_eq!(json["x"]["sort"], "(_ BitVec 8)");
+        assert_eq!(json["x"]["value"], "0x01");
+        assert_eq!(json["a"]["value"]["entries"][0], json!(["0x02", "0x03"]));
+        assert_eq!(json["i"]["value"], -5);
+
+        assert_eq!(
+            parse("((define-fun b () Bool true)").unwrap_err(),
+            "invalid model: unclosed `(`, the script ends before its `)`"
+        );
+    }
+
+    #[test]
+    fn big_ints() {
+        let ten_to_40 = format!("1{}", "0".repeat(40));
+        let output = format!(
+            "((define-fun x () Int 1180591620717411303424)\n (define-fun y () Int (- {0}))\n \
+             (define-fun z () Int {0}))",
+            ten_to_40
+        );
+        let model = parse(&output).unwrap();
+        assert_eq!(model["x"], Value::Int(1 << 70));
+        assert_eq!(model["y"].sort(), "Int");
+        assert_eq!(model["y"].to_smt2(), format!("(- {})", ten_to_40));
+        assert_eq!(model["z"].to_smt2(), ten_to_40);
+
+        // too large for a JSON number
+        let json = to_json(&model);
+        assert_eq!(json["x"]["value"], "1180591620717411303424");
+        assert_eq!(json["y"]["value"], format!("(- {})", ten_to_40));
+        assert_eq!(json["z"]["value"], ten_to_40);
+        assert_eq!(json["z"]["sort"], "Int");
+
+        let model = parse(&format!("((x {}) (y 18446744073709551615))", ten_to_40)).unwrap();
+        assert_eq!(model["x"].to_smt2(), ten_to_40);
+        assert_eq!(to_json(&model)["y"]["value"], json!(u64::MAX));
+    }
+
+    #[test]
+    fn z3_as_array() {
+        let output = "(model\n  (define-fun a () (Array (_ BitVec 4) Bool) (_ as-array k!0))\n  \
+                      (define-fun k!0 ((x!0 (_ BitVec 4))) Bool (ite (= x!0 #x1) true false))\n)";
+        let model = parse(output).unwrap();
+        let Value::Array {
+            entries, default, ..
+        } = &model["a"]
+        else {
+            panic!("not an array: {:?}", model["a"]);
+        };
+        assert_eq!(entries, &[(bv(4, "1"), Value::Bool(true))]);
+        assert_eq!(default.as_deref(), Some(&Value::Bool(false)));
+    }
+
+    #[test]
+    fn yices_model() {
+        let output = "sat\n((define-fun x () (_ BitVec 8) (_ bv255 8))\n \
+                      (define-fun f ((x!1 (_ BitVec 8))) (_ BitVec 8) (ite (= x!1 (_ bv1 8)) (_ bv2 8) (_ bv0 8))))";
+        let model = parse(output).unwrap();
+        assert_eq!(model["x"], bv(8, "ff"));
+        assert_eq!(
+            to_smt2(&model).lines().nth(2).unwrap().trim(),
+            "(define-fun f () (Array (_ BitVec 8) (_ BitVec 8)) (store ((as const (Array (_ BitVec 8) (_ BitVec 8))) #x00) #x01 #x02))"
+        );
+
+        let model = parse("(= x 0b0101)\n(= p true)").unwrap();
+        assert_eq!(model["x"], bv(4, "5"));
+        assert_eq!(model["p"], Value::Bool(true));
+    }
+
+    #[test]
+    fn boolector_model() {
+        let model = parse("2 0f x\n3[00] 01 mem\n3[01] 02 mem\n").unwrap();
+        assert_eq!(model["x"], bv(8, "0f"));
+        let Value::Array {
+            entries, default, ..
+        } = &model["mem"]
+        else {
+            panic!("not an array: {:?}", model["mem"]);
+        };
+        assert_eq!(entries.len(), 2);
+        assert_eq!(default, &None);
+    }
+
+    #[test]
+    fn stp_counterexample() {
+        let output = "ASSERT( x = 0x01 );\nASSERT( y = 0b101 );\nASSERT( mem[0x00] = 0xFF );\nASSERT( NOT(p) );\nsat\n";
+        let model = parse(output).unwrap();
+        assert_eq!(model["x"], bv(8, "01"));
+        assert_eq!(
+            model["y"],
+            Value::BitVec(BitVec::from_digits("101", 2, 3).unwrap())
+        );
+        assert_eq!(model["p"], Value::Bool(false));
+        assert_eq!(
+            model["mem"].to_smt2(),
+            "(store ((as const (Array (_ BitVec 8) (_ BitVec 8))) #x00) #x00 #xff)"
+        );
+    }
+
+    #[test]
+    fn unsupported_values_are_kept() {
+        let model =
+            parse("((define-fun r () Real 0.5) (define-fun g ((a Int) (b Int)) Int a))").unwrap();
+        assert_eq!(model["r"].sort(), "Real");
+        assert_eq!(model["r"].to_smt2(), "0.5");
+        assert_eq!(model["g"].sort(), "(-> Int Int Int)");
+    }
+}
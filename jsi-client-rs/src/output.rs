@@ -0,0 +1,119 @@
+//! Machine readable output of jsif (`--json`): a single JSON object on stdout.
+
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::protocol::{Response, SolverReport};
+use crate::result::SolveResult;
+
+#[derive(Debug, Serialize)]
+pub struct JsonOutput {
+    pub result: SolveResult,
+
+    /// name of the solver that produced the result, if any
+    pub solver: Option<String>,
+
+    /// stdout of the winning solver
+    pub output: String,
+
+    /// the model part of the output, for sat results with a model (a string, or an
+    /// object with --model-format json)
+    pub model: Option<serde_json::Value>,
+
+    /// outcome of every solver
+    pub solvers: Vec<SolverReport>,
+
+    /// time between sending the request and receiving the response, in seconds
+    pub response_time: f64,
+
+    /// time spent solving (by the daemon or the local runner), in seconds
+    pub solve_time: Option<f64>,
+
+    /// time the request waited for solver slots before that (jsid only), in seconds
+    pub queue_time: Option<f64>,
+
+    /// set when the result comes from the result cache (no solver ran)
+    pub cached: bool,
+
+    pub error: Option<String>,
+}
+
+/// The model in the output of a solver: everything after the `sat` line (stp
+/// prints its counterexample without a `sat` line).
+pub fn model_text(result: SolveResult, output: &str) -> Option<String> {
+    if result != SolveResult::Sat {
+        return None;
+    }
+
+    let output = output.trim();
+    let model = match output.split_once('\n') {
+        Some((first, rest)) if first.trim() == "sat" => rest.trim(),
+        None if output == "sat" => "",
+        _ => output,
+    };
+
+    (!model.is_empty()).then(|| model.to_string())
+}
+
+impl JsonOutput {
+    pub fn new(response: Response, response_time: Duration) -> Self {
+        JsonOutput {
+            model: model_text(response.result, &response.output).map(serde_json::Value::String),
+            result: response.result,
+            solver: response.solver,
+            output: response.output,
+            solvers: response.solvers,
+            response_time: response_time.as_secs_f64(),
+            solve_time: response.elapsed,
+            queue_time: response.queue_time,
+            cached: false,
+            error: response.error,
+        }
+    }
+
+    /// Output for a request that jsif could not complete.
+    pub fn error(message: String, response_time: Duration) -> Self {
+        JsonOutput {
+            result: SolveResult::Error,
+            solver: None,
+            output: String::new(),
+            model: None,
+            solvers: Vec::new(),
+            response_time: response_time.as_secs_f64(),
+            solve_time: None,
+            queue_time: None,
+            cached: false,
+            error: Some(message),
+        }
+    }
+
+    pub fn print(&self) {
+        match serde_json::to_string(self) {
+            Ok(json) => println!("{}", json),
+            Err(err) => eprintln!("Error: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn model_after_sat_line() {
+        let output = "sat\n(\n  (define-fun x () (_ BitVec 8) #x01)\n)\n";
+        assert_eq!(
+            model_text(SolveResult::Sat, output).as_deref(),
+            Some("(\n  (define-fun x () (_ BitVec 8) #x01)\n)")
+        );
+        assert_eq!(model_text(SolveResult::Sat, "sat\n"), None);
+        assert_eq!(model_text(SolveResult::Unsat, "unsat\n"), None);
+
+        let stp = "ASSERT( x = 0x01 );\n";
+        assert_eq!(
+            model_text(SolveResult::Sat, stp).as_deref(),
+            Some("ASSERT( x = 0x01 );")
+        );
+    }
+}
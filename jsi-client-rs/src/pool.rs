@@ -0,0 +1,632 @@
+//! Pre-warmed solvers for jsid: idle processes waiting in interactive mode, so that
+//! a query doesn't pay for starting its solvers.
+//!
+//! A query is written to the stdin of one idle process per solver, followed by an
+//! `(echo ...)` of a token that marks the end of its output. The first sat/unsat
+//! answer wins, as with the runner. Processes that are done with the query get
+//! `(reset)` and go back to the pool, the others (still busy, or crashed) are
+//! killed. `Pool::maintain` replaces them in the background.
+//!
+//! Only enabled solvers with `interactive` arguments in their definition are pooled.
+//! Queries the pool can't answer like the runner would (with `model`, `full_run`
+//! or an `interval`, or with a solver that has no idle process) are left to the
+//! runner.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::fd::OwnedFd;
+use std::process::{ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use indexmap::IndexMap;
+
+use crate::definitions::Definitions;
+use crate::protocol::{Options, Response, SolverReport, PROTOCOL_VERSION};
+use crate::result::SolveResult;
+use crate::session::{command_name, split_commands};
+use crate::solvers::SolverPaths;
+use crate::supervisor::{Supervisor, DEFAULT_GRACE_PERIOD};
+
+/// How often waiting for an answer wakes up to check for cancellation.
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How often `maintain` checks for idle processes that exited.
+const MAINTAIN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Start of the tokens echoed after each query (and each reset).
+const TOKEN_PREFIX: &str = "jsif-pool-";
+
+/// A line of output of a pooled process (None once it closed its stdout), with the
+/// index of the process in the query it was sent to.
+struct Line {
+    slot: usize,
+    text: Option<String>,
+}
+
+/// Where the reader thread of a process sends its output: changed for every query.
+type Route = Arc<Mutex<(usize, Sender<Line>)>>;
+
+/// An interactive solver process. Dropping it kills the process.
+struct Warm {
+    name: String,
+
+    /// owns just this process, so that dropping it kills and reaps the process
+    _supervisor: Supervisor,
+    stdin: File,
+    route: Route,
+
+    /// cleared by the reader thread when the process closes its stdout
+    alive: Arc<AtomicBool>,
+}
+
+impl Warm {
+    fn spawn(name: &str, args: &[String]) -> io::Result<Warm> {
+        let mut supervisor = Supervisor::new(DEFAULT_GRACE_PERIOD);
+        let child = supervisor.spawn(
+            Command::new(&args[0])
+                .args(&args[1..])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null()),
+        )?;
+
+        let (Some(stdin), Some(stdout)) = supervisor.take_pipes(child) else {
+            return Err(io::Error::other("missing pipes to the solver"));
+        };
+
+        // nobody listens until the process gets a query
+        let (sender, _) = mpsc::channel();
+        let route = Arc::new(Mutex::new((0, sender)));
+        let alive = Arc::new(AtomicBool::new(true));
+        {
+            let route = route.clone();
+            let alive = alive.clone();
+            thread::spawn(move || read_lines(stdout, route, alive));
+        }
+
+        Ok(Warm {
+            name: name.to_string(),
+            _supervisor: supervisor,
+            stdin: File::from(OwnedFd::from(stdin)),
+            route,
+            alive,
+        })
+    }
+
+    fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
+    }
+
+    /// Writes `text` to the stdin of the process from another thread, so that a
+    /// solver busy with a query doesn't hold up the others.
+    fn send(&self, text: Arc<str>) -> io::Result<()> {
+        let mut stdin = self.stdin.try_clone()?;
+        thread::spawn(move || {
+            let _ = stdin.write_all(text.as_bytes());
+        });
+
+        Ok(())
+    }
+}
+
+fn read_lines(stdout: ChildStdout, route: Route, alive: Arc<AtomicBool>) {
+    let send = |text| {
+        let (slot, sender) = route.lock().unwrap().clone();
+        let _ = sender.send(Line { slot, text });
+    };
+
+    for line in BufReader::new(stdout).lines() {
+        match line {
+            Ok(line) => send(Some(line)),
+            Err(_) => break,
+        }
+    }
+
+    alive.store(false, Ordering::SeqCst);
+    send(None);
+}
+
+/// A process working on a query.
+struct PoolRun {
+    warm: Warm,
+    output: String,
+    result: Option<SolveResult>,
+    elapsed: Option<Duration>,
+}
+
+impl PoolRun {
+    fn report(&self) -> SolverReport {
+        SolverReport {
+            name: self.warm.name.clone(),
+            result: self.result.unwrap_or(SolveResult::Killed),
+            exit: None,
+            elapsed: self.elapsed.map(|elapsed| elapsed.as_secs_f64()),
+            output_file: None,
+            size: self.output.len() as u64,
+            stderr: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct PoolState {
+    idle: HashMap<String, Vec<Warm>>,
+
+    /// number of processes of each solver taken by queries in progress
+    busy: HashMap<String, usize>,
+}
+
+impl PoolState {
+    /// Number of processes of `solver` to start to fill the pool.
+    fn missing(&self, solver: &str, size: usize) -> usize {
+        let idle = self.idle.get(solver).map_or(0, Vec::len);
+        let busy = self.busy.get(solver).copied().unwrap_or(0);
+        size.saturating_sub(idle + busy)
+    }
+}
+
+/// Solver processes waiting for queries, see the module docs.
+pub struct Pool {
+    /// number of processes to keep for each solver, idle or busy with a query
+    size: usize,
+
+    /// command lines of the pooled solvers, by name
+    commands: IndexMap<String, Vec<String>>,
+    state: Mutex<PoolState>,
+
+    /// notified when processes are killed, and by `stop`
+    changed: Condvar,
+    stopping: AtomicBool,
+    next_token: AtomicU64,
+}
+
+impl Pool {
+    /// A pool of `size` processes for each enabled solver with `interactive`
+    /// arguments that was found on PATH. The processes are started by `maintain`.
+    pub fn new(size: usize, definitions: &Definitions, available_solvers: &SolverPaths) -> Pool {
+        let mut commands = IndexMap::new();
+        for (name, definition) in definitions {
+            if !definition.enabled {
+                continue;
+            }
+
+            let (Some(executable_path), Some(interactive)) = (
+                available_solvers.get(&definition.executable),
+                &definition.interactive,
+            ) else {
+                continue;
+            };
+
+            let mut args = vec![executable_path.clone()];
+            args.extend(definition.args.iter().cloned());
+            args.extend(interactive.iter().cloned());
+            commands.insert(name.clone(), args);
+        }
+
+        Pool {
+            size,
+            commands,
+            state: Mutex::new(PoolState::default()),
+            changed: Condvar::new(),
+            stopping: AtomicBool::new(false),
+            next_token: AtomicU64::new(0),
+        }
+    }
+
+    /// Names of the pooled solvers.
+    pub fn solver_names(&self) -> Vec<String> {
+        self.commands.keys().cloned().collect()
+    }
+
+    /// Number of idle processes for `solver`.
+    pub fn idle_count(&self, solver: &str) -> usize {
+        self.state
+            .lock()
+            .unwrap()
+            .idle
+            .get(solver)
+            .map_or(0, |idle| idle.iter().filter(|warm| warm.is_alive()).count())
+    }
+
+    /// Keeps the pool full until `stop` is called, replacing the processes that are
+    /// killed or that exit.
+    ///
+    /// The processes are killed when the thread that started them exits, so this
+    /// must run in a thread that lives as long as the pool.
+    pub fn maintain(&self) {
+        let mut state = self.state.lock().unwrap();
+        while !self.stopping.load(Ordering::SeqCst) {
+            for processes in state.idle.values_mut() {
+                processes.retain(Warm::is_alive);
+            }
+
+            let missing: Vec<(&String, usize)> = self
+                .commands
+                .keys()
+                .map(|name| (name, state.missing(name, self.size)))
+                .filter(|&(_, count)| count > 0)
+                .collect();
+
+            if missing.is_empty() {
+                state = self
+                    .changed
+                    .wait_timeout(state, MAINTAIN_INTERVAL)
+                    .unwrap()
+                    .0;
+                continue;
+            }
+
+            // start the processes without holding up queries
+            drop(state);
+            let mut started = Vec::new();
+            let mut failed = false;
+            for (name, count) in missing {
+                for _ in 0..count {
+                    match Warm::spawn(name, &self.commands[name]) {
+                        Ok(warm) => started.push(warm),
+                        Err(e) => {
+                            println!("error: failed to start {} for the pool: {}", name, e);
+                            failed = true;
+                        }
+                    }
+                }
+            }
+
+            state = self.state.lock().unwrap();
+            for warm in started {
+                state.idle.entry(warm.name.clone()).or_default().push(warm);
+            }
+
+            if failed {
+                state = self
+                    .changed
+                    .wait_timeout(state, MAINTAIN_INTERVAL)
+                    .unwrap()
+                    .0;
+            }
+        }
+
+        // kills the idle processes
+        state.idle.clear();
+    }
+
+    /// Makes `maintain` return.
+    pub fn stop(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        self.changed.notify_all();
+    }
+
+    /// Solves `script` with one idle process of each of `solvers`, or returns None
+    /// if the pool can't take this query (see the module docs).
+    pub fn solve(
+        &self,
+        script: &str,
+        solvers: &[String],
+        options: &Options,
+        cancel: &AtomicBool,
+    ) -> Option<Response> {
+        if options.model || options.full_run || options.interval.is_some_and(|i| i > 0.0) {
+            return None;
+        }
+
+        if solvers.is_empty() || !solvers.iter().all(|name| self.commands.contains_key(name)) {
+            return None;
+        }
+
+        // the runner would report a parse error, let it
+        let commands = split_commands(script).ok()?;
+        let mut text = String::new();
+        for command in commands {
+            if command_name(&command) != "exit" {
+                text.push_str(&command);
+                text.push('\n');
+            }
+        }
+
+        let processes = self.take(solvers)?;
+        let token = self.token();
+        text.push_str(&format!("(echo \"{}\")\n", token));
+        let text: Arc<str> = text.into();
+
+        let (sender, lines) = mpsc::channel();
+        let start = Instant::now();
+        let mut runs: Vec<PoolRun> = processes
+            .into_iter()
+            .enumerate()
+            .map(|(slot, warm)| {
+                *warm.route.lock().unwrap() = (slot, sender.clone());
+                let result = warm.send(text.clone()).err().map(|_| SolveResult::Error);
+                PoolRun {
+                    warm,
+                    output: String::new(),
+                    result,
+                    elapsed: None,
+                }
+            })
+            .collect();
+
+        // a timeout too large for a deadline is as good as no timeout
+        let deadline = options
+            .timeout
+            .filter(|&t| t > 0.0)
+            .and_then(|timeout| Duration::try_from_secs_f64(timeout).ok())
+            .and_then(|timeout| start.checked_add(timeout));
+        let mut winner = None;
+        let mut cancelled = false;
+        let mut timed_out = false;
+        while winner.is_none() && runs.iter().any(|run| run.result.is_none()) {
+            if cancel.load(Ordering::SeqCst) {
+                cancelled = true;
+                break;
+            }
+
+            let now = Instant::now();
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                timed_out = true;
+                break;
+            }
+
+            let wait = deadline.map_or(CANCEL_CHECK_INTERVAL, |deadline| {
+                (deadline - now).min(CANCEL_CHECK_INTERVAL)
+            });
+            let line = match lines.recv_timeout(wait) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+
+            let run = &mut runs[line.slot];
+            if run.result.is_some() {
+                continue;
+            }
+
+            let Some(text) = line.text else {
+                // the solver exited (or crashed) in the middle of the query
+                run.result = Some(SolveResult::from_output(&run.output, "", false));
+                run.elapsed = Some(start.elapsed());
+                continue;
+            };
+
+            let word = text.trim().trim_matches('"');
+            if word == token {
+                let result = SolveResult::from_output(&run.output, "", true);
+                run.result = Some(result);
+                run.elapsed = Some(start.elapsed());
+                if result.is_ok() {
+                    winner = Some(line.slot);
+                }
+            } else if word.starts_with(TOKEN_PREFIX) {
+                // the end of an earlier reset, anything before it is not ours
+                run.output.clear();
+            } else {
+                run.output.push_str(&text);
+                run.output.push('\n');
+            }
+        }
+
+        let result = match winner {
+            _ if cancelled => SolveResult::Killed,
+            Some(i) => runs[i].result.unwrap_or(SolveResult::Unknown),
+            None if timed_out => SolveResult::Timeout,
+            None if runs
+                .iter()
+                .any(|run| run.result == Some(SolveResult::Error)) =>
+            {
+                SolveResult::Error
+            }
+            None => SolveResult::Unknown,
+        };
+
+        for run in runs.iter_mut().filter(|run| run.result.is_none()) {
+            if timed_out {
+                run.result = Some(SolveResult::Timeout);
+            }
+        }
+
+        let winner = winner.filter(|_| !cancelled).map(|i| &runs[i]);
+        let response = Response {
+            version: PROTOCOL_VERSION,
+            result,
+            solver: winner.map(|run| run.warm.name.clone()),
+            output: winner.map(|run| run.output.clone()).unwrap_or_default(),
+            error: None,
+            elapsed: None,
+            queue_time: None,
+            cancelled,
+            solvers: runs.iter().map(PoolRun::report).collect(),
+        };
+
+        self.give_back(runs);
+        Some(response)
+    }
+
+    /// A new token to echo after a query or a reset.
+    fn token(&self) -> String {
+        let id = self.next_token.fetch_add(1, Ordering::SeqCst);
+        format!("{}{}", TOKEN_PREFIX, id)
+    }
+
+    /// Takes an idle process for each of `solvers`, or none if one of them has no
+    /// idle process left.
+    fn take(&self, solvers: &[String]) -> Option<Vec<Warm>> {
+        let mut state = self.state.lock().unwrap();
+        let mut taken: Vec<Warm> = Vec::new();
+        for name in solvers {
+            let processes = state.idle.entry(name.clone()).or_default();
+            processes.retain(Warm::is_alive);
+            match processes.pop() {
+                Some(warm) => taken.push(warm),
+                None => {
+                    for warm in taken {
+                        state.idle.entry(warm.name.clone()).or_default().push(warm);
+                    }
+                    return None;
+                }
+            }
+        }
+
+        for warm in &taken {
+            *state.busy.entry(warm.name.clone()).or_default() += 1;
+        }
+
+        Some(taken)
+    }
+
+    /// Resets the processes that are done with their query and puts them back in the
+    /// pool, and kills the others (for `maintain` to replace them).
+    fn give_back(&self, runs: Vec<PoolRun>) {
+        let mut killed = false;
+        let mut state = self.state.lock().unwrap();
+        for run in runs {
+            if let Some(busy) = state.busy.get_mut(&run.warm.name) {
+                *busy -= 1;
+            }
+
+            // only a process that echoed the token is known to be waiting for input
+            let done = run.elapsed.is_some() && run.warm.is_alive();
+            let reset = format!("(reset)\n(echo \"{}\")\n", self.token());
+            if done && run.warm.send(reset.into()).is_ok() {
+                state
+                    .idle
+                    .entry(run.warm.name.clone())
+                    .or_default()
+                    .push(run.warm);
+            } else {
+                killed = true;
+            }
+        }
+
+        drop(state);
+        if killed {
+            self.changed.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::parse_definitions;
+    use crate::wait::wait_until;
+
+    /// Answers check-sat with `answer` and echoes tokens, like an interactive solver,
+    /// and counts the queries it answered in its statistics.
+    fn fake_solver(name: &str, answer: &str) -> String {
+        let script = format!(
+            "n=0; while read -r line; do case \"$line\" in \
+             '(check-sat)') n=$((n+1)); {} ;; \
+             '(get-info :all-statistics)') echo \"(:queries $n)\" ;; \
+             '(echo '*) t=${{line#(echo \\\"}}; echo \"${{t%\\\")}}\" ;; \
+             esac; done",
+            answer
+        );
+        format!(
+            r#"{:?}: {{"executable": "sh", "model": null, "args": [],
+                "interactive": ["-c", {:?}]}}"#,
+            name, script
+        )
+    }
+
+    #[test]
+    fn reuses_processes_across_queries() {
+        let definitions = parse_definitions(&format!(
+            "{{{}, {}, {}}}",
+            fake_solver("fast", "echo unsat"),
+            fake_solver("slow", "sleep 10; echo sat"),
+            r#""plain": {"executable": "sh", "model": null, "args": []}"#
+        ))
+        .unwrap();
+        let solvers = SolverPaths::from([("sh".to_string(), "/bin/sh".to_string())]);
+        let pool = Pool::new(1, &definitions, &solvers);
+        assert_eq!(pool.solver_names(), ["fast", "slow"]);
+
+        thread::scope(|scope| {
+            scope.spawn(|| pool.maintain());
+            let full = || pool.idle_count("fast") == 1 && pool.idle_count("slow") == 1;
+            let names = pool.solver_names();
+            let cancel = AtomicBool::new(false);
+            let script = "(assert false)\n(check-sat)\n(get-info :all-statistics)\n(exit)";
+            for queries in 1..=2 {
+                assert!(wait_until(Duration::from_secs(5), full));
+                let response = pool
+                    .solve(script, &names, &Options::default(), &cancel)
+                    .unwrap();
+
+                assert_eq!(response.result, SolveResult::Unsat);
+                assert_eq!(response.solver.as_deref(), Some("fast"));
+                assert_eq!(response.output, format!("unsat\n(:queries {})\n", queries));
+                assert_eq!(response.solvers[1].result, SolveResult::Killed);
+            }
+
+            // the runner takes the queries the pool can't answer the same way
+            let plain = ["plain".to_string()];
+            assert!(pool
+                .solve("(check-sat)", &plain, &Options::default(), &cancel)
+                .is_none());
+            let model = Options {
+                model: true,
+                ..Options::default()
+            };
+            assert!(pool.solve("(check-sat)", &names, &model, &cancel).is_none());
+
+            pool.stop();
+        });
+    }
+
+    #[test]
+    fn zero_timeout_means_no_timeout() {
+        let definitions = parse_definitions(&format!(
+            "{{{}}}",
+            fake_solver("sleepy", "sleep 0.3; echo sat")
+        ))
+        .unwrap();
+        let solvers = SolverPaths::from([("sh".to_string(), "/bin/sh".to_string())]);
+        let pool = Pool::new(1, &definitions, &solvers);
+
+        thread::scope(|scope| {
+            scope.spawn(|| pool.maintain());
+            assert!(wait_until(Duration::from_secs(5), || pool
+                .idle_count("sleepy")
+                == 1));
+
+            let options = Options {
+                timeout: Some(0.0),
+                ..Options::default()
+            };
+            let response = pool
+                .solve(
+                    "(check-sat)",
+                    &pool.solver_names(),
+                    &options,
+                    &AtomicBool::new(false),
+                )
+                .unwrap();
+            assert_eq!(response.result, SolveResult::Sat);
+
+            // and so does a timeout too large for a Duration
+            assert!(wait_until(Duration::from_secs(5), || pool
+                .idle_count("sleepy")
+                == 1));
+            let options = Options {
+                timeout: Some(f64::INFINITY),
+                ..Options::default()
+            };
+            let response = pool
+                .solve(
+                    "(check-sat)",
+                    &pool.solver_names(),
+                    &options,
+                    &AtomicBool::new(false),
+                )
+                .unwrap();
+            assert_eq!(response.result, SolveResult::Sat);
+
+            pool.stop();
+        });
+    }
+}
@@ -0,0 +1,481 @@
+//! Wire format for talking to the jsi daemon.
+//!
+//! Each message is a single JSON object terminated by a newline. A client sends
+//! one `Request` per connection and the daemon answers with one `Response`.
+//! While waiting, the client can send a `Cancel` to have the solvers killed, which
+//! the daemon acknowledges with a `cancelled` response. Requests with
+//! `queue_updates` may also get `QueueUpdate` messages before the response, while
+//! they wait for solver slots (only from jsid).
+//!
+//! A request with `session` set (and no path or input) opens an incremental
+//! session instead (only with jsid): the daemon answers with a `CommandReply` once
+//! the solvers are started, then the client sends `Commands` messages with SMT-LIB
+//! commands, each answered with a `CommandReply`, until it sends `(exit)` or closes
+//! the connection. A `Cancel` stops the query in progress.
+//!
+//! The daemon still accepts the legacy format (a bare path with no newline), but
+//! that format can't carry any options.
+
+use std::io::{self, BufRead, Write};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::result::SolveResult;
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Longest time accepted for the time options (a year), so that the deadlines computed
+/// from them always fit in a `Duration`.
+pub const MAX_TIME: f64 = 365.0 * 24.0 * 60.0 * 60.0;
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn is_zero(value: &i32) -> bool {
+    *value == 0
+}
+
+/// Per-request settings, equivalent to the options of the same name in the jsi cli.
+///
+/// Unset options fall back to the config the daemon was started with.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Options {
+    /// timeout in seconds for each solver
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<f64>,
+
+    /// interval in seconds between solver starts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<f64>,
+
+    /// time in seconds between SIGTERM and SIGKILL when solvers are stopped (default 1s)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grace_period: Option<f64>,
+
+    /// run only these solvers, in the given order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sequence: Vec<String>,
+
+    /// generate a model for satisfiable instances
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub model: bool,
+
+    /// run all solvers to completion (don't stop on first result)
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub full_run: bool,
+
+    /// include the outcome of every solver in the response
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub details: bool,
+
+    /// requests with a higher priority get solver slots first (default 0)
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub priority: i32,
+
+    /// send a `QueueUpdate` whenever the position of the request in the queue changes
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub queue_updates: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub version: u32,
+
+    /// absolute path to the smt2 file to solve
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+
+    /// the SMT-LIB script to solve, sent inline instead of a path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<String>,
+
+    /// open an incremental session instead of solving a script
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub session: bool,
+
+    #[serde(flatten)]
+    pub options: Options,
+}
+
+impl Request {
+    pub fn new(path: impl Into<String>, options: Options) -> Self {
+        Request {
+            version: PROTOCOL_VERSION,
+            path: Some(path.into()),
+            input: None,
+            session: false,
+            options,
+        }
+    }
+
+    /// A request that carries the script itself, e.g. when reading from stdin.
+    pub fn inline(script: impl Into<String>, options: Options) -> Self {
+        Request {
+            version: PROTOCOL_VERSION,
+            path: None,
+            input: Some(script.into()),
+            session: false,
+            options,
+        }
+    }
+
+    /// A request that opens an incremental session with these options.
+    pub fn session(options: Options) -> Self {
+        Request {
+            version: PROTOCOL_VERSION,
+            path: None,
+            input: None,
+            session: true,
+            options,
+        }
+    }
+}
+
+/// Sent on the connection of a request in progress to kill its solvers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cancel {
+    pub version: u32,
+    pub cancel: bool,
+}
+
+impl Cancel {
+    pub fn new() -> Self {
+        Cancel {
+            version: PROTOCOL_VERSION,
+            cancel: true,
+        }
+    }
+}
+
+impl Default for Cancel {
+    fn default() -> Self {
+        Cancel::new()
+    }
+}
+
+/// Sent while a request waits for solver slots, with its position in the queue
+/// (1 for the next request to run), and with position 0 once its solvers start.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueUpdate {
+    pub version: u32,
+    pub queued: usize,
+}
+
+impl QueueUpdate {
+    pub fn new(queued: usize) -> Self {
+        QueueUpdate {
+            version: PROTOCOL_VERSION,
+            queued,
+        }
+    }
+}
+
+/// SMT-LIB commands sent on the connection of a session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Commands {
+    pub version: u32,
+    pub commands: String,
+}
+
+impl Commands {
+    pub fn new(commands: impl Into<String>) -> Self {
+        Commands {
+            version: PROTOCOL_VERSION,
+            commands: commands.into(),
+        }
+    }
+}
+
+/// The answer to `Commands`, and to the request that opens a session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandReply {
+    pub version: u32,
+
+    /// result of the last check-sat or check-sat-assuming among the commands
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<SolveResult>,
+
+    /// name of the solver that answered the last query
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub solver: Option<String>,
+
+    /// what the solvers printed for the queries (check-sat, get-model...), one
+    /// response per line, as an interactive solver would
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub output: String,
+
+    /// set when the commands could not be run (nothing was run then, or only the
+    /// commands before the one that failed)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// time spent running the commands, in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elapsed: Option<f64>,
+
+    /// set when the query in progress was cancelled (the result is then `killed`)
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub cancelled: bool,
+
+    /// solvers of the session, only in the reply that opens it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub solvers: Vec<String>,
+}
+
+impl CommandReply {
+    pub fn new() -> Self {
+        CommandReply {
+            version: PROTOCOL_VERSION,
+            result: None,
+            solver: None,
+            output: String::new(),
+            error: None,
+            elapsed: None,
+            cancelled: false,
+            solvers: Vec::new(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        CommandReply {
+            error: Some(message.into()),
+            ..CommandReply::new()
+        }
+    }
+}
+
+impl Default for CommandReply {
+    fn default() -> Self {
+        CommandReply::new()
+    }
+}
+
+/// The outcome of a single solver, same columns as the results table of the jsi cli.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SolverReport {
+    pub name: String,
+
+    pub result: SolveResult,
+
+    /// exit code of the solver (negative for a signal), if it has exited
+    #[serde(default)]
+    pub exit: Option<i32>,
+
+    /// wall clock time in seconds, if the solver has exited
+    #[serde(default)]
+    pub elapsed: Option<f64>,
+
+    /// file the solver stdout was written to
+    #[serde(default)]
+    pub output_file: Option<String>,
+
+    /// size of the solver stdout in bytes
+    #[serde(default)]
+    pub size: u64,
+
+    /// the end of the solver stderr, if it wrote anything there
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub version: u32,
+
+    pub result: SolveResult,
+
+    /// name of the solver that produced the result, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub solver: Option<String>,
+
+    /// stdout of the winning solver
+    #[serde(default)]
+    pub output: String,
+
+    /// set when the daemon could not process the request at all
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// time spent solving the request, in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elapsed: Option<f64>,
+
+    /// time the request waited for solver slots before that, in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_time: Option<f64>,
+
+    /// set when the request was cancelled (the result is then `killed`)
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub cancelled: bool,
+
+    /// outcome of every solver, only sent if the request asked for `details`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub solvers: Vec<SolverReport>,
+}
+
+/// Writes `message` as a single line of JSON and flushes the writer.
+pub fn write_message<W: Write, T: Serialize>(writer: &mut W, message: &T) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, message)?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+/// Reads a single line of JSON from `reader`.
+pub fn read_message<R: BufRead, T: DeserializeOwned>(reader: &mut R) -> io::Result<T> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "connection closed before a response was received",
+        ));
+    }
+
+    serde_json::from_str(&line).map_err(io::Error::from)
+}
+
+/// What the daemon sends on the connection of a request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    Queued(QueueUpdate),
+    Done(Response),
+}
+
+/// Reads the next `QueueUpdate` or the `Response` from `reader`.
+pub fn read_reply<R: BufRead>(reader: &mut R) -> io::Result<Reply> {
+    let message: serde_json::Value = read_message(reader)?;
+    let reply = if message.get("queued").is_some() {
+        Reply::Queued(serde_json::from_value(message)?)
+    } else {
+        Reply::Done(serde_json::from_value(message)?)
+    };
+
+    Ok(reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_omits_unset_options() {
+        let request = Request::new("/tmp/a.smt2", Options::default());
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(json, r#"{"version":1,"path":"/tmp/a.smt2"}"#);
+    }
+
+    #[test]
+    fn request_round_trip() {
+        let options = Options {
+            timeout: Some(2.5),
+            interval: Some(0.1),
+            grace_period: Some(0.5),
+            sequence: vec!["z3".into(), "yices".into()],
+            model: true,
+            full_run: true,
+            details: false,
+            priority: -1,
+            queue_updates: true,
+        };
+
+        let mut buf = Vec::new();
+        write_message(&mut buf, &Request::new("/tmp/a.smt2", options)).unwrap();
+        assert_eq!(buf.iter().filter(|&&b| b == b'\n').count(), 1);
+
+        let request: Request = read_message(&mut buf.as_slice()).unwrap();
+        assert_eq!(request.options.sequence, ["z3", "yices"]);
+        assert_eq!(request.options.timeout, Some(2.5));
+        assert_eq!(request.options.grace_period, Some(0.5));
+        assert_eq!(request.options.priority, -1);
+        assert!(request.options.model && request.options.full_run);
+    }
+
+    #[test]
+    fn inline_request_has_no_path() {
+        let script = "(set-logic QF_BV)\n(check-sat)\n";
+        let mut buf = Vec::new();
+        write_message(&mut buf, &Request::inline(script, Options::default())).unwrap();
+
+        // newlines in the script must not break the framing
+        assert_eq!(buf.iter().filter(|&&b| b == b'\n').count(), 1);
+
+        let request: Request = read_message(&mut buf.as_slice()).unwrap();
+        assert_eq!(request.path, None);
+        assert_eq!(request.input.as_deref(), Some(script));
+    }
+
+    #[test]
+    fn parse_error_response() {
+        let line = r#"{"version":1,"result":"error","error":"unknown solver: nope"}"#;
+        let response: Response = serde_json::from_str(line).unwrap();
+        assert_eq!(response.result, SolveResult::Error);
+        assert_eq!(response.error.as_deref(), Some("unknown solver: nope"));
+        assert!(response.solver.is_none() && response.output.is_empty());
+        assert!(response.solvers.is_empty());
+    }
+
+    #[test]
+    fn parse_detailed_response() {
+        let line = r#"{"version":1,"result":"sat","solver":"z3","output":"sat\n",
+            "solvers":[{"name":"z3","result":"sat","exit":0,"elapsed":0.5,
+            "output_file":"/tmp/a.smt2.z3.out","size":4},
+            {"name":"cvc5","result":"not started","exit":null,"elapsed":null,
+            "output_file":null,"size":0,"stderr":"oops\n"}]}"#;
+        let response: Response = serde_json::from_str(line).unwrap();
+        assert_eq!(response.solvers.len(), 2);
+        assert_eq!(response.solvers[0].exit, Some(0));
+        assert_eq!(response.solvers[1].result, SolveResult::NotStarted);
+        assert_eq!(response.solvers[1].elapsed, None);
+        assert_eq!(response.solvers[0].stderr, None);
+        assert_eq!(response.solvers[1].stderr.as_deref(), Some("oops\n"));
+    }
+
+    #[test]
+    fn read_reply_tells_updates_from_responses() {
+        let mut stream =
+            "{\"version\":1,\"queued\":2}\n{\"version\":1,\"result\":\"sat\"}\n".as_bytes();
+        assert_eq!(
+            read_reply(&mut stream).unwrap(),
+            Reply::Queued(QueueUpdate::new(2))
+        );
+        match read_reply(&mut stream).unwrap() {
+            Reply::Done(response) => assert_eq!(response.result, SolveResult::Sat),
+            reply => panic!("unexpected reply: {:?}", reply),
+        }
+    }
+
+    #[test]
+    fn session_messages() {
+        let request = serde_json::to_string(&Request::session(Options::default())).unwrap();
+        assert_eq!(request, r#"{"version":1,"session":true}"#);
+
+        let commands = serde_json::to_string(&Commands::new("(check-sat)")).unwrap();
+        assert_eq!(commands, r#"{"version":1,"commands":"(check-sat)"}"#);
+
+        let line = r#"{"version":1,"result":"sat","solver":"z3","output":"sat\n"}"#;
+        let reply: CommandReply = serde_json::from_str(line).unwrap();
+        assert_eq!(reply.result, Some(SolveResult::Sat));
+        assert_eq!(reply.output, "sat\n");
+
+        let reply: CommandReply = serde_json::from_str(r#"{"version":1}"#).unwrap();
+        assert_eq!(reply, CommandReply::new());
+    }
+
+    #[test]
+    fn parse_cancel_ack() {
+        let cancel = serde_json::to_string(&Cancel::new()).unwrap();
+        assert_eq!(cancel, r#"{"version":1,"cancel":true}"#);
+
+        let line = r#"{"version":1,"result":"killed","cancelled":true,"elapsed":0.1}"#;
+        let response: Response = serde_json::from_str(line).unwrap();
+        assert_eq!(response.result, SolveResult::Killed);
+        assert!(response.cancelled && response.solver.is_none());
+    }
+
+    #[test]
+    fn read_message_on_closed_connection() {
+        let err = read_message::<_, Response>(&mut &b""[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,288 @@
+//! Cleanup of solvers left behind by a jsif or jsid process that died, the native
+//! counterpart of the reaper in jsi.
+//!
+//! On Linux, solvers are started with PR_SET_PDEATHSIG (see `Supervisor::spawn`),
+//! so they get SIGKILL when the thread that started them goes away. That does not
+//! reach the helpers they forked, and is not available on other systems, so
+//! processes that call `enable` also record their running solvers in
+//! `~/.jsi/running/<pid>.json`. `reap` kills the process groups listed in the files
+//! of processes that are gone.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::{self, Command};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::daemon::pid_exists;
+
+/// How far apart (in seconds) the start time we recorded for a process and the one
+/// `ps` reports can be, for the pid to still be the same process.
+const START_TIME_TOLERANCE: u64 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SolverRecord {
+    /// file name of the solver executable
+    pub name: String,
+
+    /// process group of the solver (the pid of the solver itself)
+    pub pgid: i32,
+
+    /// when the solver was started, in seconds since the epoch
+    pub started: u64,
+}
+
+/// Contents of `~/.jsi/running/<pid>.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Record {
+    owner: i32,
+    owner_started: u64,
+    solvers: Vec<SolverRecord>,
+}
+
+struct Registry {
+    path: PathBuf,
+    record: Record,
+}
+
+impl Registry {
+    /// Best effort: a missing record only means `reap` can't clean up after us.
+    fn save(&self) {
+        if self.record.solvers.is_empty() {
+            let _ = fs::remove_file(&self.path);
+            return;
+        }
+
+        let Ok(json) = serde_json::to_string(&self.record) else {
+            return;
+        };
+
+        let temp_path = self.path.with_extension("json.tmp");
+        if fs::write(&temp_path, json).is_ok() {
+            let _ = fs::rename(&temp_path, &self.path);
+        }
+    }
+}
+
+static REGISTRY: Mutex<Option<Registry>> = Mutex::new(None);
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Starts recording the solvers of this process in `config.running_solvers`.
+pub fn enable(config: &Config) -> io::Result<()> {
+    fs::create_dir_all(&config.running_solvers)?;
+
+    let owner = process::id() as i32;
+    *REGISTRY.lock().unwrap() = Some(Registry {
+        path: config.running_solvers.join(format!("{}.json", owner)),
+        record: Record {
+            owner,
+            owner_started: process_start(owner).unwrap_or_else(now),
+            solvers: Vec::new(),
+        },
+    });
+
+    Ok(())
+}
+
+/// Adds a solver that was just started to the record (if `enable` was called).
+pub fn record(name: &str, pgid: i32) {
+    if let Some(registry) = REGISTRY.lock().unwrap().as_mut() {
+        registry.record.solvers.push(SolverRecord {
+            name: name.to_string(),
+            pgid,
+            started: now(),
+        });
+        registry.save();
+    }
+}
+
+/// Removes a solver that was reaped from the record.
+pub fn forget(pgid: i32) {
+    if let Some(registry) = REGISTRY.lock().unwrap().as_mut() {
+        registry.record.solvers.retain(|solver| solver.pgid != pgid);
+        registry.save();
+    }
+}
+
+/// Parses the `etime` column of ps (`[[dd-]hh:]mm:ss`) into seconds.
+fn parse_etime(etime: &str) -> Option<u64> {
+    let (days, time) = match etime.trim().split_once('-') {
+        Some((days, time)) => (days.parse::<u64>().ok()?, time),
+        None => (0, etime.trim()),
+    };
+
+    let mut seconds = 0;
+    for part in time.split(':') {
+        seconds = seconds * 60 + part.parse::<u64>().ok()?;
+    }
+
+    Some(days * 86400 + seconds)
+}
+
+/// When the process started, in seconds since the epoch (from ps, so that it works
+/// on macOS too).
+fn process_start(pid: i32) -> Option<u64> {
+    let output = Command::new("ps")
+        .args(["-o", "etime=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+
+    let elapsed = parse_etime(&String::from_utf8_lossy(&output.stdout))?;
+    Some(now().saturating_sub(elapsed))
+}
+
+fn started_around(pid: i32, started: u64) -> bool {
+    process_start(pid).is_some_and(|start| start.abs_diff(started) <= START_TIME_TOLERANCE)
+}
+
+/// (pid, pgid) of every process.
+fn process_groups() -> io::Result<Vec<(i32, i32)>> {
+    let output = Command::new("ps")
+        .args(["-A", "-o", "pid=,pgid="])
+        .output()?;
+    let processes = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace().map(str::parse::<i32>);
+            Some((columns.next()?.ok()?, columns.next()?.ok()?))
+        })
+        .collect();
+
+    Ok(processes)
+}
+
+/// A solver process group killed by `reap`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leftover {
+    pub solver: SolverRecord,
+
+    /// pid of the jsif or jsid process that started the solver
+    pub owner: i32,
+
+    /// number of processes in the group when it was killed
+    pub processes: usize,
+}
+
+/// Kills the solvers recorded by processes that are gone, and removes their records.
+pub fn reap(config: &Config) -> io::Result<Vec<Leftover>> {
+    let entries = match fs::read_dir(&config.running_solvers) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+
+    let groups = process_groups()?;
+    let mut leftovers = Vec::new();
+
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+
+        let Some(record) = fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice::<Record>(&data).ok())
+        else {
+            // unreadable or half written, nothing we can act on
+            continue;
+        };
+
+        if pid_exists(record.owner) && started_around(record.owner, record.owner_started) {
+            continue;
+        }
+
+        for solver in record.solvers {
+            let members: Vec<i32> = groups
+                .iter()
+                .filter(|&&(_, pgid)| pgid == solver.pgid)
+                .map(|&(pid, _)| pid)
+                .collect();
+
+            // if the solver itself is still there, make sure the pid was not reused
+            let leader_alive = members.contains(&solver.pgid);
+            if members.is_empty() || (leader_alive && !started_around(solver.pgid, solver.started))
+            {
+                continue;
+            }
+
+            // SAFETY: plain syscall
+            unsafe { libc::kill(-solver.pgid, libc::SIGKILL) };
+            leftovers.push(Leftover {
+                solver,
+                owner: record.owner,
+                processes: members.len(),
+            });
+        }
+
+        fs::remove_file(&path)?;
+    }
+
+    Ok(leftovers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_config;
+    use crate::wait::wait_until;
+    use std::os::unix::process::CommandExt;
+    use std::time::Duration;
+
+    #[test]
+    fn etime_formats() {
+        assert_eq!(parse_etime("   00:07\n"), Some(7));
+        assert_eq!(parse_etime("01:02:03"), Some(3723));
+        assert_eq!(parse_etime("2-00:00:10"), Some(2 * 86400 + 10));
+        assert_eq!(parse_etime(""), None);
+    }
+
+    #[test]
+    fn reap_kills_groups_of_dead_owners() {
+        let config = temp_config("reaper");
+        fs::create_dir_all(&config.running_solvers).unwrap();
+
+        let mut orphan = Command::new("sleep")
+            .arg("30")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let mut dead_owner = Command::new("true").spawn().unwrap();
+        dead_owner.wait().unwrap();
+
+        let record = Record {
+            owner: dead_owner.id() as i32,
+            owner_started: now(),
+            solvers: vec![SolverRecord {
+                name: "z3".into(),
+                pgid: orphan.id() as i32,
+                started: now(),
+            }],
+        };
+
+        let path = config
+            .running_solvers
+            .join(format!("{}.json", record.owner));
+        fs::write(&path, serde_json::to_string(&record).unwrap()).unwrap();
+
+        let leftovers = reap(&config).unwrap();
+        assert_eq!(leftovers.len(), 1);
+        assert_eq!(leftovers[0].solver.name, "z3");
+        assert!(!path.exists());
+        assert!(wait_until(Duration::from_secs(1), || orphan
+            .try_wait()
+            .unwrap()
+            .is_some()));
+
+        fs::remove_dir_all(&config.jsi_home).unwrap();
+    }
+}
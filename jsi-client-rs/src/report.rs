@@ -0,0 +1,196 @@
+//! Rendering of the per-solver breakdown of a response, equivalent to the results
+//! table (and `--csv` output) of the jsi cli.
+
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use crate::protocol::SolverReport;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultsFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+impl FromStr for ResultsFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(ResultsFormat::Table),
+            "csv" => Ok(ResultsFormat::Csv),
+            "json" => Ok(ResultsFormat::Json),
+            _ => Err(format!("invalid results format: {} (table, csv, json)", s)),
+        }
+    }
+}
+
+const HEADER: [&str; 6] = ["solver", "result", "exit", "time", "output file", "size"];
+
+/// Same as `readable_size` in jsi.
+fn readable_size(size: u64) -> String {
+    match size {
+        n if n < 1024 => format!("{}B", n),
+        n if n < 1024 * 1024 => format!("{:.1}KiB", n as f64 / 1024.0),
+        n => format!("{:.1}MiB", n as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+/// Solvers that found an answer first, then by elapsed time (like jsi).
+fn sorted(reports: &[SolverReport]) -> Vec<&SolverReport> {
+    let mut sorted: Vec<&SolverReport> = reports.iter().collect();
+    sorted.sort_by(|a, b| {
+        (!a.result.is_ok()).cmp(&!b.result.is_ok()).then_with(|| {
+            let (a, b) = (a.elapsed.unwrap_or(0.0), b.elapsed.unwrap_or(0.0));
+            a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+        })
+    });
+    sorted
+}
+
+fn row(report: &SolverReport, size: String) -> [String; 6] {
+    [
+        report.name.clone(),
+        report.result.to_string(),
+        report
+            .exit
+            .map_or("N/A".to_string(), |exit| exit.to_string()),
+        report
+            .elapsed
+            .map_or("N/A".to_string(), |elapsed| format!("{:.2}s", elapsed)),
+        report.output_file.clone().unwrap_or("N/A".to_string()),
+        size,
+    ]
+}
+
+fn table(reports: &[SolverReport]) -> String {
+    let rows: Vec<[String; 6]> = sorted(reports)
+        .into_iter()
+        .map(|report| row(report, readable_size(report.size)))
+        .collect();
+
+    let mut widths = HEADER.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let header = HEADER.map(String::from);
+    let mut out = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(i, (cell, width))| match i {
+                // numeric columns are right aligned, like in jsi
+                2 | 3 | 5 => format!("{:>width$}", cell),
+                _ => format!("{:width$}", cell),
+            })
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Quotes a field if needed, like python's csv module does by default.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn csv(reports: &[SolverReport]) -> String {
+    let header = HEADER.map(String::from);
+    let rows = sorted(reports)
+        .into_iter()
+        .map(|report| row(report, report.size.to_string()));
+
+    let mut out = String::new();
+    for row in std::iter::once(header).chain(rows) {
+        let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        out.push_str(&fields.join(","));
+        out.push_str("\r\n");
+    }
+
+    out
+}
+
+pub fn render(reports: &[SolverReport], format: ResultsFormat) -> String {
+    match format {
+        ResultsFormat::Table => table(reports),
+        ResultsFormat::Csv => csv(reports),
+        ResultsFormat::Json => {
+            let json = serde_json::to_string_pretty(&sorted(reports)).unwrap_or_default();
+            json + "\n"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::result::SolveResult;
+
+    fn reports() -> Vec<SolverReport> {
+        vec![
+            SolverReport {
+                name: "cvc5".into(),
+                result: SolveResult::Killed,
+                exit: Some(-15),
+                elapsed: Some(0.25),
+                output_file: Some("/tmp/a,b.smt2.cvc5.out".into()),
+                size: 0,
+                stderr: None,
+            },
+            SolverReport {
+                name: "z3".into(),
+                result: SolveResult::Sat,
+                exit: Some(0),
+                elapsed: Some(0.5),
+                output_file: Some("/tmp/a.smt2.z3.out".into()),
+                size: 2048,
+                stderr: None,
+            },
+            SolverReport {
+                name: "stp".into(),
+                result: SolveResult::NotStarted,
+                exit: None,
+                elapsed: None,
+                output_file: None,
+                size: 0,
+                stderr: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn table_puts_winner_first() {
+        let table = render(&reports(), ResultsFormat::Table);
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("solver  result"));
+        assert!(lines[1].starts_with("z3  "));
+        assert!(lines[1].ends_with("2.0KiB"));
+        assert!(lines[3].starts_with("cvc5"));
+        assert!(lines[2].contains("N/A"));
+    }
+
+    #[test]
+    fn csv_quotes_fields() {
+        let csv = render(&reports(), ResultsFormat::Csv);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "solver,result,exit,time,output file,size");
+        assert_eq!(lines[1], "z3,sat,0,0.50s,/tmp/a.smt2.z3.out,2048");
+        assert_eq!(lines[2], "stp,not started,N/A,N/A,N/A,0");
+        assert_eq!(
+            lines[3],
+            "cvc5,killed,-15,0.25s,\"/tmp/a,b.smt2.cvc5.out\",0"
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! Typed solver results, mirroring `TaskResult` in jsi.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Exit code when jsif itself fails (bad arguments, daemon unreachable, bad response).
+pub const EXIT_FAILURE: i32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SolveResult {
+    Sat,
+    Unsat,
+    Error,
+    Unknown,
+    Timeout,
+    Killed,
+    #[serde(rename = "not started")]
+    NotStarted,
+}
+
+impl SolveResult {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SolveResult::Sat => "sat",
+            SolveResult::Unsat => "unsat",
+            SolveResult::Error => "error",
+            SolveResult::Unknown => "unknown",
+            SolveResult::Timeout => "timeout",
+            SolveResult::Killed => "killed",
+            SolveResult::NotStarted => "not started",
+        }
+    }
+
+    /// True if the result is a definitive answer (sat or unsat).
+    pub fn is_ok(&self) -> bool {
+        matches!(self, SolveResult::Sat | SolveResult::Unsat)
+    }
+
+    /// Classifies the output of a solver that ran to completion, using the same
+    /// rules as `Command._get_result` in jsi.
+    ///
+    /// We can't just rely on the exit code here because:
+    /// - stp can return 0 when it fails to parse the input file
+    /// - boolector returns non 0 even when it's happy
+    pub fn from_output(stdout: &str, stderr: &str, exit_success: bool) -> Self {
+        if stdout.is_empty() {
+            if stderr.contains("error") || !exit_success {
+                return SolveResult::Error;
+            }
+
+            return SolveResult::Unknown;
+        }
+
+        let line = stdout.lines().next().unwrap_or_default().trim();
+        match line {
+            "sat" => SolveResult::Sat,
+            "unsat" => SolveResult::Unsat,
+            _ if line.contains("error") => SolveResult::Error,
+
+            // stp may not return sat as the first line when there is a counterexample
+            _ if line.contains("ASSERT(") => SolveResult::Sat,
+            _ => SolveResult::Unknown,
+        }
+    }
+
+    /// Process exit code for this result, using SMT-COMP conventions for sat/unsat.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SolveResult::Sat => 10,
+            SolveResult::Unsat => 20,
+            SolveResult::Unknown => 30,
+            SolveResult::Timeout => 31,
+            SolveResult::Killed => 32,
+            SolveResult::NotStarted => 33,
+            SolveResult::Error => 40,
+        }
+    }
+}
+
+impl fmt::Display for SolveResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SolveResult {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sat" => Ok(SolveResult::Sat),
+            "unsat" => Ok(SolveResult::Unsat),
+            "error" => Ok(SolveResult::Error),
+            "unknown" => Ok(SolveResult::Unknown),
+            "timeout" => Ok(SolveResult::Timeout),
+            "killed" => Ok(SolveResult::Killed),
+            "not started" => Ok(SolveResult::NotStarted),
+            _ => Err(format!("unknown result: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [SolveResult; 7] = [
+        SolveResult::Sat,
+        SolveResult::Unsat,
+        SolveResult::Error,
+        SolveResult::Unknown,
+        SolveResult::Timeout,
+        SolveResult::Killed,
+        SolveResult::NotStarted,
+    ];
+
+    #[test]
+    fn serde_matches_display() {
+        for result in ALL {
+            let json = serde_json::to_string(&result).unwrap();
+            assert_eq!(json, format!("\"{}\"", result));
+            assert_eq!(result.as_str().parse::<SolveResult>(), Ok(result));
+        }
+    }
+
+    #[test]
+    fn from_output_first_line_rules() {
+        assert_eq!(
+            SolveResult::from_output("sat\n", "", true),
+            SolveResult::Sat
+        );
+        assert_eq!(
+            SolveResult::from_output("unsat", "", true),
+            SolveResult::Unsat
+        );
+        assert_eq!(
+            SolveResult::from_output(" sat \n(model)", "", false),
+            SolveResult::Sat
+        );
+        assert_eq!(
+            SolveResult::from_output("ASSERT( x = 0x01 );\n", "", true),
+            SolveResult::Sat
+        );
+        assert_eq!(
+            SolveResult::from_output("(error \"line 1\")", "", true),
+            SolveResult::Error
+        );
+        assert_eq!(
+            SolveResult::from_output("unknown\n", "", true),
+            SolveResult::Unknown
+        );
+        assert_eq!(SolveResult::from_output("", "", true), SolveResult::Unknown);
+        assert_eq!(SolveResult::from_output("", "", false), SolveResult::Error);
+        assert_eq!(
+            SolveResult::from_output("", "parse error", true),
+            SolveResult::Error
+        );
+    }
+
+    #[test]
+    fn exit_codes_are_distinct() {
+        let mut codes: Vec<i32> = ALL.iter().map(|r| r.exit_code()).collect();
+        codes.push(EXIT_FAILURE);
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), ALL.len() + 1);
+        assert_eq!(SolveResult::Sat.exit_code(), 10);
+        assert_eq!(SolveResult::Unsat.exit_code(), 20);
+    }
+}
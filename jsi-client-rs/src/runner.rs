@@ -0,0 +1,820 @@
+//! Native portfolio runner: launches solvers in parallel without going through the
+//! daemon, takes the first sat/unsat answer and kills the rest.
+//!
+//! This follows the behavior of `ProcessController` in jsi: solver output is written
+//! to `<input>.<solver>.out` and `<input>.<solver>.err` next to the input file.
+//! Solvers run under a `Supervisor`, in their own process group.
+
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::definitions::{enabled_solvers, Definitions};
+use crate::protocol::{Options, Response, SolverReport, PROTOCOL_VERSION};
+use crate::result::SolveResult;
+use crate::solvers::SolverPaths;
+use crate::supervisor::{ChildId, Supervisor, DEFAULT_GRACE_PERIOD};
+
+/// How often the run loop wakes up to check for cancellation (solver exits, start
+/// times and timeouts wake it up on their own).
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How much of the end of the solver stderr goes in the reports (same as the daemon).
+const STDERR_EXCERPT_SIZE: usize = 1024;
+
+/// The outcome of a single solver.
+#[derive(Debug, Clone)]
+pub struct SolverRun {
+    pub name: String,
+    pub result: SolveResult,
+    pub stdout: String,
+
+    /// exit code (negative for a signal, like Popen.returncode), if it exited
+    pub exit: Option<i32>,
+    pub elapsed: Option<Duration>,
+    pub stdout_path: PathBuf,
+
+    /// the end of the stderr, if the solver wrote anything there
+    pub stderr: Option<String>,
+}
+
+impl SolverRun {
+    pub fn report(&self) -> SolverReport {
+        SolverReport {
+            name: self.name.clone(),
+            result: self.result,
+            exit: self.exit,
+            elapsed: self.elapsed.map(|elapsed| elapsed.as_secs_f64()),
+            output_file: Some(self.stdout_path.to_string_lossy().into_owned()),
+            size: self.stdout.len() as u64,
+            stderr: self.stderr.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RunOutcome {
+    pub result: SolveResult,
+
+    /// index in `runs` of the solver that produced the result, if any
+    pub winner: Option<usize>,
+    pub runs: Vec<SolverRun>,
+
+    /// set if the run was cancelled before it was done (the result is then `killed`)
+    pub cancelled: bool,
+}
+
+impl RunOutcome {
+    /// The response the daemon would have sent for this run (with `details`).
+    pub fn into_response(self) -> Response {
+        let solvers = self.runs.iter().map(SolverRun::report).collect();
+        let winner = self
+            .winner
+            .filter(|_| !self.cancelled)
+            .map(|i| &self.runs[i]);
+
+        Response {
+            version: PROTOCOL_VERSION,
+            result: self.result,
+            solver: winner.map(|run| run.name.clone()),
+            output: winner.map(|run| run.stdout.clone()).unwrap_or_default(),
+            error: None,
+            elapsed: None,
+            queue_time: None,
+            cancelled: self.cancelled,
+            solvers,
+        }
+    }
+}
+
+struct Process {
+    name: String,
+    args: Vec<String>,
+    stdout_path: PathBuf,
+    stderr_path: PathBuf,
+    start_at: Duration,
+    child: Option<ChildId>,
+    start_time: Option<Instant>,
+    end_time: Option<Instant>,
+    status: Option<ExitStatus>,
+    timed_out: bool,
+    /// When a timed out process gets SIGKILL if SIGTERM didn't stop it.
+    kill_at: Option<Instant>,
+    launched: bool,
+    reported: bool,
+    result: Option<SolveResult>,
+}
+
+impl Process {
+    fn running(&self) -> bool {
+        self.child.is_some() && self.status.is_none()
+    }
+
+    fn start(&mut self, supervisor: &mut Supervisor) -> io::Result<()> {
+        self.launched = true;
+        let stdout = File::create(&self.stdout_path)?;
+        let stderr = File::create(&self.stderr_path)?;
+
+        self.start_time = Some(Instant::now());
+        let child = supervisor.spawn(
+            Command::new(&self.args[0])
+                .args(&self.args[1..])
+                .stdin(Stdio::null())
+                .stdout(stdout)
+                .stderr(stderr),
+        )?;
+
+        self.child = Some(child);
+        Ok(())
+    }
+
+    /// Checks if the supervisor reaped the process, returns true if it did.
+    fn poll(&mut self, supervisor: &Supervisor) -> bool {
+        let Some(child) = self.child else {
+            return false;
+        };
+
+        if self.status.is_none() {
+            self.status = supervisor.status(child);
+            self.end_time = supervisor.exited_at(child);
+        }
+
+        self.status.is_some()
+    }
+
+    fn result(&mut self) -> SolveResult {
+        if let Some(result) = self.result {
+            return result;
+        }
+
+        let Some(status) = self.status else {
+            return SolveResult::NotStarted;
+        };
+
+        let result = if self.timed_out {
+            SolveResult::Timeout
+        } else if matches!(status.signal(), Some(libc::SIGTERM | libc::SIGKILL)) {
+            SolveResult::Killed
+        } else {
+            let stdout = fs::read_to_string(&self.stdout_path).unwrap_or_default();
+            let stderr = fs::read_to_string(&self.stderr_path).unwrap_or_default();
+            SolveResult::from_output(&stdout, &stderr, status.success())
+        };
+
+        self.result = Some(result);
+        result
+    }
+
+    fn into_run(mut self) -> SolverRun {
+        let result = self.result();
+        let stdout = match self.status {
+            Some(_) => fs::read_to_string(&self.stdout_path).unwrap_or_default(),
+            None => String::new(),
+        };
+
+        let stderr = match self.status {
+            Some(_) => fs::read_to_string(&self.stderr_path).unwrap_or_default(),
+            None => String::new(),
+        };
+
+        let elapsed = match (self.start_time, self.end_time) {
+            (Some(start), Some(end)) => Some(end - start),
+            _ => None,
+        };
+
+        SolverRun {
+            name: self.name,
+            result,
+            stdout,
+            exit: self
+                .status
+                .and_then(|status| status.code().or(status.signal().map(|signal| -signal))),
+            elapsed,
+            stdout_path: self.stdout_path,
+            stderr: excerpt(&stderr),
+        }
+    }
+}
+
+/// The last `STDERR_EXCERPT_SIZE` bytes of `text` (on a char boundary), or None if
+/// there is nothing but whitespace.
+fn excerpt(text: &str) -> Option<String> {
+    if text.trim().is_empty() {
+        return None;
+    }
+
+    let mut start = text.len().saturating_sub(STDERR_EXCERPT_SIZE);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+
+    Some(text[start..].to_string())
+}
+
+pub(crate) fn solver_names(options: &Options, definitions: &Definitions) -> Vec<String> {
+    if options.sequence.is_empty() {
+        enabled_solvers(definitions)
+    } else {
+        options.sequence.clone()
+    }
+}
+
+/// The solvers a run with these options would start.
+pub(crate) fn available_solver_names(
+    options: &Options,
+    definitions: &Definitions,
+    available_solvers: &SolverPaths,
+) -> Vec<String> {
+    solver_names(options, definitions)
+        .into_iter()
+        .filter(|name| {
+            definitions
+                .get(name)
+                .is_some_and(|definition| available_solvers.contains_key(&definition.executable))
+        })
+        .collect()
+}
+
+/// How many solvers a run with these options would start (e.g. to ask a `Scheduler`
+/// for as many slots).
+pub fn solver_count(
+    options: &Options,
+    definitions: &Definitions,
+    available_solvers: &SolverPaths,
+) -> usize {
+    available_solver_names(options, definitions, available_solvers).len()
+}
+
+fn invalid_time(option: &str, value: Option<f64>) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("invalid {} value: {}", option, value.unwrap_or_default()),
+    )
+}
+
+/// Builds the processes to run, equivalent to `base_commands` + `set_input_output`.
+fn build_processes(
+    input: &Path,
+    options: &Options,
+    definitions: &Definitions,
+    available_solvers: &SolverPaths,
+) -> io::Result<Vec<Process>> {
+    let solver_names = solver_names(options, definitions);
+
+    let output_dir = input.parent().unwrap_or(Path::new("."));
+    let basename = input.file_name().unwrap_or_default().to_string_lossy();
+    let interval = Duration::try_from_secs_f64(options.interval.unwrap_or_default())
+        .map_err(|_| invalid_time("interval", options.interval))?;
+
+    let mut processes = Vec::new();
+    for name in solver_names {
+        let definition = definitions.get(&name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown solver: {}", name),
+            )
+        })?;
+
+        let Some(executable_path) = available_solvers.get(&definition.executable) else {
+            continue;
+        };
+
+        let mut args = vec![executable_path.clone()];
+
+        // append the model option if requested
+        if let (true, Some(model_arg)) = (options.model, &definition.model) {
+            args.push(model_arg.clone());
+        }
+
+        // append solver-specific extra arguments
+        args.extend(definition.args.iter().cloned());
+        args.push(input.to_string_lossy().into_owned());
+
+        processes.push(Process {
+            stdout_path: output_dir.join(format!("{}.{}.out", basename, name)),
+            stderr_path: output_dir.join(format!("{}.{}.err", basename, name)),
+            start_at: interval
+                .checked_mul(processes.len() as u32)
+                .unwrap_or(Duration::MAX),
+            name,
+            args,
+            child: None,
+            start_time: None,
+            end_time: None,
+            status: None,
+            timed_out: false,
+            kill_at: None,
+            launched: false,
+            reported: false,
+            result: None,
+        });
+    }
+
+    if processes.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no solvers to run (none of the requested solvers were found on PATH)",
+        ));
+    }
+
+    Ok(processes)
+}
+
+/// Sends SIGTERM to the process group of every running process, then SIGKILL to
+/// those that are still running after the grace period.
+fn kill_all(supervisor: &mut Supervisor, processes: &mut [Process]) -> io::Result<()> {
+    let running: Vec<ChildId> = processes
+        .iter()
+        .filter(|process| process.running())
+        .filter_map(|process| process.child)
+        .collect();
+
+    supervisor.terminate(&running)?;
+    for process in processes.iter_mut() {
+        process.poll(supervisor);
+    }
+
+    Ok(())
+}
+
+/// Runs the portfolio on `input`.
+///
+/// `on_exit` is called with the name and result of each solver as it finishes.
+pub fn run(
+    input: &Path,
+    options: &Options,
+    definitions: &Definitions,
+    available_solvers: &SolverPaths,
+    on_exit: impl FnMut(&str, SolveResult),
+) -> io::Result<RunOutcome> {
+    let never = AtomicBool::new(false);
+    run_until(
+        input,
+        options,
+        definitions,
+        available_solvers,
+        &never,
+        usize::MAX,
+        on_exit,
+    )
+}
+
+/// Same as `run`, but kills the solvers and returns as soon as `cancel` is set, and
+/// runs at most `max_running` solvers at a time (the others start as they finish).
+pub fn run_until(
+    input: &Path,
+    options: &Options,
+    definitions: &Definitions,
+    available_solvers: &SolverPaths,
+    cancel: &AtomicBool,
+    max_running: usize,
+    mut on_exit: impl FnMut(&str, SolveResult),
+) -> io::Result<RunOutcome> {
+    let mut processes = build_processes(input, options, definitions, available_solvers)?;
+    // a timeout too large for a Duration is as good as no timeout
+    let timeout = options
+        .timeout
+        .filter(|&t| t > 0.0)
+        .and_then(|t| Duration::try_from_secs_f64(t).ok());
+
+    let grace_period = match options.grace_period.filter(|&t| t >= 0.0) {
+        Some(t) => Duration::try_from_secs_f64(t)
+            .map_err(|_| invalid_time("grace_period", options.grace_period))?,
+        None => DEFAULT_GRACE_PERIOD,
+    };
+
+    let mut supervisor = Supervisor::new(grace_period);
+    let start = Instant::now();
+    let mut winner: Option<usize> = None;
+    let mut cancelled = false;
+
+    loop {
+        if cancel.load(Ordering::SeqCst) {
+            kill_all(&mut supervisor, &mut processes)?;
+            cancelled = true;
+            break;
+        }
+
+        let now = Instant::now();
+        let mut pending = false;
+        let mut wake_at = now + CANCEL_CHECK_INTERVAL;
+        let mut running = processes
+            .iter()
+            .filter(|process| process.child.is_some() && !process.reported)
+            .count();
+
+        for (i, process) in processes.iter_mut().enumerate() {
+            // launch processes as their start time comes up, unless we're done
+            if !process.launched {
+                if winner.is_some() && !options.full_run {
+                    continue;
+                }
+
+                if now - start < process.start_at {
+                    pending = true;
+                    if let Some(start_at) = start.checked_add(process.start_at) {
+                        wake_at = wake_at.min(start_at);
+                    }
+                    continue;
+                }
+
+                // wait for a solver to exit (which wakes us up)
+                if running >= max_running {
+                    pending = true;
+                    continue;
+                }
+
+                match process.start(&mut supervisor) {
+                    Ok(()) => running += 1,
+                    Err(err) => {
+                        eprintln!("error: failed to start {}: {}", process.name, err);
+                        process.result = Some(SolveResult::Error);
+                    }
+                }
+            }
+
+            if process.reported {
+                continue;
+            }
+
+            if process.running() && !process.poll(&supervisor) {
+                pending = true;
+                let child = process.child.expect("running process has a child");
+                if process.timed_out {
+                    // SIGTERM was sent, the supervisor reaps it once it exits
+                    if process.kill_at.is_some_and(|kill_at| now >= kill_at) {
+                        supervisor.signal(child, libc::SIGKILL);
+                        process.kill_at = None;
+                    }
+                    if let Some(kill_at) = process.kill_at {
+                        wake_at = wake_at.min(kill_at);
+                    }
+                    continue;
+                }
+
+                let started = process.start_time.unwrap_or(now);
+                if timeout.is_none_or(|t| now - started < t) {
+                    if let Some(deadline) = timeout.and_then(|t| started.checked_add(t)) {
+                        wake_at = wake_at.min(deadline);
+                    }
+                    continue;
+                }
+
+                // don't wait out the grace period here, the other solvers keep running
+                process.timed_out = true;
+                supervisor.signal(child, libc::SIGTERM);
+                process.kill_at = now.checked_add(grace_period);
+                if let Some(kill_at) = process.kill_at {
+                    wake_at = wake_at.min(kill_at);
+                }
+                continue;
+            }
+
+            process.reported = true;
+            if process.child.is_some() {
+                running -= 1;
+            }
+
+            let result = process.result();
+            on_exit(&process.name, result);
+
+            if result.is_ok() && winner.is_none() {
+                winner = Some(i);
+            }
+        }
+
+        if winner.is_some() && !options.full_run {
+            kill_all(&mut supervisor, &mut processes)?;
+            break;
+        }
+
+        if !pending {
+            break;
+        }
+
+        supervisor.wait(Some(wake_at.saturating_duration_since(Instant::now())))?;
+    }
+
+    let runs: Vec<SolverRun> = processes.into_iter().map(Process::into_run).collect();
+    let result = match winner {
+        _ if cancelled => SolveResult::Killed,
+        Some(i) => runs[i].result,
+        None if runs.iter().any(|r| r.result == SolveResult::Timeout) => SolveResult::Timeout,
+        None if runs.iter().any(|r| r.result == SolveResult::Error) => SolveResult::Error,
+        None => SolveResult::Unknown,
+    };
+
+    Ok(RunOutcome {
+        result,
+        winner,
+        runs,
+        cancelled,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::SolverDefinition;
+    use crate::testing::temp_config;
+
+    /// A solver running `script` with sh, the input file is `$1`.
+    fn fake_solver(script: &str) -> SolverDefinition {
+        SolverDefinition {
+            executable: "sh".to_string(),
+            model: None,
+            args: vec!["-c".to_string(), script.to_string(), "fake".to_string()],
+            interactive: None,
+            enabled: true,
+            meta: None,
+        }
+    }
+
+    /// Runs the solvers on an input in a fresh directory, with the callback results.
+    fn run_solvers(
+        name: &str,
+        solvers: &[(&str, &str)],
+        options: &Options,
+    ) -> (io::Result<RunOutcome>, Vec<(String, SolveResult)>) {
+        let dir = temp_config(name).jsi_home;
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("query.smt2");
+        fs::write(&input, "(check-sat)\n").unwrap();
+
+        let definitions: Definitions = solvers
+            .iter()
+            .map(|(name, script)| (name.to_string(), fake_solver(script)))
+            .collect();
+        let available_solvers = SolverPaths::from([("sh".to_string(), "/bin/sh".to_string())]);
+
+        let mut exits = Vec::new();
+        let outcome = run(
+            &input,
+            options,
+            &definitions,
+            &available_solvers,
+            |name, result| exits.push((name.to_string(), result)),
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+        (outcome, exits)
+    }
+
+    fn results(outcome: &RunOutcome) -> Vec<(&str, SolveResult)> {
+        outcome
+            .runs
+            .iter()
+            .map(|run| (run.name.as_str(), run.result))
+            .collect()
+    }
+
+    #[test]
+    fn first_result_wins() {
+        let start = Instant::now();
+        let (outcome, exits) = run_solvers(
+            "runner-first",
+            &[
+                ("broken", "echo 'no such option' >&2; exit 1"),
+                ("slow", "sleep 10; echo unsat"),
+                ("fast", "sleep 0.2; cat \"$1\" >/dev/null && echo sat"),
+            ],
+            &Options::default(),
+        );
+        let outcome = outcome.unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(outcome.result, SolveResult::Sat);
+        assert_eq!(outcome.winner, Some(2));
+        assert_eq!(
+            results(&outcome),
+            [
+                ("broken", SolveResult::Error),
+                ("slow", SolveResult::Killed),
+                ("fast", SolveResult::Sat)
+            ]
+        );
+        assert_eq!(outcome.runs[0].stderr.as_deref(), Some("no such option\n"));
+        assert_eq!(outcome.runs[2].stdout, "sat\n");
+        assert_eq!(outcome.runs[0].exit, Some(1));
+        assert_eq!(outcome.runs[1].exit, Some(-libc::SIGTERM));
+
+        // the winner is reported before the solvers killed after it
+        assert_eq!(exits[0], ("broken".to_string(), SolveResult::Error));
+        assert_eq!(exits[1], ("fast".to_string(), SolveResult::Sat));
+
+        let response = outcome.into_response();
+        assert_eq!(response.solver.as_deref(), Some("fast"));
+        assert_eq!(response.output, "sat\n");
+    }
+
+    #[test]
+    fn solvers_time_out() {
+        let options = Options {
+            timeout: Some(0.2),
+            ..Options::default()
+        };
+        let start = Instant::now();
+        let (outcome, _) = run_solvers(
+            "runner-timeout",
+            &[("slow", "sleep 10; echo sat"), ("unsure", "echo unknown")],
+            &options,
+        );
+        let outcome = outcome.unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(outcome.result, SolveResult::Timeout);
+        assert_eq!(outcome.winner, None);
+        assert_eq!(
+            results(&outcome),
+            [
+                ("slow", SolveResult::Timeout),
+                ("unsure", SolveResult::Unknown)
+            ]
+        );
+
+        // 0 means no timeout, as in jsi
+        let options = Options {
+            timeout: Some(0.0),
+            ..Options::default()
+        };
+        let (outcome, _) = run_solvers(
+            "runner-no-timeout",
+            &[("slow", "sleep 0.3; echo sat")],
+            &options,
+        );
+        assert_eq!(outcome.unwrap().result, SolveResult::Sat);
+
+        // and so does a timeout too large for a Duration
+        let options = Options {
+            timeout: Some(f64::INFINITY),
+            ..Options::default()
+        };
+        let (outcome, _) = run_solvers("runner-huge-timeout", &[("fast", "echo sat")], &options);
+        assert_eq!(outcome.unwrap().result, SolveResult::Sat);
+    }
+
+    #[test]
+    fn timed_out_solver_does_not_hold_up_the_others() {
+        // the first solver ignores SIGTERM, the second answers during its grace period
+        let options = Options {
+            sequence: vec!["stubborn".into(), "late".into()],
+            interval: Some(0.3),
+            timeout: Some(0.2),
+            grace_period: Some(2.0),
+            ..Options::default()
+        };
+        let (outcome, exits) = run_solvers(
+            "runner-grace",
+            &[("stubborn", "trap '' TERM; sleep 10"), ("late", "echo sat")],
+            &options,
+        );
+        let outcome = outcome.unwrap();
+
+        assert_eq!(outcome.result, SolveResult::Sat);
+        assert_eq!(
+            results(&outcome),
+            [
+                ("stubborn", SolveResult::Timeout),
+                ("late", SolveResult::Sat)
+            ]
+        );
+        assert_eq!(outcome.runs[0].exit, Some(-libc::SIGKILL));
+        assert_eq!(exits, [("late".to_string(), SolveResult::Sat)]);
+
+        // once the grace period is over, the timed out solver is killed and reported
+        let options = Options {
+            timeout: Some(0.2),
+            grace_period: Some(0.3),
+            ..Options::default()
+        };
+        let start = Instant::now();
+        let (outcome, exits) = run_solvers(
+            "runner-grace-kill",
+            &[("stubborn", "trap '' TERM; sleep 10")],
+            &options,
+        );
+        let outcome = outcome.unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(outcome.result, SolveResult::Timeout);
+        assert_eq!(outcome.runs[0].exit, Some(-libc::SIGKILL));
+        assert_eq!(exits, [("stubborn".to_string(), SolveResult::Timeout)]);
+    }
+
+    #[test]
+    fn sequence_and_interval() {
+        let solvers = [
+            ("a", "sleep 10; echo sat"),
+            ("b", "sleep 0.1; echo unsat"),
+            ("c", "sleep 10; echo sat"),
+        ];
+
+        // only the solvers of the sequence run, in its order, one interval apart
+        let options = Options {
+            sequence: vec!["b".into(), "a".into()],
+            interval: Some(0.5),
+            ..Options::default()
+        };
+        let (outcome, exits) = run_solvers("runner-sequence", &solvers, &options);
+        let outcome = outcome.unwrap();
+        assert_eq!(outcome.result, SolveResult::Unsat);
+        assert_eq!(
+            results(&outcome),
+            [("b", SolveResult::Unsat), ("a", SolveResult::NotStarted)]
+        );
+        assert_eq!(outcome.runs[1].exit, None);
+        assert_eq!(exits, [("b".to_string(), SolveResult::Unsat)]);
+
+        // the next solver starts after the interval if there is no answer yet
+        let options = Options {
+            sequence: vec!["c".into(), "b".into()],
+            interval: Some(0.3),
+            ..Options::default()
+        };
+        let start = Instant::now();
+        let (outcome, _) = run_solvers("runner-interval", &solvers, &options);
+        let outcome = outcome.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(400));
+        assert_eq!(
+            results(&outcome),
+            [("c", SolveResult::Killed), ("b", SolveResult::Unsat)]
+        );
+
+        // solvers whose start time is out of reach never start
+        let options = Options {
+            sequence: vec!["b".into(), "a".into()],
+            interval: Some(1e18),
+            ..Options::default()
+        };
+        let (outcome, _) = run_solvers("runner-far-interval", &solvers, &options);
+        assert_eq!(
+            results(&outcome.unwrap()),
+            [("b", SolveResult::Unsat), ("a", SolveResult::NotStarted)]
+        );
+
+        for (option, options) in [
+            (
+                "interval",
+                Options {
+                    interval: Some(f64::INFINITY),
+                    ..Options::default()
+                },
+            ),
+            (
+                "grace_period",
+                Options {
+                    grace_period: Some(f64::INFINITY),
+                    ..Options::default()
+                },
+            ),
+        ] {
+            let (outcome, _) = run_solvers("runner-bad-time", &solvers, &options);
+            let error = outcome.unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+            assert_eq!(error.to_string(), format!("invalid {} value: inf", option));
+        }
+
+        let options = Options {
+            sequence: vec!["b".into(), "z3".into()],
+            ..Options::default()
+        };
+        let (outcome, _) = run_solvers("runner-unknown", &solvers, &options);
+        let error = outcome.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(error.to_string(), "unknown solver: z3");
+    }
+
+    #[test]
+    fn full_run_waits_for_every_solver() {
+        let options = Options {
+            full_run: true,
+            ..Options::default()
+        };
+        let (outcome, exits) = run_solvers(
+            "runner-full",
+            &[
+                ("fast", "echo sat"),
+                ("slow", "sleep 0.3; echo sat"),
+                ("unsure", "sleep 0.1; echo unknown"),
+            ],
+            &options,
+        );
+        let outcome = outcome.unwrap();
+
+        assert_eq!(outcome.result, SolveResult::Sat);
+        assert_eq!(outcome.winner, Some(0));
+        assert_eq!(
+            results(&outcome),
+            [
+                ("fast", SolveResult::Sat),
+                ("slow", SolveResult::Sat),
+                ("unsure", SolveResult::Unknown)
+            ]
+        );
+        assert_eq!(exits.len(), 3);
+    }
+}
@@ -0,0 +1,329 @@
+//! Solver slot budget shared by concurrent runs, so that N requests on a machine
+//! with C cores don't start N times every solver at once.
+//!
+//! A run asks for one slot per solver it wants to start (capped at the budget), and
+//! waits in a queue until they are free. The queue is ordered by priority (higher
+//! first), then by arrival, and only the head of the queue can take slots: a large
+//! run is not starved by smaller ones that arrived after it.
+//!
+//! With `with_shared_slots`, the budget is also shared with the other processes
+//! (jsif runs and jsid) using the same directory (~/.jsi/slots): slot i is taken by
+//! holding an flock on the file `i` in it, so the slots of a process that dies are
+//! freed by the kernel. The head of the queue only gets its slots once it can lock
+//! that many of the first `slots` files, retrying until it does; there is no
+//! ordering between processes. The python daemon doesn't take part.
+
+use std::fs::{self, File, OpenOptions};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How often waiters wake up to check if their request was cancelled.
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug)]
+struct Waiter {
+    ticket: u64,
+    priority: i32,
+}
+
+#[derive(Debug)]
+struct State {
+    free: usize,
+    next_ticket: u64,
+
+    /// sorted by priority (descending), then ticket
+    queue: Vec<Waiter>,
+}
+
+impl State {
+    fn position(&self, ticket: u64) -> usize {
+        self.queue
+            .iter()
+            .position(|waiter| waiter.ticket == ticket)
+            .expect("waiters stay in the queue until they leave it")
+    }
+
+    fn leave(&mut self, ticket: u64) {
+        let position = self.position(ticket);
+        self.queue.remove(position);
+    }
+}
+
+/// Locks `wanted` of the first `budget` slot files in `dir`, or none of them.
+///
+/// The budget can't be enforced without the directory, so failing to create it
+/// gives the slots without locks.
+fn lock_slot_files(dir: &Path, wanted: usize, budget: usize) -> Option<Vec<File>> {
+    if fs::create_dir_all(dir).is_err() {
+        return Some(Vec::new());
+    }
+
+    let mut locks = Vec::new();
+    for slot in 0..budget {
+        if locks.len() == wanted {
+            break;
+        }
+
+        let Ok(file) = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join(slot.to_string()))
+        else {
+            continue;
+        };
+
+        // SAFETY: plain syscall on a file we own, the lock goes away when it is closed
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+            locks.push(file);
+        }
+    }
+
+    (locks.len() == wanted).then_some(locks)
+}
+
+#[derive(Debug)]
+pub struct Scheduler {
+    slots: usize,
+    state: Mutex<State>,
+    changed: Condvar,
+
+    /// directory of the slot files shared with other processes
+    shared: Option<PathBuf>,
+}
+
+/// Slots held by a run, given back when dropped.
+#[derive(Debug)]
+pub struct Permit<'a> {
+    scheduler: &'a Scheduler,
+    slots: usize,
+
+    /// locked slot files, with a shared budget
+    locks: Vec<File>,
+}
+
+impl Permit<'_> {
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.locks.clear();
+        self.scheduler.state.lock().unwrap().free += self.slots;
+        self.scheduler.changed.notify_all();
+    }
+}
+
+impl Scheduler {
+    /// A scheduler with `slots` solver slots (at least 1).
+    pub fn new(slots: usize) -> Self {
+        let slots = slots.max(1);
+        Scheduler {
+            slots,
+            state: Mutex::new(State {
+                free: slots,
+                next_ticket: 0,
+                queue: Vec::new(),
+            }),
+            changed: Condvar::new(),
+            shared: None,
+        }
+    }
+
+    /// Shares the budget with the other processes using the slot files in `dir`, see
+    /// the module docs.
+    pub fn with_shared_slots(mut self, dir: PathBuf) -> Self {
+        self.shared = Some(dir);
+        self
+    }
+
+    /// One slot per CPU.
+    pub fn default_slots() -> usize {
+        thread::available_parallelism().map_or(1, |n| n.get())
+    }
+
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    /// Waits for `wanted` slots (capped at the budget, at least 1), or returns None if
+    /// `cancel` is set first.
+    ///
+    /// `on_queued` is called with the 1-based position in the queue whenever the run
+    /// has to wait, and again each time the position changes.
+    pub fn acquire(
+        &self,
+        wanted: usize,
+        priority: i32,
+        cancel: &AtomicBool,
+        mut on_queued: impl FnMut(usize),
+    ) -> Option<Permit<'_>> {
+        let slots = wanted.clamp(1, self.slots);
+        let mut state = self.state.lock().unwrap();
+
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        let index = state
+            .queue
+            .partition_point(|waiter| waiter.priority >= priority);
+        state.queue.insert(index, Waiter { ticket, priority });
+
+        let mut reported = None;
+        loop {
+            let position = state.position(ticket);
+            let locks = match &self.shared {
+                _ if position > 0 || state.free < slots => None,
+                Some(dir) => lock_slot_files(dir, slots, self.slots),
+                None => Some(Vec::new()),
+            };
+
+            if let Some(locks) = locks {
+                state.free -= slots;
+                state.leave(ticket);
+                drop(state);
+
+                // the next waiter may fit in what is left
+                self.changed.notify_all();
+                return Some(Permit {
+                    scheduler: self,
+                    slots,
+                    locks,
+                });
+            }
+
+            if cancel.load(Ordering::SeqCst) {
+                state.leave(ticket);
+                drop(state);
+                self.changed.notify_all();
+                return None;
+            }
+
+            if reported != Some(position) {
+                reported = Some(position);
+                on_queued(position + 1);
+            }
+
+            state = self
+                .changed
+                .wait_timeout(state, CANCEL_CHECK_INTERVAL)
+                .unwrap()
+                .0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_config;
+    use std::sync::mpsc;
+
+    #[test]
+    fn waits_for_slots_in_order() {
+        let scheduler = Scheduler::new(4);
+        let never = AtomicBool::new(false);
+
+        let first = scheduler
+            .acquire(3, 0, &never, |_| panic!("slots are free"))
+            .unwrap();
+        assert_eq!(first.slots(), 3);
+
+        let (sender, receiver) = mpsc::channel();
+        let (scheduler, never) = (&scheduler, &never);
+        thread::scope(|scope| {
+            let sender_low = sender.clone();
+            scope.spawn(move || {
+                let permit = scheduler.acquire(2, 0, never, |_| {}).unwrap();
+                sender_low.send(("low", permit.slots())).unwrap();
+            });
+
+            // wait until the first waiter is queued
+            while scheduler.state.lock().unwrap().queue.is_empty() {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            scope.spawn(|| {
+                let permit = scheduler.acquire(10, 1, never, |_| {}).unwrap();
+                sender.send(("high", permit.slots())).unwrap();
+            });
+
+            // the high priority run asks for everything, and goes first
+            thread::sleep(Duration::from_millis(50));
+            assert!(receiver.try_recv().is_err());
+            drop(first);
+        });
+
+        assert_eq!(receiver.recv().unwrap(), ("high", 4));
+        assert_eq!(receiver.recv().unwrap(), ("low", 2));
+    }
+
+    #[test]
+    fn cancelled_waiters_leave_the_queue() {
+        let scheduler = Scheduler::new(1);
+        let never = AtomicBool::new(false);
+        let cancel = AtomicBool::new(false);
+
+        let permit = scheduler.acquire(1, 0, &never, |_| {}).unwrap();
+        let mut positions = Vec::new();
+        thread::scope(|scope| {
+            let waiter = scope.spawn(|| {
+                scheduler
+                    .acquire(1, 0, &cancel, |p| positions.push(p))
+                    .is_none()
+            });
+            thread::sleep(Duration::from_millis(20));
+            cancel.store(true, Ordering::SeqCst);
+            assert!(waiter.join().unwrap());
+        });
+
+        assert_eq!(positions, [1]);
+        assert!(scheduler.state.lock().unwrap().queue.is_empty());
+        drop(permit);
+        assert_eq!(scheduler.state.lock().unwrap().free, 1);
+    }
+
+    #[test]
+    fn shares_slots_with_other_processes() {
+        let config = temp_config("scheduler-shared");
+        let never = AtomicBool::new(false);
+
+        // schedulers in other processes, as far as the slot files are concerned
+        let ours = Scheduler::new(2).with_shared_slots(config.solver_slots.clone());
+        let theirs = Scheduler::new(2).with_shared_slots(config.solver_slots.clone());
+        let larger = Scheduler::new(3).with_shared_slots(config.solver_slots.clone());
+
+        let permit = ours.acquire(2, 0, &never, |_| {}).unwrap();
+        let extra = larger
+            .acquire(1, 0, &never, |_| panic!("slot 2 is free"))
+            .unwrap();
+
+        let cancel = AtomicBool::new(false);
+        let mut positions = Vec::new();
+        thread::scope(|scope| {
+            let waiter = scope.spawn(|| {
+                theirs
+                    .acquire(1, 0, &cancel, |p| positions.push(p))
+                    .is_none()
+            });
+            thread::sleep(Duration::from_millis(50));
+            cancel.store(true, Ordering::SeqCst);
+            assert!(waiter.join().unwrap());
+        });
+        assert_eq!(positions, [1]);
+
+        drop(permit);
+        let permit = theirs
+            .acquire(2, 0, &never, |_| panic!("slots 0 and 1 are free"))
+            .unwrap();
+        assert_eq!(permit.slots(), 2);
+
+        drop((permit, extra));
+        fs::remove_dir_all(&config.jsi_home).unwrap();
+    }
+}
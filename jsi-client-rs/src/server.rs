@@ -0,0 +1,696 @@
+//! Native implementation of the jsi daemon, run by `jsid`.
+//!
+//! Speaks the same protocol as `jsi.server` on the same socket: one request per
+//! connection, either a JSON `Request` line or a bare path (legacy format, with a
+//! plain text response). Solvers are run by the native runner, with the solver
+//! definitions and paths loaded once at startup instead of for every request.
+//!
+//! A request with `session` set opens an incremental session instead, which keeps
+//! the connection (and a `Portfolio` of solvers) until the client sends `(exit)` or
+//! disconnects.
+//!
+//! With a `Pool` (`jsid --pool N`), queries are answered by idle solver processes
+//! when possible, instead of starting solvers for each of them.
+//!
+//! Requests share a `Scheduler`: a request waits until it gets a slot for each of
+//! its solvers (or all the slots), and runs at most that many solvers at a time.
+//! Sessions hold their slots until they end. `jsid` shares the slots with the local
+//! runs of jsif (~/.jsi/slots).
+
+use std::borrow::Cow;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::definitions::{check_sequence, Definitions};
+use crate::input::TempInput;
+use crate::pool::Pool;
+use crate::protocol::{
+    write_message, Cancel, CommandReply, Commands, Options, QueueUpdate, Request, Response,
+    MAX_TIME, PROTOCOL_VERSION,
+};
+use crate::result::SolveResult;
+use crate::runner::{self, RunOutcome};
+use crate::scheduler::Scheduler;
+use crate::session::Portfolio;
+use crate::solvers::SolverPaths;
+use crate::wait::wait_until;
+
+/// Same as in jsi.server: size of a legacy request, and of the request excerpts
+/// in the logs.
+const CONN_BUFFER_SIZE: usize = 1024;
+
+/// Same as in jsi.server: requests with inline scripts can be large.
+const MAX_REQUEST_SIZE: u64 = 256 * 1024 * 1024;
+
+/// How long `shutdown` waits for the requests in progress to kill their solvers.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How often idle sessions check if the server is shutting down.
+const SESSION_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What the client did while its request was being solved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ClientEvent {
+    /// sent a cancel message, and waits for the acknowledgement
+    Cancelled,
+
+    /// closed the connection, nobody is waiting for the response anymore
+    Disconnected,
+
+    /// nothing, the request is done
+    Waited,
+}
+
+pub fn error_response(message: impl Into<String>) -> Response {
+    Response {
+        version: PROTOCOL_VERSION,
+        result: SolveResult::Error,
+        solver: None,
+        output: String::new(),
+        error: Some(message.into()),
+        elapsed: None,
+        queue_time: None,
+        cancelled: false,
+        solvers: Vec::new(),
+    }
+}
+
+/// Parses a JSON request, with the same checks (and messages) as `parse_request`
+/// in jsi.server.
+pub fn parse_request(line: &str) -> Result<Request, String> {
+    let value: serde_json::Value =
+        serde_json::from_str(line).map_err(|e| format!("invalid request: {}", e))?;
+
+    let Some(object) = value.as_object() else {
+        return Err("invalid request: expected a JSON object".to_string());
+    };
+
+    match object.get("version") {
+        Some(version) if version.as_u64() == Some(PROTOCOL_VERSION.into()) => {}
+        version => {
+            return Err(format!(
+                "unsupported protocol version: {}",
+                version.map_or("None".to_string(), |v| v.to_string())
+            ))
+        }
+    }
+
+    let request: Request =
+        serde_json::from_value(value).map_err(|e| format!("invalid request: {}", e))?;
+
+    match (&request.path, &request.input) {
+        (None, None) if request.session => {}
+        _ if request.session => {
+            return Err("invalid request: a session can't have a path or input".into())
+        }
+        (Some(_), Some(_)) => return Err("invalid request: both path and input provided".into()),
+        (_, Some(script)) if script.is_empty() => return Err("invalid request: empty input".into()),
+        (None, None) => return Err("invalid request: missing path".into()),
+        (Some(path), None) if path.is_empty() => return Err("invalid request: missing path".into()),
+        _ => {}
+    }
+
+    let invalid_time = |t: &f64| !(0.0..=MAX_TIME).contains(t);
+
+    if let Some(timeout) = request.options.timeout.filter(invalid_time) {
+        return Err(format!("invalid timeout value: {}", timeout));
+    }
+
+    if let Some(interval) = request.options.interval.filter(invalid_time) {
+        return Err(format!("invalid interval value: {}", interval));
+    }
+
+    if let Some(grace_period) = request.options.grace_period.filter(invalid_time) {
+        return Err(format!("invalid grace_period value: {}", grace_period));
+    }
+
+    Ok(request)
+}
+
+fn is_cancel(line: &str) -> bool {
+    serde_json::from_str::<Cancel>(line).is_ok_and(|message| message.cancel)
+}
+
+/// Reads the messages of a session: cancels are applied right away (to the query in
+/// progress), the others are passed on. Disconnecting cancels the session.
+fn forward_messages(mut reader: impl BufRead, cancel: &AtomicBool, messages: Sender<String>) {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) if is_cancel(&line) => {
+                println!("session query cancelled");
+                cancel.store(true, Ordering::SeqCst);
+            }
+            Ok(_) => {
+                if messages.send(line.clone()).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    cancel.store(true, Ordering::SeqCst);
+}
+
+/// Reads from the connection of a request in progress until the client cancels
+/// the request or disconnects (and then sets `cancel`), or until `done` is set.
+fn watch_client(mut reader: impl BufRead, cancel: &AtomicBool, done: &AtomicBool) -> ClientEvent {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) if is_cancel(&line) => {
+                println!("request cancelled, killing solvers");
+                cancel.store(true, Ordering::SeqCst);
+                return ClientEvent::Cancelled;
+            }
+            Ok(_) => println!("ignoring unexpected message: {}", excerpt(line.trim_end())),
+        }
+    }
+
+    if done.load(Ordering::SeqCst) {
+        return ClientEvent::Waited;
+    }
+
+    println!("client disconnected, killing solvers");
+    cancel.store(true, Ordering::SeqCst);
+    ClientEvent::Disconnected
+}
+
+fn excerpt(message: &str) -> &str {
+    let mut end = message.len().min(CONN_BUFFER_SIZE);
+    while !message.is_char_boundary(end) {
+        end -= 1;
+    }
+    &message[..end]
+}
+
+pub struct Server {
+    definitions: Definitions,
+    available_solvers: SolverPaths,
+    scheduler: Scheduler,
+    pool: Option<Pool>,
+
+    /// cancel flags of the requests in progress
+    active: Mutex<Vec<Arc<AtomicBool>>>,
+
+    /// set by `shutdown`, so that sessions end instead of waiting for commands
+    stopping: AtomicBool,
+}
+
+impl Server {
+    /// A server that runs at most `slots` solvers at a time, across all requests.
+    pub fn new(definitions: Definitions, available_solvers: SolverPaths, slots: usize) -> Self {
+        Server {
+            definitions,
+            available_solvers,
+            scheduler: Scheduler::new(slots),
+            pool: None,
+            active: Mutex::new(Vec::new()),
+            stopping: AtomicBool::new(false),
+        }
+    }
+
+    /// Shares the slots with the local runs of jsif, see `Scheduler::with_shared_slots`.
+    pub fn with_shared_slots(mut self, dir: PathBuf) -> Self {
+        self.scheduler = self.scheduler.with_shared_slots(dir);
+        self
+    }
+
+    /// Keeps `size` processes of each solver that can run in interactive mode, see
+    /// `Pool`. The pool is filled by `maintain_pool`.
+    pub fn with_pool(mut self, size: usize) -> Self {
+        self.pool = Some(Pool::new(size, &self.definitions, &self.available_solvers));
+        self
+    }
+
+    /// Names of the solvers in the pool (none without a pool).
+    pub fn pooled_solvers(&self) -> Vec<String> {
+        self.pool
+            .as_ref()
+            .map(Pool::solver_names)
+            .unwrap_or_default()
+    }
+
+    /// Keeps the pool full until `shutdown`, in a thread that must last as long as
+    /// the server (returns right away without a pool).
+    pub fn maintain_pool(&self) {
+        if let Some(pool) = &self.pool {
+            pool.maintain();
+        }
+    }
+
+    /// Accepts connections forever, handling each of them in its own thread.
+    pub fn serve(&self, listener: &UnixListener) {
+        thread::scope(|scope| loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    scope.spawn(move || {
+                        if let Err(e) = self.handle(stream) {
+                            println!("error handling client: {}", e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    // e.g. out of file descriptors, give requests in progress a chance to finish
+                    println!("error accepting connection: {}", e);
+                    thread::sleep(Duration::from_millis(100));
+                }
+            }
+        })
+    }
+
+    /// Cancels the requests in progress and waits (a bit) for their solvers to be gone.
+    pub fn shutdown(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        for cancel in self.active.lock().unwrap().iter() {
+            cancel.store(true, Ordering::SeqCst);
+        }
+
+        wait_until(SHUTDOWN_TIMEOUT, || self.active.lock().unwrap().is_empty());
+        if let Some(pool) = &self.pool {
+            pool.stop();
+        }
+    }
+
+    fn handle(&self, stream: UnixStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?.take(MAX_REQUEST_SIZE));
+        let data = reader.fill_buf()?;
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        if !data.trim_ascii_start().starts_with(b"{") {
+            let message = String::from_utf8_lossy(&data[..data.len().min(CONN_BUFFER_SIZE)]);
+            return self.handle_legacy(message.trim(), &stream);
+        }
+
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        println!("received request: {}", excerpt(line.trim_end()));
+
+        let request = match parse_request(&line) {
+            Ok(request) => request,
+            Err(message) => return write_message(&mut &stream, &error_response(message)),
+        };
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.active.lock().unwrap().push(cancel.clone());
+
+        if request.session {
+            // the size limit is for the request, not for the whole session
+            reader.get_mut().set_limit(u64::MAX);
+            let (sender, messages) = mpsc::channel();
+            let result = thread::scope(|scope| {
+                scope.spawn(|| forward_messages(reader, &cancel, sender));
+                let result = self.session(&request, &cancel, &messages, &stream);
+
+                // wakes up the reader
+                let _ = stream.shutdown(Shutdown::Both);
+                result
+            });
+
+            self.active
+                .lock()
+                .unwrap()
+                .retain(|other| !Arc::ptr_eq(other, &cancel));
+            return result;
+        }
+
+        let done = AtomicBool::new(false);
+        let (response, event) = thread::scope(|scope| {
+            let watcher = scope.spawn(|| watch_client(reader, &cancel, &done));
+            let response = self.solve(&request, &cancel, |position| {
+                if request.options.queue_updates {
+                    let _ = write_message(&mut &stream, &QueueUpdate::new(position));
+                }
+            });
+
+            // wakes up the watcher
+            done.store(true, Ordering::SeqCst);
+            let _ = stream.shutdown(Shutdown::Read);
+            (response, watcher.join().unwrap_or(ClientEvent::Waited))
+        });
+
+        self.active
+            .lock()
+            .unwrap()
+            .retain(|other| !Arc::ptr_eq(other, &cancel));
+
+        if event == ClientEvent::Disconnected {
+            println!("client disconnected, dropping response");
+            return Ok(());
+        }
+
+        write_message(&mut &stream, &response)
+    }
+
+    /// A bare path, answered with the output of the winning solver as plain text.
+    fn handle_legacy(&self, path: &str, mut stream: &UnixStream) -> io::Result<()> {
+        println!("received request: {}", path);
+
+        let request = Request::new(path, Options::default());
+        let response = self.solve(&request, &AtomicBool::new(false), |_| {});
+        let text = match (&response.error, &response.solver) {
+            (Some(error), _) => format!("error: {}", error),
+            (None, Some(solver)) => {
+                format!("{}\n; (result from {})", response.output.trim(), solver)
+            }
+            (None, None) => format!("{}\n; (no solver succeeded)", response.result),
+        };
+
+        stream.write_all(text.as_bytes())
+    }
+
+    /// Runs a session until the client sends `(exit)` or disconnects, or until the
+    /// server shuts down.
+    fn session(
+        &self,
+        request: &Request,
+        cancel: &AtomicBool,
+        messages: &Receiver<String>,
+        mut stream: &UnixStream,
+    ) -> io::Result<()> {
+        let options = &request.options;
+        if let Err(message) = check_sequence(&self.definitions, &options.sequence) {
+            return write_message(&mut stream, &CommandReply::error(message));
+        }
+
+        let wanted = runner::solver_count(options, &self.definitions, &self.available_solvers);
+        let mut queued = false;
+        let permit = self
+            .scheduler
+            .acquire(wanted, options.priority, cancel, |position| {
+                println!("session queued, position {}", position);
+                queued = true;
+                if options.queue_updates {
+                    let _ = write_message(&mut stream, &QueueUpdate::new(position));
+                }
+            });
+
+        let Some(_permit) = permit else {
+            let reply = CommandReply {
+                result: Some(SolveResult::Killed),
+                cancelled: true,
+                ..CommandReply::new()
+            };
+            return write_message(&mut stream, &reply);
+        };
+
+        if queued && options.queue_updates {
+            write_message(&mut stream, &QueueUpdate::new(0))?;
+        }
+
+        let mut portfolio =
+            match Portfolio::start(options, &self.definitions, &self.available_solvers) {
+                Ok(portfolio) => portfolio,
+                Err(e) => return write_message(&mut stream, &CommandReply::error(e.to_string())),
+            };
+
+        let solvers = portfolio.solver_names();
+        println!("session started with {}", solvers.join(", "));
+        write_message(
+            &mut stream,
+            &CommandReply {
+                solvers,
+                ..CommandReply::new()
+            },
+        )?;
+
+        while !portfolio.is_closed() && !self.stopping.load(Ordering::SeqCst) {
+            let line = match messages.recv_timeout(SESSION_CHECK_INTERVAL) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+
+            // the cancel flag is also how `shutdown` stops the query in progress
+            if self.stopping.load(Ordering::SeqCst) {
+                break;
+            }
+
+            // a cancel sent while no query was in progress has nothing to stop
+            cancel.store(false, Ordering::SeqCst);
+            let reply = match serde_json::from_str::<Commands>(&line) {
+                Ok(message) => portfolio.run(&message.commands, cancel),
+                Err(e) => CommandReply::error(format!("invalid message: {}", e)),
+            };
+
+            write_message(&mut stream, &reply)?;
+        }
+
+        println!("session closed");
+        Ok(())
+    }
+
+    fn solve(
+        &self,
+        request: &Request,
+        cancel: &AtomicBool,
+        mut on_queued: impl FnMut(usize),
+    ) -> Response {
+        if let Err(message) = check_sequence(&self.definitions, &request.options.sequence) {
+            return error_response(message);
+        }
+
+        // solver outputs are written next to the input, so keep inline scripts private
+        let temp_input = match request
+            .input
+            .as_deref()
+            .map(|script| TempInput::new("input.smt2", script))
+        {
+            Some(Err(e)) => return error_response(format!("could not write the input: {}", e)),
+            temp_input => temp_input.and_then(Result::ok),
+        };
+
+        let path = match (&temp_input, &request.path) {
+            (Some(temp_input), _) => temp_input.path().to_path_buf(),
+            (None, Some(path)) => PathBuf::from(path),
+            (None, None) => return error_response("invalid request: missing path"),
+        };
+
+        if !Path::new(&path).is_file() {
+            return error_response(format!("file not found: {}", path.display()));
+        }
+
+        let options = &request.options;
+        let wanted = runner::solver_count(options, &self.definitions, &self.available_solvers);
+        let queued_at = Instant::now();
+        let mut queued = false;
+        let permit = self
+            .scheduler
+            .acquire(wanted, options.priority, cancel, |position| {
+                println!("request queued, position {}", position);
+                queued = true;
+                on_queued(position);
+            });
+
+        let queue_time = Some(queued_at.elapsed().as_secs_f64());
+        if queued && permit.is_some() {
+            println!(
+                "request dequeued after {:.2}s",
+                queued_at.elapsed().as_secs_f64()
+            );
+            on_queued(0);
+        }
+        let Some(permit) = permit else {
+            let outcome = RunOutcome {
+                result: SolveResult::Killed,
+                winner: None,
+                runs: Vec::new(),
+                cancelled: true,
+            };
+
+            return Response {
+                queue_time,
+                ..outcome.into_response()
+            };
+        };
+
+        let start = Instant::now();
+        let mut response = match self.solve_pooled(request, &path, permit.slots(), cancel) {
+            Some(response) => response,
+            None => {
+                let outcome = runner::run_until(
+                    &path,
+                    options,
+                    &self.definitions,
+                    &self.available_solvers,
+                    cancel,
+                    permit.slots(),
+                    |name, result| println!("{} returned {}", name, result),
+                );
+
+                match outcome {
+                    Ok(outcome) => outcome.into_response(),
+                    Err(e) => return error_response(e.to_string()),
+                }
+            }
+        };
+
+        // like jsi.server: the per-solver results are sent on request, or when no
+        // solver succeeded
+        if !request.options.details && (response.solver.is_some() || response.cancelled) {
+            response.solvers.clear();
+        }
+
+        response.elapsed = Some(start.elapsed().as_secs_f64());
+        response.queue_time = queue_time;
+        response
+    }
+
+    /// Solves the request with the pool, if there is one and it can take the request
+    /// with all its solvers running at once.
+    fn solve_pooled(
+        &self,
+        request: &Request,
+        path: &Path,
+        slots: usize,
+        cancel: &AtomicBool,
+    ) -> Option<Response> {
+        let pool = self.pool.as_ref()?;
+        let options = &request.options;
+        let solvers =
+            runner::available_solver_names(options, &self.definitions, &self.available_solvers);
+        if solvers.len() > slots {
+            return None;
+        }
+
+        let script = match &request.input {
+            Some(input) => Cow::Borrowed(input.as_str()),
+            None => Cow::Owned(fs::read_to_string(path).ok()?),
+        };
+
+        let response = pool.solve(&script, &solvers, options, cancel)?;
+        for report in &response.solvers {
+            println!("{} returned {} (pooled)", report.name, report.result);
+        }
+
+        Some(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::read_message;
+
+    #[test]
+    fn parse_request_checks() {
+        let request = parse_request(r#"{"version":1,"path":"/tmp/a.smt2","timeout":2}"#).unwrap();
+        assert_eq!(request.path.as_deref(), Some("/tmp/a.smt2"));
+        assert_eq!(request.options.timeout, Some(2.0));
+
+        let errors = [
+            ("[1]", "invalid request: expected a JSON object"),
+            (r#"{"path":"/a"}"#, "unsupported protocol version: None"),
+            (
+                r#"{"version":2,"path":"/a"}"#,
+                "unsupported protocol version: 2",
+            ),
+            (r#"{"version":1}"#, "invalid request: missing path"),
+            (
+                r#"{"version":1,"input":""}"#,
+                "invalid request: empty input",
+            ),
+            (
+                r#"{"version":1,"path":"/a","input":"x"}"#,
+                "invalid request: both path and input provided",
+            ),
+            (
+                r#"{"version":1,"session":true,"path":"/a"}"#,
+                "invalid request: a session can't have a path or input",
+            ),
+            (
+                r#"{"version":1,"path":"/a","timeout":-1}"#,
+                "invalid timeout value: -1",
+            ),
+            (
+                r#"{"version":1,"path":"/a","timeout":1e10}"#,
+                "invalid timeout value: 10000000000",
+            ),
+            (
+                r#"{"version":1,"path":"/a","interval":1e20}"#,
+                "invalid interval value: 100000000000000000000",
+            ),
+            (
+                r#"{"version":1,"path":"/a","grace_period":31536001}"#,
+                "invalid grace_period value: 31536001",
+            ),
+        ];
+
+        for (line, message) in errors {
+            assert_eq!(parse_request(line).unwrap_err(), message, "{}", line);
+        }
+        assert_eq!(
+            parse_request(r#"{"version":1,"path":"/a","timeout":1e300}"#).unwrap_err(),
+            format!("invalid timeout value: {}", 1e300)
+        );
+    }
+
+    #[test]
+    fn handle_runs_sessions() {
+        let script = "while read -r line; do \
+                      case \"$line\" in '(check-sat)') echo unsat ;; *) echo success ;; esac; \
+                      done";
+        let definitions = crate::definitions::parse_definitions(&format!(
+            r#"{{"fake": {{"executable": "sh", "model": null, "args": [],
+                "interactive": ["-c", {:?}]}}}}"#,
+            script
+        ))
+        .unwrap();
+        let solvers = SolverPaths::from([("sh".to_string(), "/bin/sh".to_string())]);
+        let server = Server::new(definitions, solvers, 1);
+        let (client, daemon) = UnixStream::pair().unwrap();
+
+        thread::scope(|scope| {
+            scope.spawn(|| server.handle(daemon).unwrap());
+
+            let mut reader = BufReader::new(client.try_clone().unwrap());
+            let mut client = &client;
+            write_message(&mut client, &Request::session(Options::default())).unwrap();
+            let reply: CommandReply = read_message(&mut reader).unwrap();
+            assert_eq!(reply.solvers, ["fake"]);
+
+            write_message(&mut client, &Commands::new("(assert false)(check-sat)")).unwrap();
+            let reply: CommandReply = read_message(&mut reader).unwrap();
+            assert_eq!(reply.result, Some(SolveResult::Unsat));
+            assert_eq!(reply.output, "unsat\n");
+
+            write_message(&mut client, &Commands::new("(exit)")).unwrap();
+            let reply: CommandReply = read_message(&mut reader).unwrap();
+            assert_eq!(reply.error, None);
+        });
+    }
+
+    #[test]
+    fn handle_answers_with_errors() {
+        let server = Server::new(Definitions::new(), SolverPaths::new(), 1);
+        let (mut client, daemon) = UnixStream::pair().unwrap();
+
+        let request = Request::new(
+            "/tmp/a.smt2",
+            Options {
+                sequence: vec!["nope".into()],
+                ..Options::default()
+            },
+        );
+        write_message(&mut client, &request).unwrap();
+        server.handle(daemon).unwrap();
+
+        let response: Response = read_message(&mut BufReader::new(client)).unwrap();
+        assert_eq!(response.result, SolveResult::Error);
+        assert!(response.error.unwrap().contains("nope"));
+    }
+}
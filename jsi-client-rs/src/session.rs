@@ -0,0 +1,755 @@
+//! Incremental solving sessions: a portfolio of solvers kept alive in interactive
+//! mode and fed the same SMT-LIB commands, with each check-sat raced across them.
+//!
+//! Solvers run with `:print-success`, so that every command gets exactly one
+//! response and responses can be matched with the commands they answer. The losers
+//! of a race are not killed: they keep working on the query and their late answer
+//! is dropped, so their assertion stack stays in sync with the others. A solver
+//! that falls too far behind is restarted instead, and brought back in sync by
+//! replaying the commands of the session. So are the solvers still working on a
+//! query that timed out or was cancelled, since none of them is likely to answer
+//! the next query in time otherwise.
+//!
+//! Only solvers with `interactive` arguments in their definition take part in
+//! sessions.
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::definitions::Definitions;
+use crate::protocol::{CommandReply, Options};
+use crate::result::SolveResult;
+use crate::runner::solver_names;
+use crate::smtlib::{split_sexprs, Splitter};
+use crate::solvers::SolverPaths;
+use crate::supervisor::{ChildId, Supervisor, DEFAULT_GRACE_PERIOD};
+
+/// How often waiting for an answer wakes up to check for cancellation.
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Solvers that still owe answers to this many earlier queries when a new one
+/// starts are restarted.
+const MAX_BACKLOG: usize = 2;
+
+const PRINT_SUCCESS: &str = "(set-option :print-success true)";
+const PRODUCE_MODELS: &str = "(set-option :produce-models true)";
+
+/// Splits SMT-LIB text into commands, one per line (see `smtlib::Splitter`).
+pub fn split_commands(text: &str) -> Result<Vec<String>, String> {
+    let commands = split_sexprs(text).map_err(|error| error.message)?;
+
+    match commands.iter().find(|command| !command.starts_with('(')) {
+        Some(atom) => Err(format!("expected a command, found `{}`", atom)),
+        None => Ok(commands),
+    }
+}
+
+/// The keyword of a command, e.g. `assert` for `(assert (> x 1))`.
+pub(crate) fn command_name(command: &str) -> &str {
+    command[1..]
+        .trim_start()
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .next()
+        .unwrap_or_default()
+}
+
+/// What a solver response is expected to be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expect {
+    /// `success` (or an error) for a command that is not a query
+    Ack,
+
+    /// the answer to the query with this id
+    Answer(u64),
+}
+
+/// A response read from a solver, or None when its stdout is closed.
+struct Event {
+    solver: usize,
+    generation: u64,
+    response: Option<String>,
+}
+
+/// How waiting for the answer to a query ended.
+enum Waited {
+    Answer(usize, String),
+
+    /// something else happened (another response, a solver exit, or nothing yet)
+    Other,
+    Cancelled,
+    TimedOut,
+}
+
+/// How a query ended.
+enum QueryEnd {
+    Answered(usize, String),
+    Cancelled,
+    TimedOut,
+
+    /// every solver that could answer exited
+    NoSolver,
+}
+
+struct SessionSolver {
+    name: String,
+    args: Vec<String>,
+    child: ChildId,
+
+    /// tells the responses of this process from those of the one it replaced
+    generation: u64,
+
+    /// commands for the thread that writes to the solver stdin
+    commands: Sender<String>,
+
+    /// what the responses still to come are for, oldest first
+    expected: VecDeque<Expect>,
+    alive: bool,
+
+    /// errors the solver reported for commands that are not queries, not shown yet
+    errors: Vec<String>,
+}
+
+impl SessionSolver {
+    fn send(&mut self, command: &str, expect: Expect) {
+        if self.alive && self.commands.send(command.to_string()).is_ok() {
+            self.expected.push_back(expect);
+        } else {
+            self.alive = false;
+        }
+    }
+
+    /// Number of queries the solver has yet to answer.
+    fn backlog(&self) -> usize {
+        self.expected
+            .iter()
+            .filter(|expect| matches!(expect, Expect::Answer(_)))
+            .count()
+    }
+}
+
+fn write_commands(mut stdin: ChildStdin, commands: Receiver<String>) {
+    for command in commands {
+        if writeln!(stdin, "{}", command).is_err() {
+            break;
+        }
+    }
+}
+
+fn read_responses(stdout: ChildStdout, solver: usize, generation: u64, events: Sender<Event>) {
+    let mut splitter = Splitter::default();
+    let mut responses = Vec::new();
+    for line in BufReader::new(stdout).lines() {
+        let Ok(mut line) = line else {
+            break;
+        };
+
+        // a solver that prints unbalanced output can't be followed, drop it
+        line.push('\n');
+        if splitter.feed(&line, &mut responses).is_err() {
+            break;
+        }
+
+        for response in responses.drain(..) {
+            let event = Event {
+                solver,
+                generation,
+                response: Some(response),
+            };
+
+            if events.send(event).is_err() {
+                return;
+            }
+        }
+    }
+
+    let _ = events.send(Event {
+        solver,
+        generation,
+        response: None,
+    });
+}
+
+/// Solvers kept alive for a session, see the module docs.
+///
+/// The solvers are killed when the portfolio is dropped.
+pub struct Portfolio {
+    supervisor: Supervisor,
+    solvers: Vec<SessionSolver>,
+
+    /// commands sent to every solver before those of the session
+    setup: Vec<String>,
+
+    /// commands of the session so far (except queries), replayed to restarted solvers
+    history: Vec<String>,
+    events: Receiver<Event>,
+    sender: Sender<Event>,
+    next_generation: u64,
+    next_query: u64,
+
+    /// solver that answered the last query, asked for get-model and the like
+    answerer: Option<usize>,
+    timeout: Option<Duration>,
+    closed: bool,
+}
+
+impl Portfolio {
+    /// Starts the solvers of a session with these options: `sequence` and `model`
+    /// as for a single query, and `timeout` for each query of the session.
+    pub fn start(
+        options: &Options,
+        definitions: &Definitions,
+        available_solvers: &SolverPaths,
+    ) -> io::Result<Portfolio> {
+        let mut setup = vec![PRINT_SUCCESS.to_string()];
+        if options.model {
+            setup.push(PRODUCE_MODELS.to_string());
+        }
+
+        let grace_period = match options.grace_period {
+            Some(t) => Duration::try_from_secs_f64(t).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid grace_period value: {}", t),
+                )
+            })?,
+            None => DEFAULT_GRACE_PERIOD,
+        };
+        let (sender, events) = mpsc::channel();
+        let mut portfolio = Portfolio {
+            supervisor: Supervisor::new(grace_period),
+            solvers: Vec::new(),
+            setup,
+            history: Vec::new(),
+            events,
+            sender,
+            next_generation: 0,
+            next_query: 0,
+            answerer: None,
+            // a timeout too large for a Duration is as good as no timeout
+            timeout: options
+                .timeout
+                .filter(|&t| t > 0.0)
+                .and_then(|t| Duration::try_from_secs_f64(t).ok()),
+            closed: false,
+        };
+
+        for name in solver_names(options, definitions) {
+            let definition = definitions.get(&name).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unknown solver: {}", name),
+                )
+            })?;
+
+            let (Some(executable_path), Some(interactive)) = (
+                available_solvers.get(&definition.executable),
+                &definition.interactive,
+            ) else {
+                continue;
+            };
+
+            let mut args = vec![executable_path.clone()];
+            args.extend(definition.args.iter().cloned());
+            args.extend(interactive.iter().cloned());
+
+            let solver = portfolio.launch(portfolio.solvers.len(), name, args)?;
+            portfolio.solvers.push(solver);
+        }
+
+        if portfolio.solvers.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no solvers for a session (none of the requested solvers with \
+                 `interactive` arguments were found on PATH)",
+            ));
+        }
+
+        Ok(portfolio)
+    }
+
+    /// Names of the solvers of the session.
+    pub fn solver_names(&self) -> Vec<String> {
+        self.solvers
+            .iter()
+            .map(|solver| solver.name.clone())
+            .collect()
+    }
+
+    /// True once the session got `(exit)`.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Starts a solver process, and sends it the commands of the session so far.
+    fn launch(
+        &mut self,
+        index: usize,
+        name: String,
+        args: Vec<String>,
+    ) -> io::Result<SessionSolver> {
+        let child = self.supervisor.spawn(
+            Command::new(&args[0])
+                .args(&args[1..])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null()),
+        )?;
+
+        let generation = self.next_generation;
+        self.next_generation += 1;
+
+        let (commands, receiver) = mpsc::channel();
+        let (stdin, stdout) = self.supervisor.take_pipes(child);
+        if let (Some(stdin), Some(stdout)) = (stdin, stdout) {
+            let events = self.sender.clone();
+            thread::spawn(move || write_commands(stdin, receiver));
+            thread::spawn(move || read_responses(stdout, index, generation, events));
+        }
+
+        let mut solver = SessionSolver {
+            name,
+            args,
+            child,
+            generation,
+            commands,
+            expected: VecDeque::new(),
+            alive: true,
+            errors: Vec::new(),
+        };
+
+        for command in self.setup.iter().chain(&self.history) {
+            solver.send(command, Expect::Ack);
+        }
+
+        Ok(solver)
+    }
+
+    /// Replaces the solvers that owe answers to `max_backlog` queries or more with
+    /// new processes.
+    fn restart_laggards(&mut self, max_backlog: usize) {
+        for index in 0..self.solvers.len() {
+            let solver = &self.solvers[index];
+            if !solver.alive || solver.backlog() < max_backlog {
+                continue;
+            }
+
+            // a new process has no answer to ask about
+            if self.answerer == Some(index) {
+                self.answerer = None;
+            }
+
+            self.supervisor.signal(solver.child, libc::SIGKILL);
+            let (name, args) = (solver.name.clone(), solver.args.clone());
+            match self.launch(index, name, args) {
+                Ok(solver) => self.solvers[index] = solver,
+                Err(_) => self.solvers[index].alive = false,
+            }
+        }
+
+        let _ = self.supervisor.reap();
+    }
+
+    /// Updates the state of the solver a response comes from, and returns the
+    /// response if it answers a query: (solver, query, response).
+    fn handle(&mut self, event: Event) -> Option<(usize, u64, String)> {
+        let solver = &mut self.solvers[event.solver];
+        if solver.generation != event.generation || !solver.alive {
+            return None;
+        }
+
+        let Some(response) = event.response else {
+            solver.alive = false;
+            let _ = self.supervisor.reap();
+            return None;
+        };
+
+        match solver.expected.pop_front() {
+            Some(Expect::Answer(query)) => Some((event.solver, query, response)),
+            Some(Expect::Ack) if response == "success" => None,
+
+            // errors, and output nobody asked for
+            Some(Expect::Ack) | None => {
+                solver.errors.push(response);
+                None
+            }
+        }
+    }
+
+    fn next_answer(
+        &mut self,
+        query: u64,
+        deadline: Option<Instant>,
+        cancel: &AtomicBool,
+    ) -> Waited {
+        if cancel.load(Ordering::SeqCst) {
+            return Waited::Cancelled;
+        }
+
+        let mut wait = CANCEL_CHECK_INTERVAL;
+        if let Some(deadline) = deadline {
+            let now = Instant::now();
+            if now >= deadline {
+                return Waited::TimedOut;
+            }
+            wait = wait.min(deadline - now);
+        }
+
+        match self.events.recv_timeout(wait) {
+            Ok(event) => match self.handle(event) {
+                Some((solver, answered, response)) if answered == query => {
+                    Waited::Answer(solver, response)
+                }
+                _ => Waited::Other,
+            },
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => Waited::Other,
+        }
+    }
+
+    fn new_query(&mut self) -> (u64, Option<Instant>) {
+        let query = self.next_query;
+        self.next_query += 1;
+        let deadline = self
+            .timeout
+            .and_then(|timeout| Instant::now().checked_add(timeout));
+        (query, deadline)
+    }
+
+    /// Sends a check-sat (or check-sat-assuming) to every solver, and returns the
+    /// first sat/unsat answer, or else unknown, or else an error.
+    fn race(&mut self, command: &str, cancel: &AtomicBool) -> QueryEnd {
+        self.restart_laggards(MAX_BACKLOG);
+        self.answerer = None;
+
+        let (query, deadline) = self.new_query();
+        let mut pending = Vec::new();
+        for (index, solver) in self.solvers.iter_mut().enumerate() {
+            solver.send(command, Expect::Answer(query));
+            if solver.alive {
+                pending.push(index);
+            }
+        }
+
+        let mut fallback: Option<(usize, String, SolveResult)> = None;
+        loop {
+            pending.retain(|&index| self.solvers[index].alive);
+            if pending.is_empty() {
+                break;
+            }
+
+            match self.next_answer(query, deadline, cancel) {
+                Waited::Answer(index, response) => {
+                    pending.retain(|&other| other != index);
+                    let result = SolveResult::from_output(&response, "", true);
+                    if matches!(result, SolveResult::Sat | SolveResult::Unsat) {
+                        return QueryEnd::Answered(index, response);
+                    }
+
+                    // unknown is a better answer than an error
+                    let better = match &fallback {
+                        None => true,
+                        Some((_, _, other)) => {
+                            *other == SolveResult::Error && result != SolveResult::Error
+                        }
+                    };
+                    if better {
+                        fallback = Some((index, response, result));
+                    }
+                }
+                Waited::Other => {}
+                Waited::Cancelled => return QueryEnd::Cancelled,
+                Waited::TimedOut => return QueryEnd::TimedOut,
+            }
+        }
+
+        match fallback {
+            Some((index, response, _)) => QueryEnd::Answered(index, response),
+            None => QueryEnd::NoSolver,
+        }
+    }
+
+    /// Sends a query other than check-sat to a single solver: the one that answered
+    /// the last query, or else the first one that is not busy.
+    fn ask(&mut self, command: &str, cancel: &AtomicBool) -> QueryEnd {
+        let target = self
+            .answerer
+            .filter(|&index| self.solvers[index].alive)
+            .or_else(|| {
+                self.solvers
+                    .iter()
+                    .position(|solver| solver.alive && solver.backlog() == 0)
+            });
+
+        let Some(index) = target else {
+            return QueryEnd::NoSolver;
+        };
+
+        let (query, deadline) = self.new_query();
+        self.solvers[index].send(command, Expect::Answer(query));
+        while self.solvers[index].alive {
+            match self.next_answer(query, deadline, cancel) {
+                Waited::Answer(index, response) => return QueryEnd::Answered(index, response),
+                Waited::Other => {}
+                Waited::Cancelled => return QueryEnd::Cancelled,
+                Waited::TimedOut => return QueryEnd::TimedOut,
+            }
+        }
+
+        QueryEnd::NoSolver
+    }
+
+    /// Runs `commands` (any number of SMT-LIB commands) in the session, and replies
+    /// with what the solvers printed for the queries among them: check-sat and
+    /// check-sat-assuming are raced across the solvers, while get-model, get-value
+    /// and the other get-* commands go to the solver that answered the last query.
+    ///
+    /// Errors a solver reported for earlier commands come before its next answer.
+    /// Stops at the first query that times out or is cancelled with `cancel`.
+    pub fn run(&mut self, commands: &str, cancel: &AtomicBool) -> CommandReply {
+        let start = Instant::now();
+        let commands = match split_commands(commands) {
+            Ok(commands) => commands,
+            Err(message) => return CommandReply::error(message),
+        };
+
+        let mut reply = CommandReply::new();
+        for command in commands {
+            if self.closed {
+                reply.error = Some("the session is closed".to_string());
+                break;
+            }
+
+            let name = command_name(&command);
+            let end = match name {
+                "exit" => {
+                    self.closed = true;
+                    continue;
+                }
+                "check-sat" | "check-sat-assuming" => self.race(&command, cancel),
+                "echo" => self.ask(&command, cancel),
+                _ if name.starts_with("get-") => self.ask(&command, cancel),
+                "set-option" if command.contains(":print-success") => {
+                    reply.error = Some("print-success can't be changed in a session".to_string());
+                    break;
+                }
+                _ => {
+                    for solver in self.solvers.iter_mut() {
+                        solver.send(&command, Expect::Ack);
+                    }
+                    self.history.push(command);
+                    continue;
+                }
+            };
+
+            match end {
+                QueryEnd::Answered(index, response) => {
+                    let solver = &mut self.solvers[index];
+                    for error in solver.errors.drain(..) {
+                        reply.output.push_str(&error);
+                        reply.output.push('\n');
+                    }
+
+                    reply.output.push_str(&response);
+                    reply.output.push('\n');
+                    if name.starts_with("check-sat") {
+                        reply.result = Some(SolveResult::from_output(&response, "", true));
+                    }
+
+                    reply.solver = Some(solver.name.clone());
+                    self.answerer = Some(index);
+                }
+                QueryEnd::Cancelled => {
+                    self.restart_laggards(1);
+                    reply.result = Some(SolveResult::Killed);
+                    reply.cancelled = true;
+                    break;
+                }
+                QueryEnd::TimedOut => {
+                    self.restart_laggards(1);
+                    reply.result = Some(SolveResult::Timeout);
+                    break;
+                }
+                QueryEnd::NoSolver => {
+                    reply.error = Some(format!("no solver left to answer {}", name));
+                    break;
+                }
+            }
+        }
+
+        reply.elapsed = Some(start.elapsed().as_secs_f64());
+        reply
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::SolverDefinition;
+
+    #[test]
+    fn split_commands_one_per_line() {
+        let text = "; comment\n(set-logic QF_BV)\n(assert\n  (= x |a ; b|))(check-sat)\n\
+                    (echo \"hello ( world\")";
+        assert_eq!(
+            split_commands(text).unwrap(),
+            [
+                "(set-logic QF_BV)",
+                "(assert (= x |a ; b|))",
+                "(check-sat)",
+                "(echo \"hello ( world\")"
+            ]
+        );
+        assert_eq!(
+            command_name("( check-sat-assuming (a))"),
+            "check-sat-assuming"
+        );
+
+        assert!(split_commands("(assert (= x y)").is_err());
+        assert!(split_commands("(check-sat))").is_err());
+        assert_eq!(
+            split_commands("sat").unwrap_err(),
+            "expected a command, found `sat`"
+        );
+    }
+
+    /// A solver that answers `answer` to check-sat after `delay` seconds, and
+    /// `(model)` to get-model.
+    fn fake_solver(answer: &str, delay: &str) -> SolverDefinition {
+        let script = format!(
+            "while read -r line; do case \"$line\" in \
+             '(check-sat)') sleep {}; echo {} ;; \
+             '(get-model)') echo '(model'; echo ')' ;; \
+             '(assert bad)') echo '(error \"bad\")' ;; \
+             *) echo success ;; esac; done",
+            delay, answer
+        );
+
+        SolverDefinition {
+            executable: "sh".to_string(),
+            model: None,
+            args: Vec::new(),
+            interactive: Some(vec!["-c".to_string(), script]),
+            enabled: true,
+            meta: None,
+        }
+    }
+
+    #[test]
+    fn races_check_sat_across_live_solvers() {
+        let mut definitions = Definitions::new();
+        definitions.insert("slow".to_string(), fake_solver("unsat", "5"));
+        definitions.insert("fast".to_string(), fake_solver("sat", "0"));
+        let available_solvers = SolverPaths::from([("sh".to_string(), "/bin/sh".to_string())]);
+        let options = Options {
+            sequence: vec!["slow".into(), "fast".into()],
+            timeout: Some(3.0),
+            ..Options::default()
+        };
+
+        let never = AtomicBool::new(false);
+        let mut portfolio = Portfolio::start(&options, &definitions, &available_solvers).unwrap();
+        assert_eq!(portfolio.solver_names(), ["slow", "fast"]);
+
+        let reply = portfolio.run("(push 1)\n(assert bad)\n(check-sat)", &never);
+        assert_eq!(reply.result, Some(SolveResult::Sat));
+        assert_eq!(reply.solver.as_deref(), Some("fast"));
+        assert_eq!(reply.output, "(error \"bad\")\nsat\n");
+
+        // the winner answers, while the loser is still busy
+        let reply = portfolio.run("(pop 1)\n(get-model)", &never);
+        assert_eq!(reply.output, "(model)\n");
+        assert_eq!(portfolio.solvers[0].backlog(), 1);
+        assert_eq!(portfolio.history, ["(push 1)", "(assert bad)", "(pop 1)"]);
+
+        // queries are answered in order, the stale answer of `slow` is dropped
+        let start = Instant::now();
+        let reply = portfolio.run("(check-sat)", &never);
+        assert_eq!(reply.result, Some(SolveResult::Sat));
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        // `slow` owes 2 answers by now, and is restarted with the history of the
+        // session; then both are restarted, as they are busy with the cancelled query
+        let cancel = AtomicBool::new(true);
+        let reply = portfolio.run("(check-sat)\n(get-model)", &cancel);
+        assert!(reply.cancelled && reply.output.is_empty());
+        assert_eq!(portfolio.solvers[0].generation, 3);
+        assert_eq!(portfolio.solvers[1].generation, 4);
+        assert_eq!(portfolio.solvers[0].backlog(), 0);
+        assert_eq!(portfolio.solvers[1].backlog(), 0);
+
+        let reply = portfolio.run("(exit)\n(check-sat)", &never);
+        assert!(portfolio.is_closed());
+        assert_eq!(reply.error.as_deref(), Some("the session is closed"));
+    }
+
+    #[test]
+    fn solvers_stuck_on_a_timed_out_query_are_restarted() {
+        // check-sat is slow while `hard` is asserted
+        let script = "hard=0; while read -r line; do case \"$line\" in \
+                      '(assert hard)') hard=1; echo success ;; \
+                      '(pop 1)') hard=0; echo success ;; \
+                      '(check-sat)') [ $hard = 1 ] && sleep 10; echo unsat ;; \
+                      *) echo success ;; esac; done";
+        let mut definitions = Definitions::new();
+        definitions.insert(
+            "stuck".to_string(),
+            SolverDefinition {
+                interactive: Some(vec!["-c".to_string(), script.to_string()]),
+                ..fake_solver("unsat", "0")
+            },
+        );
+        let available_solvers = SolverPaths::from([("sh".to_string(), "/bin/sh".to_string())]);
+        let options = Options {
+            timeout: Some(0.5),
+            ..Options::default()
+        };
+
+        let never = AtomicBool::new(false);
+        let mut portfolio = Portfolio::start(&options, &definitions, &available_solvers).unwrap();
+        let reply = portfolio.run("(push 1)\n(assert hard)\n(check-sat)", &never);
+        assert_eq!(reply.result, Some(SolveResult::Timeout));
+
+        // the next query goes to a new process, replayed up to the pop
+        let reply = portfolio.run("(pop 1)\n(check-sat)", &never);
+        assert_eq!(reply.result, Some(SolveResult::Unsat));
+        assert_eq!(reply.solver.as_deref(), Some("stuck"));
+        assert_eq!(portfolio.solvers[0].generation, 1);
+    }
+
+    #[test]
+    fn zero_timeout_means_no_timeout() {
+        let mut definitions = Definitions::new();
+        definitions.insert("sleepy".to_string(), fake_solver("sat", "0.3"));
+        let available_solvers = SolverPaths::from([("sh".to_string(), "/bin/sh".to_string())]);
+        let options = Options {
+            timeout: Some(0.0),
+            ..Options::default()
+        };
+
+        let mut portfolio = Portfolio::start(&options, &definitions, &available_solvers).unwrap();
+        let reply = portfolio.run("(check-sat)", &AtomicBool::new(false));
+        assert_eq!(reply.result, Some(SolveResult::Sat));
+
+        // and so does a timeout too large for a Duration
+        let options = Options {
+            timeout: Some(f64::INFINITY),
+            ..Options::default()
+        };
+        let mut portfolio = Portfolio::start(&options, &definitions, &available_solvers).unwrap();
+        let reply = portfolio.run("(check-sat)", &AtomicBool::new(false));
+        assert_eq!(reply.result, Some(SolveResult::Sat));
+
+        let options = Options {
+            grace_period: Some(-1.0),
+            ..Options::default()
+        };
+        let error = Portfolio::start(&options, &definitions, &available_solvers)
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "invalid grace_period value: -1");
+    }
+}
@@ -0,0 +1,2039 @@
+//! SMT-LIB 2.6 scripts: a lexer, and a parser into commands, terms and sorts that
+//! keep their source spans.
+//!
+//! Parsing goes in three steps: `tokenize` splits the text into tokens, which are
+//! grouped into s-expressions, which are then read as commands. The standard
+//! commands are checked against the grammar of the standard; other commands (solver
+//! extensions, like z3's `minimize` or `check-sat-using`) are kept as s-expressions.
+//! Some common departures from the standard are accepted too: `push`, `pop` and
+//! `declare-sort` without a numeral, constructors without parentheses, and the
+//! SMT-LIB 2.5 form of `declare-datatypes`.
+//!
+//! Errors are `Diagnostic`s, which point at the offending part of the script.
+//!
+//! The lexer also has a lenient mode, for text that is not checked against the
+//! standard: the commands of sessions and the scripts keyed by the result cache
+//! (split with `Splitter`), and the output of solvers (responses and models).
+
+use std::borrow::Cow;
+use std::fmt;
+use std::iter::Peekable;
+use std::mem;
+use std::sync::Mutex;
+use std::thread;
+use std::vec;
+
+/// Terms and sorts nested deeper than this are rejected by `parse`, instead of
+/// overflowing the stack of the parser.
+pub const MAX_DEPTH: usize = 10_000;
+
+/// Stack of the threads of `with_parser_stack`: enough for terms nested `MAX_DEPTH`
+/// deep, even in debug builds (the memory is only used as the parser goes deeper).
+const PARSER_STACK_SIZE: usize = 1 << 30;
+
+/// Byte offsets in the script, `end` excluded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+}
+
+/// A parse error, and where it is in the script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    fn new(message: impl Into<String>, span: Span) -> Diagnostic {
+        Diagnostic {
+            message: message.into(),
+            span,
+        }
+    }
+
+    /// Line and column (both from 1, the column in characters) of the start of the
+    /// error in `text`.
+    pub fn location(&self, text: &str) -> (usize, usize) {
+        let start = self.span.start.min(text.len());
+        let before = &text[..start];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (line, before[line_start..].chars().count() + 1)
+    }
+
+    /// `name:line:column: message`, for one-line reports.
+    pub fn summary(&self, name: &str, text: &str) -> String {
+        let (line, column) = self.location(text);
+        format!("{}:{}:{}: {}", name, line, column, self.message)
+    }
+
+    /// The summary, followed by the line of the error with the span underlined.
+    pub fn render(&self, name: &str, text: &str) -> String {
+        let (line, column) = self.location(text);
+        let start = self.span.start.min(text.len());
+        let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = text[start..].find('\n').map_or(text.len(), |i| start + i);
+        let source = text[line_start..line_end].trim_end_matches('\r');
+
+        let end = self.span.end.clamp(start, line_end);
+        let width = text[start..end].chars().count().max(1);
+        let margin = " ".repeat(line.to_string().len());
+        format!(
+            "{}\n{} |\n{} | {}\n{} | {}{}",
+            self.summary(name, text),
+            margin,
+            line,
+            source,
+            margin,
+            " ".repeat(column - 1),
+            "^".repeat(width)
+        )
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Open,
+    Close,
+    Numeral,
+    Decimal,
+    Hexadecimal,
+    Binary,
+    String,
+    Symbol,
+    QuotedSymbol,
+    Keyword,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+
+    /// the contents of string literals and quoted symbols (without quotes and
+    /// escapes), the text of the token otherwise (`#x` included for hexadecimals,
+    /// `:` for keywords)
+    pub text: String,
+    pub span: Span,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self.kind {
+            TokenKind::Open => "`(`".to_string(),
+            TokenKind::Close => "`)`".to_string(),
+            TokenKind::String => "a string literal".to_string(),
+            TokenKind::Symbol => format!("symbol `{}`", self.text),
+            TokenKind::QuotedSymbol => format!("symbol `|{}|`", self.text),
+            TokenKind::Keyword => format!("keyword `{}`", self.text),
+            _ => format!("literal `{}`", self.text),
+        }
+    }
+
+    /// True for the given reserved word (quoted symbols are never reserved).
+    fn is_reserved(&self, word: &str) -> bool {
+        self.kind == TokenKind::Symbol && self.text == word
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            TokenKind::Open => f.write_str("("),
+            TokenKind::Close => f.write_str(")"),
+            TokenKind::String => f.write_str(&quote_string(&self.text)),
+            TokenKind::Symbol | TokenKind::QuotedSymbol => f.write_str(&quote_symbol(&self.text)),
+            _ => f.write_str(&self.text),
+        }
+    }
+}
+
+fn is_symbol_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"~!@$%^&*_-+=<>.?/".contains(&c)
+}
+
+/// Words that are symbols for the lexer, but that can't name anything.
+const RESERVED: [&str; 12] = [
+    "!",
+    "_",
+    "as",
+    "BINARY",
+    "DECIMAL",
+    "exists",
+    "forall",
+    "HEXADECIMAL",
+    "let",
+    "match",
+    "NUMERAL",
+    "par",
+];
+
+/// `name` as it must be written in a script: between `|` unless it is a valid
+/// simple symbol.
+pub fn quote_symbol(name: &str) -> Cow<'_, str> {
+    let simple = !name.is_empty()
+        && !name.as_bytes()[0].is_ascii_digit()
+        && name.bytes().all(is_symbol_char)
+        && !RESERVED.contains(&name);
+
+    if simple {
+        Cow::Borrowed(name)
+    } else {
+        Cow::Owned(format!("|{}|", name))
+    }
+}
+
+/// `text` as a string literal.
+pub fn quote_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+/// True for the errors of literals that the text ends in the middle of.
+fn is_unterminated(error: &Diagnostic) -> bool {
+    error.message.starts_with("unterminated")
+}
+
+/// Reads the tokens of a script one at a time, dropping whitespace and comments.
+///
+/// Stops after the first error.
+pub struct Lexer<'a> {
+    text: &'a str,
+    offset: usize,
+
+    /// read invalid tokens as symbols instead of failing
+    lenient: bool,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(text: &'a str) -> Lexer<'a> {
+        Lexer {
+            text,
+            offset: 0,
+            lenient: false,
+        }
+    }
+
+    /// A lexer for text that doesn't have to follow the standard (solver output, or
+    /// scripts that only the solvers get to check): what is not a valid token is read
+    /// as a symbol, up to the next whitespace, parenthesis, quote or comment (e.g.
+    /// `0b0101` in yices models). Only unterminated string literals and quoted
+    /// symbols are errors.
+    pub fn lenient(text: &'a str) -> Lexer<'a> {
+        Lexer {
+            lenient: true,
+            ..Lexer::new(text)
+        }
+    }
+
+    /// The next token, or None at the end of the text.
+    fn scan(&mut self) -> Result<Option<Token>, Diagnostic> {
+        let text = self.text;
+        let bytes = text.as_bytes();
+        let mut i = self.offset;
+        let token = match self.scan_from(&mut i) {
+            // every other error is at the start of the token
+            Err(error) if self.lenient && !is_unterminated(&error) => {
+                Ok(Some(self.raw_symbol(error.span.start, &mut i)))
+            }
+            token => token,
+        };
+        self.offset = if token.is_err() { bytes.len() } else { i };
+        token
+    }
+
+    /// Reads the text from `start` up to the next delimiter (always ascii) as a symbol.
+    fn raw_symbol(&self, start: usize, position: &mut usize) -> Token {
+        let bytes = self.text.as_bytes();
+        let mut i = start + 1;
+        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !b"();\"|".contains(&bytes[i]) {
+            i += 1;
+        }
+
+        *position = i;
+        Token {
+            kind: TokenKind::Symbol,
+            text: self.text[start..i].to_string(),
+            span: Span::new(start, i),
+        }
+    }
+
+    fn scan_from(&self, position: &mut usize) -> Result<Option<Token>, Diagnostic> {
+        let text = self.text;
+        let bytes = text.as_bytes();
+        let mut i = *position;
+        while i < bytes.len() {
+            let start = i;
+            let kind = match bytes[i] {
+                b' ' | b'\t' | b'\r' | b'\n' => {
+                    i += 1;
+                    continue;
+                }
+                b';' => {
+                    while i < bytes.len() && bytes[i] != b'\n' {
+                        i += 1;
+                    }
+                    continue;
+                }
+                b'(' => {
+                    i += 1;
+                    TokenKind::Open
+                }
+                b')' => {
+                    i += 1;
+                    TokenKind::Close
+                }
+                b'"' => {
+                    let mut contents = String::new();
+                    loop {
+                        i += 1;
+                        let Some(len) = text[i..].find('"') else {
+                            let span = Span::new(start, start + 1);
+                            return Err(Diagnostic::new("unterminated string literal", span));
+                        };
+
+                        contents.push_str(&text[i..i + len]);
+                        i += len + 1;
+
+                        // "" is an escaped quote
+                        if bytes.get(i) != Some(&b'"') {
+                            break;
+                        }
+                        contents.push('"');
+                    }
+
+                    *position = i;
+                    return Ok(Some(Token {
+                        kind: TokenKind::String,
+                        text: contents,
+                        span: Span::new(start, i),
+                    }));
+                }
+                b'|' => {
+                    let Some(len) = text[i + 1..].find('|') else {
+                        let span = Span::new(start, start + 1);
+                        return Err(Diagnostic::new("unterminated quoted symbol", span));
+                    };
+
+                    i += len + 2;
+                    *position = i;
+                    return Ok(Some(Token {
+                        kind: TokenKind::QuotedSymbol,
+                        text: text[start + 1..i - 1].to_string(),
+                        span: Span::new(start, i),
+                    }));
+                }
+                b'0'..=b'9' => {
+                    let mut kind = TokenKind::Numeral;
+                    while i < bytes.len() && bytes[i].is_ascii_digit() {
+                        i += 1;
+                    }
+
+                    if bytes.get(i) == Some(&b'.') {
+                        kind = TokenKind::Decimal;
+                        i += 1;
+                        let digits = i;
+                        while i < bytes.len() && bytes[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                        if i == digits {
+                            return Err(invalid_literal(text, start));
+                        }
+                    }
+
+                    kind
+                }
+                b'#' => {
+                    let (kind, is_digit): (_, fn(&u8) -> bool) = match bytes.get(i + 1) {
+                        Some(b'x') => (TokenKind::Hexadecimal, u8::is_ascii_hexdigit),
+                        Some(b'b') => (TokenKind::Binary, |c| matches!(c, b'0' | b'1')),
+                        _ => return Err(invalid_literal(text, start)),
+                    };
+
+                    i += 2;
+                    while i < bytes.len() && is_digit(&bytes[i]) {
+                        i += 1;
+                    }
+                    if i == start + 2 {
+                        return Err(invalid_literal(text, start));
+                    }
+
+                    kind
+                }
+                b':' => {
+                    i += 1;
+                    while i < bytes.len() && is_symbol_char(bytes[i]) {
+                        i += 1;
+                    }
+                    if i == start + 1 {
+                        let span = Span::new(start, start + 1);
+                        return Err(Diagnostic::new("expected a keyword after `:`", span));
+                    }
+
+                    TokenKind::Keyword
+                }
+                c if is_symbol_char(c) => {
+                    while i < bytes.len() && is_symbol_char(bytes[i]) {
+                        i += 1;
+                    }
+
+                    TokenKind::Symbol
+                }
+                _ => {
+                    let c = text[i..].chars().next().unwrap_or_default();
+                    let span = Span::new(start, start + c.len_utf8());
+                    return Err(Diagnostic::new(
+                        format!("unexpected character `{}`", c.escape_debug()),
+                        span,
+                    ));
+                }
+            };
+
+            // e.g. `12ab` or `#x1g`, which would otherwise be read as two tokens
+            let literal = matches!(
+                kind,
+                TokenKind::Numeral
+                    | TokenKind::Decimal
+                    | TokenKind::Hexadecimal
+                    | TokenKind::Binary
+            );
+            if literal && bytes.get(i).is_some_and(|&c| is_symbol_char(c)) {
+                return Err(invalid_literal(text, start));
+            }
+
+            *position = i;
+            return Ok(Some(Token {
+                kind,
+                text: text[start..i].to_string(),
+                span: Span::new(start, i),
+            }));
+        }
+
+        *position = i;
+        Ok(None)
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Result<Token, Diagnostic>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.scan().transpose()
+    }
+}
+
+/// Splits `text` into tokens, dropping whitespace and comments.
+pub fn tokenize(text: &str) -> Result<Vec<Token>, Diagnostic> {
+    Lexer::new(text).collect()
+}
+
+fn invalid_literal(text: &str, start: usize) -> Diagnostic {
+    let end = text[start + 1..]
+        .find(|c: char| !c.is_ascii() || !is_symbol_char(c as u8) && c != '#')
+        .map_or(text.len(), |i| start + 1 + i);
+    Diagnostic::new(
+        format!("invalid literal `{}`", &text[start..end]),
+        Span::new(start, end),
+    )
+}
+
+/// A token, or a parenthesized list of s-expressions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SExpr {
+    Atom(Token),
+    List(Vec<SExpr>, Span),
+}
+
+impl SExpr {
+    pub fn span(&self) -> Span {
+        match self {
+            SExpr::Atom(token) => token.span,
+            SExpr::List(_, span) => *span,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            SExpr::Atom(token) => token.describe(),
+            SExpr::List(items, _) if items.is_empty() => "`()`".to_string(),
+            SExpr::List(..) => "a list".to_string(),
+        }
+    }
+
+    fn is_reserved(&self, word: &str) -> bool {
+        matches!(self, SExpr::Atom(token) if token.is_reserved(word))
+    }
+
+    /// The reserved word this list starts with, if any.
+    fn head_is(&self, word: &str) -> bool {
+        matches!(self, SExpr::List(items, _) if items.first().is_some_and(|head| head.is_reserved(word)))
+    }
+}
+
+impl fmt::Display for SExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SExpr::Atom(token) => write!(f, "{}", token),
+            SExpr::List(items, _) => write_list(f, "", items),
+        }
+    }
+}
+
+/// Writes `(prefix items...)`, or `(items...)` if the prefix is empty.
+fn write_list<T: fmt::Display>(
+    f: &mut fmt::Formatter<'_>,
+    prefix: &str,
+    items: &[T],
+) -> fmt::Result {
+    f.write_str("(")?;
+    f.write_str(prefix)?;
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 || !prefix.is_empty() {
+            f.write_str(" ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    f.write_str(")")
+}
+
+/// Reads the top-level s-expressions of a script one at a time (without recursion,
+/// so that deeply nested terms can't overflow the stack here).
+///
+/// Stops after the first error.
+pub struct Reader<'a> {
+    lexer: Lexer<'a>,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(text: &'a str) -> Reader<'a> {
+        Reader {
+            lexer: Lexer::new(text),
+        }
+    }
+
+    /// A reader with a lenient lexer, see `Lexer::lenient`.
+    pub fn lenient(text: &'a str) -> Reader<'a> {
+        Reader {
+            lexer: Lexer::lenient(text),
+        }
+    }
+
+    fn read(&mut self) -> Result<Option<SExpr>, Diagnostic> {
+        let mut open: Vec<(usize, Vec<SExpr>)> = Vec::new();
+        while let Some(token) = self.lexer.next().transpose()? {
+            let sexpr = match token.kind {
+                TokenKind::Open => {
+                    open.push((token.span.start, Vec::new()));
+                    continue;
+                }
+                TokenKind::Close => {
+                    let Some((start, items)) = open.pop() else {
+                        return Err(Diagnostic::new("unexpected `)`", token.span));
+                    };
+                    SExpr::List(items, Span::new(start, token.span.end))
+                }
+                _ => SExpr::Atom(token),
+            };
+
+            match open.last_mut() {
+                Some((_, items)) => items.push(sexpr),
+                None => return Ok(Some(sexpr)),
+            }
+        }
+
+        match open.first() {
+            Some(&(start, _)) => Err(Diagnostic::new(
+                "unclosed `(`, the script ends before its `)`",
+                Span::new(start, start + 1),
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Iterator for Reader<'_> {
+    type Item = Result<SExpr, Diagnostic>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sexpr = self.read().transpose();
+        if matches!(sexpr, Some(Err(_))) {
+            self.lexer.offset = self.lexer.text.len();
+        }
+        sexpr
+    }
+}
+
+/// Groups the tokens of `text` into s-expressions.
+pub fn read_sexprs(text: &str) -> Result<Vec<SExpr>, Diagnostic> {
+    Reader::new(text).collect()
+}
+
+/// Splits text into its top-level s-expressions, each on one line: the tokens are
+/// read with a lenient lexer and written as they are in the text, separated by a
+/// single space (none after `(` or before `)`), and comments are dropped.
+///
+/// The text can come in pieces (e.g. the output of a solver, line by line), as long
+/// as each piece ends between two tokens. The s-expressions are not built, so there
+/// is no limit to how deep they can be nested.
+#[derive(Debug, Default)]
+pub struct Splitter {
+    /// text after the last complete token (spans are relative to it)
+    pending: String,
+    depth: usize,
+    current: String,
+}
+
+impl Splitter {
+    /// Adds `text`, and appends the s-expressions it completes to `out`.
+    pub fn feed(&mut self, text: &str, out: &mut Vec<String>) -> Result<(), Diagnostic> {
+        self.pending.push_str(text);
+        let mut lexer = Lexer::lenient(&self.pending);
+        let mut scanned = 0;
+        loop {
+            let token = match lexer.next() {
+                Some(Ok(token)) => token,
+                None => break,
+
+                // the literal may end in the next piece
+                Some(Err(error)) if is_unterminated(&error) => break,
+                Some(Err(error)) => return Err(error),
+            };
+
+            match token.kind {
+                TokenKind::Close if self.depth == 0 => {
+                    return Err(Diagnostic::new("unexpected `)`", token.span));
+                }
+                TokenKind::Close => self.depth -= 1,
+                _ if !self.current.is_empty() && !self.current.ends_with('(') => {
+                    self.current.push(' ')
+                }
+                _ => {}
+            }
+
+            if token.kind == TokenKind::Open {
+                self.depth += 1;
+            }
+
+            self.current
+                .push_str(&self.pending[token.span.start..token.span.end]);
+            scanned = token.span.end;
+            if self.depth == 0 {
+                out.push(mem::take(&mut self.current));
+            }
+        }
+
+        self.pending.drain(..scanned);
+        Ok(())
+    }
+
+    /// Ends the text, failing if an s-expression is left open.
+    pub fn finish(&mut self) -> Result<(), Diagnostic> {
+        if let Some(Err(error)) = Lexer::lenient(&self.pending).next() {
+            return Err(error);
+        }
+
+        if self.depth > 0 {
+            let end = self.pending.len();
+            return Err(Diagnostic::new(
+                "unclosed `(`, the text ends before its `)`",
+                Span::new(end, end),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits `text` into its top-level s-expressions, one per line, see `Splitter`.
+pub fn split_sexprs(text: &str) -> Result<Vec<String>, Diagnostic> {
+    let mut splitter = Splitter::default();
+    let mut sexprs = Vec::new();
+    splitter.feed(text, &mut sexprs)?;
+    splitter.finish()?;
+    Ok(sexprs)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub span: Span,
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&quote_symbol(&self.name))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Constant {
+    Numeral(String),
+    Decimal(String),
+
+    /// with the `#x` prefix
+    Hexadecimal(String),
+
+    /// with the `#b` prefix
+    Binary(String),
+
+    /// the contents of the literal, unescaped
+    String(String),
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constant::Numeral(text)
+            | Constant::Decimal(text)
+            | Constant::Hexadecimal(text)
+            | Constant::Binary(text) => f.write_str(text),
+            Constant::String(text) => f.write_str(&quote_string(text)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Index {
+    Numeral(String),
+    Symbol(String),
+}
+
+impl fmt::Display for Index {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Index::Numeral(numeral) => f.write_str(numeral),
+            Index::Symbol(name) => f.write_str(&quote_symbol(name)),
+        }
+    }
+}
+
+/// A symbol, or an indexed symbol like `(_ extract 7 0)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identifier {
+    pub symbol: Symbol,
+    pub indices: Vec<Index>,
+    pub span: Span,
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.indices.is_empty() {
+            return write!(f, "{}", self.symbol);
+        }
+
+        write!(f, "(_ {}", self.symbol)?;
+        for index in &self.indices {
+            write!(f, " {}", index)?;
+        }
+        f.write_str(")")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sort {
+    pub identifier: Identifier,
+    pub parameters: Vec<Sort>,
+    pub span: Span,
+}
+
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.parameters.is_empty() {
+            return write!(f, "{}", self.identifier);
+        }
+
+        write_list(f, &self.identifier.to_string(), &self.parameters)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortedVar {
+    pub symbol: Symbol,
+    pub sort: Sort,
+}
+
+impl fmt::Display for SortedVar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({} {})", self.symbol, self.sort)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VarBinding {
+    pub symbol: Symbol,
+    pub term: Term,
+}
+
+impl fmt::Display for VarBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({} {})", self.symbol, self.term)
+    }
+}
+
+/// `:keyword value`, as in `set-option` or `(! term :named x)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute {
+    /// with the `:`
+    pub keyword: String,
+    pub value: Option<SExpr>,
+    pub span: Span,
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.keyword)?;
+        match &self.value {
+            Some(value) => write!(f, " {}", value),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pattern {
+    /// a variable, or a constructor without arguments
+    Symbol(Symbol),
+    Constructor(Symbol, Vec<Symbol>),
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Symbol(symbol) => write!(f, "{}", symbol),
+            Pattern::Constructor(constructor, variables) => {
+                write_list(f, &constructor.to_string(), variables)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchCase {
+    pub pattern: Pattern,
+    pub term: Term,
+}
+
+impl fmt::Display for MatchCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({} {})", self.pattern, self.term)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TermKind {
+    Constant(Constant),
+
+    /// an identifier, with its sort if qualified (`(as nil (List Int))`)
+    Identifier(Identifier, Option<Sort>),
+    Application(Identifier, Option<Sort>, Vec<Term>),
+    Let(Vec<VarBinding>, Box<Term>),
+    Forall(Vec<SortedVar>, Box<Term>),
+    Exists(Vec<SortedVar>, Box<Term>),
+    Match(Box<Term>, Vec<MatchCase>),
+    Annotated(Box<Term>, Vec<Attribute>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Term {
+    pub kind: TermKind,
+    pub span: Span,
+}
+
+/// Writes an identifier, qualified with its sort if it has one.
+fn write_qualified(
+    f: &mut fmt::Formatter<'_>,
+    identifier: &Identifier,
+    sort: &Option<Sort>,
+) -> fmt::Result {
+    match sort {
+        Some(sort) => write!(f, "(as {} {})", identifier, sort),
+        None => write!(f, "{}", identifier),
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            TermKind::Constant(constant) => write!(f, "{}", constant),
+            TermKind::Identifier(identifier, sort) => write_qualified(f, identifier, sort),
+            TermKind::Application(identifier, sort, arguments) => {
+                f.write_str("(")?;
+                write_qualified(f, identifier, sort)?;
+                for argument in arguments {
+                    write!(f, " {}", argument)?;
+                }
+                f.write_str(")")
+            }
+            TermKind::Let(bindings, body) => {
+                f.write_str("(let ")?;
+                write_list(f, "", bindings)?;
+                write!(f, " {})", body)
+            }
+            TermKind::Forall(variables, body) | TermKind::Exists(variables, body) => {
+                let binder = match &self.kind {
+                    TermKind::Forall(..) => "forall",
+                    _ => "exists",
+                };
+                write!(f, "({} ", binder)?;
+                write_list(f, "", variables)?;
+                write!(f, " {})", body)
+            }
+            TermKind::Match(term, cases) => {
+                write!(f, "(match {} ", term)?;
+                write_list(f, "", cases)?;
+                f.write_str(")")
+            }
+            TermKind::Annotated(term, attributes) => {
+                write!(f, "(! {}", term)?;
+                for attribute in attributes {
+                    write!(f, " {}", attribute)?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+/// A function in `declare-fun`-like position: `(f ((x Int)) Bool)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionDec {
+    pub name: Symbol,
+    pub parameters: Vec<SortedVar>,
+    pub sort: Sort,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectorDec {
+    pub name: Symbol,
+    pub sort: Sort,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstructorDec {
+    pub name: Symbol,
+    pub selectors: Vec<SelectorDec>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatatypeDec {
+    /// sort parameters, from `(par (T) ...)`
+    pub parameters: Vec<Symbol>,
+    pub constructors: Vec<ConstructorDec>,
+}
+
+/// A sort declared by `declare-datatypes`, with its number of parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortDec {
+    pub name: Symbol,
+    pub arity: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandKind {
+    Assert(Term),
+    CheckSat,
+    CheckSatAssuming(Vec<Term>),
+    DeclareConst(Symbol, Sort),
+    DeclareDatatype(Symbol, DatatypeDec),
+    DeclareDatatypes(Vec<SortDec>, Vec<DatatypeDec>),
+    DeclareFun(Symbol, Vec<Sort>, Sort),
+    DeclareSort(Symbol, usize),
+    DefineFun(FunctionDec, Term),
+    DefineFunRec(FunctionDec, Term),
+    DefineFunsRec(Vec<FunctionDec>, Vec<Term>),
+    DefineSort(Symbol, Vec<Symbol>, Sort),
+    Echo(String),
+    Exit,
+    GetAssertions,
+    GetAssignment,
+    GetInfo(String),
+    GetModel,
+    GetOption(String),
+    GetProof,
+    GetUnsatAssumptions,
+    GetUnsatCore,
+    GetValue(Vec<Term>),
+    Pop(usize),
+    Push(usize),
+    Reset,
+    ResetAssertions,
+    SetInfo(Attribute),
+    SetLogic(Symbol),
+    SetOption(Attribute),
+
+    /// a command outside of the standard, with its arguments
+    Other(Symbol, Vec<SExpr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    pub kind: CommandKind,
+
+    /// from the `(` to the `)` of the command
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Script {
+    pub commands: Vec<Command>,
+}
+
+impl Script {
+    /// The logic of the script, from its (first) `set-logic`.
+    pub fn logic(&self) -> Option<&str> {
+        self.commands
+            .iter()
+            .find_map(|command| match &command.kind {
+                CommandKind::SetLogic(logic) => Some(logic.name.as_str()),
+                _ => None,
+            })
+    }
+}
+
+/// Parses a script. Terms nested thousands of levels deep need more than the usual
+/// stack, see `with_parser_stack`.
+pub fn parse(text: &str) -> Result<Script, Diagnostic> {
+    let commands = Reader::new(text)
+        .map(|sexpr| command(sexpr?))
+        .collect::<Result<_, _>>()?;
+
+    Ok(Script { commands })
+}
+
+/// Runs `f` on a thread with a stack large enough to parse terms up to `MAX_DEPTH`
+/// deep (or on this thread if that thread can't be started).
+pub fn with_parser_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    // the closure is taken by whichever thread ends up running it
+    let f = Mutex::new(Some(f));
+    let run = || (f.lock().unwrap().take().unwrap())();
+
+    thread::scope(|scope| {
+        let worker = thread::Builder::new()
+            .stack_size(PARSER_STACK_SIZE)
+            .spawn_scoped(scope, run);
+
+        match worker {
+            Ok(worker) => worker
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic)),
+            Err(_) => run(),
+        }
+    })
+}
+
+/// Checks that `text` parses, with `with_parser_stack`. Commands with terms nested
+/// deeper than `MAX_DEPTH` are skipped (solvers don't have such a limit), only
+/// their parentheses are checked.
+pub fn check(text: &str) -> Result<(), Diagnostic> {
+    with_parser_stack(|| check_commands(text))
+}
+
+fn check_commands(text: &str) -> Result<(), Diagnostic> {
+    for sexpr in Reader::new(text) {
+        match command(sexpr?) {
+            Err(e) if e.message != too_deep(e.span).message => return Err(e),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+type Args = Peekable<vec::IntoIter<SExpr>>;
+
+fn expected(what: &str, found: &SExpr) -> Diagnostic {
+    Diagnostic::new(
+        format!("expected {}, found {}", what, found.describe()),
+        found.span(),
+    )
+}
+
+/// The next argument of `command`, or an error if it has no more.
+fn next_arg(
+    args: &mut Args,
+    command: &Symbol,
+    span: Span,
+    what: &str,
+) -> Result<SExpr, Diagnostic> {
+    args.next().ok_or_else(|| {
+        Diagnostic::new(
+            format!("missing {} in `{}`", what, command.name),
+            Span::new(span.end - 1, span.end),
+        )
+    })
+}
+
+/// Fails if `command` has arguments left.
+fn no_more_args(args: &mut Args, command: &Symbol) -> Result<(), Diagnostic> {
+    match args.next() {
+        Some(extra) => Err(Diagnostic::new(
+            format!("unexpected argument to `{}`", command.name),
+            extra.span(),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn symbol(sexpr: SExpr) -> Result<Symbol, Diagnostic> {
+    match sexpr {
+        SExpr::Atom(token)
+            if token.kind == TokenKind::QuotedSymbol
+                || token.kind == TokenKind::Symbol && !RESERVED.contains(&token.text.as_str()) =>
+        {
+            Ok(Symbol {
+                name: token.text,
+                span: token.span,
+            })
+        }
+        other => Err(expected("a symbol", &other)),
+    }
+}
+
+fn numeral(sexpr: SExpr) -> Result<usize, Diagnostic> {
+    match sexpr {
+        SExpr::Atom(token) if token.kind == TokenKind::Numeral => token
+            .text
+            .parse()
+            .map_err(|_| Diagnostic::new(format!("numeral too large: {}", token.text), token.span)),
+        other => Err(expected("a numeral", &other)),
+    }
+}
+
+fn keyword(sexpr: SExpr) -> Result<String, Diagnostic> {
+    match sexpr {
+        SExpr::Atom(token) if token.kind == TokenKind::Keyword => Ok(token.text),
+        other => Err(expected("a keyword", &other)),
+    }
+}
+
+/// The items of a list, `what` describing the expected list.
+fn list(sexpr: SExpr, what: &str) -> Result<(Vec<SExpr>, Span), Diagnostic> {
+    match sexpr {
+        SExpr::List(items, span) => Ok((items, span)),
+        other => Err(expected(what, &other)),
+    }
+}
+
+/// Reads `:keyword value` pairs (the value is optional) until the end of `args`.
+fn attributes(args: &mut Args) -> Result<Vec<Attribute>, Diagnostic> {
+    let mut attributes = Vec::new();
+    while let Some(sexpr) = args.next() {
+        let span = sexpr.span();
+        let keyword = keyword(sexpr)?;
+        let value = args.next_if(
+            |value| !matches!(value, SExpr::Atom(token) if token.kind == TokenKind::Keyword),
+        );
+
+        let end = value.as_ref().map_or(span.end, |value| value.span().end);
+        attributes.push(Attribute {
+            keyword,
+            value,
+            span: Span::new(span.start, end),
+        });
+    }
+
+    Ok(attributes)
+}
+
+fn index(sexpr: SExpr) -> Result<Index, Diagnostic> {
+    match sexpr {
+        SExpr::Atom(token) if token.kind == TokenKind::Numeral => Ok(Index::Numeral(token.text)),
+        SExpr::Atom(token) if token.kind == TokenKind::QuotedSymbol => {
+            Ok(Index::Symbol(token.text))
+        }
+        SExpr::Atom(token) if token.kind == TokenKind::Symbol => {
+            symbol(SExpr::Atom(token)).map(|symbol| Index::Symbol(symbol.name))
+        }
+        other => Err(expected("a numeral or a symbol as index", &other)),
+    }
+}
+
+/// A symbol, or `(_ symbol index+)`.
+fn identifier(sexpr: SExpr) -> Result<Identifier, Diagnostic> {
+    if !sexpr.head_is("_") {
+        let symbol = symbol(sexpr).map_err(|e| Diagnostic {
+            message: e.message.replace("a symbol", "an identifier"),
+            ..e
+        })?;
+        let span = symbol.span;
+        return Ok(Identifier {
+            symbol,
+            indices: Vec::new(),
+            span,
+        });
+    }
+
+    let (items, span) = list(sexpr, "an identifier")?;
+    let mut args = items.into_iter().skip(1);
+    let Some(name) = args.next() else {
+        return Err(Diagnostic::new("missing symbol after `_`", span));
+    };
+
+    let symbol = symbol(name)?;
+    let indices: Vec<Index> = args.map(index).collect::<Result<_, _>>()?;
+    if indices.is_empty() {
+        return Err(Diagnostic::new(
+            format!("missing indices for `{}`", symbol.name),
+            span,
+        ));
+    }
+
+    Ok(Identifier {
+        symbol,
+        indices,
+        span,
+    })
+}
+
+/// An identifier, with its sort if it is written `(as identifier sort)`.
+fn qualified_identifier(
+    sexpr: SExpr,
+    depth: usize,
+) -> Result<(Identifier, Option<Sort>), Diagnostic> {
+    if !sexpr.head_is("as") {
+        return Ok((identifier(sexpr)?, None));
+    }
+
+    let (items, span) = list(sexpr, "an identifier")?;
+    let as_keyword = Symbol {
+        name: "as".to_string(),
+        span,
+    };
+    let mut args = items.into_iter().peekable();
+    args.next();
+    let identifier = identifier(next_arg(&mut args, &as_keyword, span, "an identifier")?)?;
+    let sort = sort(next_arg(&mut args, &as_keyword, span, "a sort")?, depth + 1)?;
+    no_more_args(&mut args, &as_keyword)?;
+    Ok((identifier, Some(sort)))
+}
+
+fn too_deep(span: Span) -> Diagnostic {
+    Diagnostic::new(format!("nested more than {} levels deep", MAX_DEPTH), span)
+}
+
+fn check_depth(depth: usize, span: Span) -> Result<(), Diagnostic> {
+    if depth > MAX_DEPTH {
+        return Err(too_deep(span));
+    }
+
+    Ok(())
+}
+
+fn sort(sexpr: SExpr, depth: usize) -> Result<Sort, Diagnostic> {
+    check_depth(depth, sexpr.span())?;
+    let span = sexpr.span();
+    if !matches!(sexpr, SExpr::List(..)) || sexpr.head_is("_") {
+        let identifier = identifier(sexpr).map_err(|e| Diagnostic {
+            message: e.message.replace("an identifier", "a sort"),
+            ..e
+        })?;
+        return Ok(Sort {
+            identifier,
+            parameters: Vec::new(),
+            span,
+        });
+    }
+
+    let (items, span) = list(sexpr, "a sort")?;
+    let mut items = items.into_iter();
+    let Some(head) = items.next() else {
+        return Err(Diagnostic::new("expected a sort, found `()`", span));
+    };
+
+    let identifier = identifier(head)?;
+    let parameters: Vec<Sort> = items
+        .map(|parameter| sort(parameter, depth + 1))
+        .collect::<Result<_, _>>()?;
+    if parameters.is_empty() {
+        return Err(Diagnostic::new(
+            format!("missing parameters for sort `{}`", identifier),
+            span,
+        ));
+    }
+
+    Ok(Sort {
+        identifier,
+        parameters,
+        span,
+    })
+}
+
+fn sorted_vars(sexpr: SExpr, depth: usize) -> Result<Vec<SortedVar>, Diagnostic> {
+    let (items, _) = list(sexpr, "a list of sorted variables")?;
+    items
+        .into_iter()
+        .map(|item| {
+            let (pair, span) = list(item, "a sorted variable `(name sort)`")?;
+            let [name, sort_sexpr]: [SExpr; 2] = pair
+                .try_into()
+                .map_err(|_| Diagnostic::new("expected a sorted variable `(name sort)`", span))?;
+
+            Ok(SortedVar {
+                symbol: symbol(name)?,
+                sort: sort(sort_sexpr, depth + 1)?,
+            })
+        })
+        .collect()
+}
+
+fn terms(sexprs: Vec<SExpr>, depth: usize) -> Result<Vec<Term>, Diagnostic> {
+    // a loop rather than `collect`, which would take more stack for each level
+    let mut terms = Vec::with_capacity(sexprs.len());
+    for sexpr in sexprs {
+        terms.push(term(sexpr, depth)?);
+    }
+
+    Ok(terms)
+}
+
+fn term(sexpr: SExpr, depth: usize) -> Result<Term, Diagnostic> {
+    check_depth(depth, sexpr.span())?;
+    let span = sexpr.span();
+    let kind = match sexpr {
+        SExpr::Atom(token) => match token.kind {
+            TokenKind::Numeral => TermKind::Constant(Constant::Numeral(token.text)),
+            TokenKind::Decimal => TermKind::Constant(Constant::Decimal(token.text)),
+            TokenKind::Hexadecimal => TermKind::Constant(Constant::Hexadecimal(token.text)),
+            TokenKind::Binary => TermKind::Constant(Constant::Binary(token.text)),
+            TokenKind::String => TermKind::Constant(Constant::String(token.text)),
+            TokenKind::Symbol | TokenKind::QuotedSymbol => {
+                TermKind::Identifier(identifier(SExpr::Atom(token))?, None)
+            }
+            _ => return Err(expected("a term", &SExpr::Atom(token))),
+        },
+        sexpr if sexpr.head_is("_") || sexpr.head_is("as") => {
+            let (identifier, sort) = qualified_identifier(sexpr, depth)?;
+            TermKind::Identifier(identifier, sort)
+        }
+        SExpr::List(items, span) => compound_term(items, span, depth)?,
+    };
+
+    Ok(Term { kind, span })
+}
+
+/// A function application, or a binder (`let`, `forall`, `exists`, `match` or `!`).
+fn compound_term(items: Vec<SExpr>, span: Span, depth: usize) -> Result<TermKind, Diagnostic> {
+    let mut items = items.into_iter().peekable();
+    let Some(head) = items.next() else {
+        return Err(Diagnostic::new("expected a term, found `()`", span));
+    };
+
+    let binder = ["let", "forall", "exists", "match", "!"]
+        .into_iter()
+        .find(|word| head.is_reserved(word));
+    if let Some(binder) = binder {
+        let keyword = Symbol {
+            name: binder.to_string(),
+            span: head.span(),
+        };
+        return binder_term(keyword, &mut items, span, depth);
+    }
+
+    let (identifier, sort) = qualified_identifier(head, depth).map_err(|e| Diagnostic {
+        message: e.message.replace("an identifier", "a function"),
+        ..e
+    })?;
+    let arguments = terms(items.collect(), depth + 1)?;
+    if arguments.is_empty() {
+        return Err(Diagnostic::new(
+            format!("missing arguments for `{}`", identifier),
+            span,
+        ));
+    }
+
+    Ok(TermKind::Application(identifier, sort, arguments))
+}
+
+fn binder_term(
+    keyword: Symbol,
+    items: &mut Args,
+    span: Span,
+    depth: usize,
+) -> Result<TermKind, Diagnostic> {
+    let kind = match keyword.name.as_str() {
+        "let" => {
+            let (items_list, _) = list(
+                next_arg(items, &keyword, span, "bindings")?,
+                "a list of bindings",
+            )?;
+            let mut bindings = Vec::with_capacity(items_list.len());
+            for item in items_list {
+                let (pair, span) = list(item, "a binding `(name term)`")?;
+                let [name, value]: [SExpr; 2] = pair
+                    .try_into()
+                    .map_err(|_| Diagnostic::new("expected a binding `(name term)`", span))?;
+
+                bindings.push(VarBinding {
+                    symbol: symbol(name)?,
+                    term: term(value, depth + 1)?,
+                });
+            }
+
+            let body = term(next_arg(items, &keyword, span, "a body")?, depth + 1)?;
+            TermKind::Let(bindings, Box::new(body))
+        }
+        "forall" | "exists" => {
+            let variables = sorted_vars(next_arg(items, &keyword, span, "variables")?, depth)?;
+            let body = Box::new(term(next_arg(items, &keyword, span, "a body")?, depth + 1)?);
+            match keyword.name.as_str() {
+                "forall" => TermKind::Forall(variables, body),
+                _ => TermKind::Exists(variables, body),
+            }
+        }
+        "match" => {
+            let scrutinee = term(next_arg(items, &keyword, span, "a term")?, depth + 1)?;
+            let (cases_list, _) =
+                list(next_arg(items, &keyword, span, "cases")?, "a list of cases")?;
+            let mut cases = Vec::with_capacity(cases_list.len());
+            for case in cases_list {
+                cases.push(match_case(case, depth + 1)?);
+            }
+            TermKind::Match(Box::new(scrutinee), cases)
+        }
+        _ => {
+            let annotated = term(next_arg(items, &keyword, span, "a term")?, depth + 1)?;
+            let attributes = attributes(items)?;
+            if attributes.is_empty() {
+                return Err(Diagnostic::new("missing attributes in `!`", span));
+            }
+            TermKind::Annotated(Box::new(annotated), attributes)
+        }
+    };
+
+    no_more_args(items, &keyword)?;
+    Ok(kind)
+}
+
+fn match_case(sexpr: SExpr, depth: usize) -> Result<MatchCase, Diagnostic> {
+    let (pair, span) = list(sexpr, "a case `(pattern term)`")?;
+    let [pattern, value]: [SExpr; 2] = pair
+        .try_into()
+        .map_err(|_| Diagnostic::new("expected a case `(pattern term)`", span))?;
+
+    let pattern = match pattern {
+        SExpr::List(items, span) => {
+            let mut items = items.into_iter();
+            let Some(constructor) = items.next() else {
+                return Err(Diagnostic::new("expected a pattern, found `()`", span));
+            };
+            let constructor = symbol(constructor)?;
+            let variables: Vec<Symbol> = items.map(symbol).collect::<Result<_, _>>()?;
+            Pattern::Constructor(constructor, variables)
+        }
+        atom => Pattern::Symbol(symbol(atom)?),
+    };
+
+    Ok(MatchCase {
+        pattern,
+        term: term(value, depth)?,
+    })
+}
+
+fn function_dec(sexpr: SExpr) -> Result<FunctionDec, Diagnostic> {
+    let (items, span) = list(sexpr, "a function declaration `(name (params) sort)`")?;
+    let [name, parameters, result]: [SExpr; 3] = items.try_into().map_err(|_| {
+        Diagnostic::new(
+            "expected a function declaration `(name (params) sort)`",
+            span,
+        )
+    })?;
+
+    Ok(FunctionDec {
+        name: symbol(name)?,
+        parameters: sorted_vars(parameters, 0)?,
+        sort: sort(result, 0)?,
+    })
+}
+
+/// A constructor: `(name (selector sort)*)`, or just `name`.
+fn constructor_dec(sexpr: SExpr) -> Result<ConstructorDec, Diagnostic> {
+    let SExpr::List(items, span) = sexpr else {
+        return Ok(ConstructorDec {
+            name: symbol(sexpr)?,
+            selectors: Vec::new(),
+        });
+    };
+
+    let mut items = items.into_iter();
+    let Some(name) = items.next() else {
+        return Err(Diagnostic::new("expected a constructor, found `()`", span));
+    };
+
+    let selectors = items
+        .map(|item| {
+            let (pair, span) = list(item, "a selector `(name sort)`")?;
+            let [name, selector_sort]: [SExpr; 2] = pair
+                .try_into()
+                .map_err(|_| Diagnostic::new("expected a selector `(name sort)`", span))?;
+
+            Ok(SelectorDec {
+                name: symbol(name)?,
+                sort: sort(selector_sort, 0)?,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(ConstructorDec {
+        name: symbol(name)?,
+        selectors,
+    })
+}
+
+/// `(constructor+)` or `(par (parameter+) (constructor+))`.
+fn datatype_dec(sexpr: SExpr) -> Result<DatatypeDec, Diagnostic> {
+    let is_par = sexpr.head_is("par");
+    let (items, span) = list(sexpr, "a datatype declaration")?;
+    if !is_par {
+        return constructors(items, span, Vec::new());
+    }
+
+    let [_, parameters, constructor_list]: [SExpr; 3] = items
+        .try_into()
+        .map_err(|_| Diagnostic::new("expected `(par (parameters) (constructors))`", span))?;
+    let (parameters, _) = list(parameters, "a list of sort parameters")?;
+    let parameters = parameters
+        .into_iter()
+        .map(symbol)
+        .collect::<Result<_, _>>()?;
+    let (items, span) = list(constructor_list, "a list of constructors")?;
+    constructors(items, span, parameters)
+}
+
+fn constructors(
+    items: Vec<SExpr>,
+    span: Span,
+    parameters: Vec<Symbol>,
+) -> Result<DatatypeDec, Diagnostic> {
+    if items.is_empty() {
+        return Err(Diagnostic::new(
+            "a datatype needs at least one constructor",
+            span,
+        ));
+    }
+
+    Ok(DatatypeDec {
+        parameters,
+        constructors: items
+            .into_iter()
+            .map(constructor_dec)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+/// The arguments of `declare-datatypes`, in the SMT-LIB 2.6 form
+/// (`((name arity)+) (datatype+)`) or in the 2.5 form (`(parameters)
+/// ((name constructor+)+)`), which z3 still accepts.
+fn declare_datatypes(
+    sorts: SExpr,
+    datatypes: SExpr,
+) -> Result<(Vec<SortDec>, Vec<DatatypeDec>), Diagnostic> {
+    let (sorts, sorts_span) = list(sorts, "a list of sorts")?;
+    let (datatypes, datatypes_span) = list(datatypes, "a list of datatypes")?;
+
+    if sorts
+        .first()
+        .is_none_or(|first| matches!(first, SExpr::Atom(_)))
+    {
+        let parameters: Vec<Symbol> = sorts.into_iter().map(symbol).collect::<Result<_, _>>()?;
+        let mut declared = Vec::new();
+        let mut decs = Vec::new();
+        for datatype in datatypes {
+            let (items, span) = list(datatype, "a datatype `(name constructor+)`")?;
+            let mut items = items.into_iter();
+            let Some(name) = items.next() else {
+                return Err(Diagnostic::new("expected a datatype, found `()`", span));
+            };
+
+            declared.push(SortDec {
+                name: symbol(name)?,
+                arity: parameters.len(),
+            });
+            decs.push(constructors(items.collect(), span, parameters.clone())?);
+        }
+
+        return Ok((declared, decs));
+    }
+
+    let declared: Vec<SortDec> = sorts
+        .into_iter()
+        .map(|item| {
+            let (pair, span) = list(item, "a sort declaration `(name arity)`")?;
+            let [name, arity]: [SExpr; 2] = pair
+                .try_into()
+                .map_err(|_| Diagnostic::new("expected a sort declaration `(name arity)`", span))?;
+
+            Ok(SortDec {
+                name: symbol(name)?,
+                arity: numeral(arity)?,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    if declared.len() != datatypes.len() {
+        let span = Span::new(sorts_span.start, datatypes_span.end);
+        return Err(Diagnostic::new(
+            format!(
+                "{} sort(s) declared, but {} datatype(s) defined",
+                declared.len(),
+                datatypes.len()
+            ),
+            span,
+        ));
+    }
+
+    let decs = datatypes
+        .into_iter()
+        .map(datatype_dec)
+        .collect::<Result<_, _>>()?;
+    Ok((declared, decs))
+}
+
+/// `(term*)`, as in `get-value` or `check-sat-assuming`.
+fn term_list(sexpr: SExpr) -> Result<Vec<Term>, Diagnostic> {
+    let (items, _) = list(sexpr, "a list of terms")?;
+    terms(items, 0)
+}
+
+fn command(sexpr: SExpr) -> Result<Command, Diagnostic> {
+    let (items, span) = match sexpr {
+        SExpr::List(items, span) if !items.is_empty() => (items, span),
+        other => return Err(expected("a command", &other)),
+    };
+
+    let mut args = items.into_iter().peekable();
+    let name =
+        symbol(args.next().unwrap_or_else(|| unreachable!("checked above"))).map_err(|e| {
+            Diagnostic {
+                message: e.message.replace("a symbol", "a command name"),
+                ..e
+            }
+        })?;
+
+    let mut arg = |what: &str| next_arg(&mut args, &name, span, what);
+    let kind = match name.name.as_str() {
+        "assert" => CommandKind::Assert(term(arg("a term")?, 0)?),
+        "check-sat" => CommandKind::CheckSat,
+        "check-sat-assuming" => CommandKind::CheckSatAssuming(term_list(arg("literals")?)?),
+        "declare-const" => {
+            let constant = symbol(arg("a name")?)?;
+            CommandKind::DeclareConst(constant, sort(arg("a sort")?, 0)?)
+        }
+        "declare-datatype" => {
+            let datatype = symbol(arg("a name")?)?;
+            CommandKind::DeclareDatatype(datatype, datatype_dec(arg("constructors")?)?)
+        }
+        "declare-datatypes" => {
+            let sorts = arg("sorts")?;
+            let (declared, decs) = declare_datatypes(sorts, arg("datatypes")?)?;
+            CommandKind::DeclareDatatypes(declared, decs)
+        }
+        "declare-fun" => {
+            let function = symbol(arg("a name")?)?;
+            let (parameters, _) = list(arg("parameter sorts")?, "a list of sorts")?;
+            let parameters = parameters
+                .into_iter()
+                .map(|parameter| sort(parameter, 0))
+                .collect::<Result<_, _>>()?;
+            CommandKind::DeclareFun(function, parameters, sort(arg("a sort")?, 0)?)
+        }
+        "declare-sort" => {
+            let declared = symbol(arg("a name")?)?;
+            let arity = match args.next() {
+                Some(arity) => numeral(arity)?,
+                None => 0,
+            };
+            CommandKind::DeclareSort(declared, arity)
+        }
+        "define-fun" | "define-fun-rec" => {
+            let function = symbol(arg("a name")?)?;
+            let parameters = sorted_vars(arg("parameters")?, 0)?;
+            let result = sort(arg("a sort")?, 0)?;
+            let body = term(arg("a body")?, 0)?;
+            let dec = FunctionDec {
+                name: function,
+                parameters,
+                sort: result,
+            };
+
+            match name.name.as_str() {
+                "define-fun" => CommandKind::DefineFun(dec, body),
+                _ => CommandKind::DefineFunRec(dec, body),
+            }
+        }
+        "define-funs-rec" => {
+            let (decs, decs_span) = list(arg("declarations")?, "a list of declarations")?;
+            let (bodies, bodies_span) = list(arg("bodies")?, "a list of bodies")?;
+            if decs.len() != bodies.len() {
+                return Err(Diagnostic::new(
+                    format!(
+                        "{} function(s) declared, but {} bodies given",
+                        decs.len(),
+                        bodies.len()
+                    ),
+                    Span::new(decs_span.start, bodies_span.end),
+                ));
+            }
+
+            let decs = decs
+                .into_iter()
+                .map(function_dec)
+                .collect::<Result<_, _>>()?;
+            CommandKind::DefineFunsRec(decs, terms(bodies, 0)?)
+        }
+        "define-sort" => {
+            let defined = symbol(arg("a name")?)?;
+            let (parameters, _) = list(arg("parameters")?, "a list of sort parameters")?;
+            let parameters = parameters
+                .into_iter()
+                .map(symbol)
+                .collect::<Result<_, _>>()?;
+            CommandKind::DefineSort(defined, parameters, sort(arg("a sort")?, 0)?)
+        }
+        "echo" => match arg("a string")? {
+            SExpr::Atom(token) if token.kind == TokenKind::String => CommandKind::Echo(token.text),
+            other => return Err(expected("a string literal", &other)),
+        },
+        "exit" => CommandKind::Exit,
+        "get-assertions" => CommandKind::GetAssertions,
+        "get-assignment" => CommandKind::GetAssignment,
+        "get-info" => CommandKind::GetInfo(keyword(arg("a keyword")?)?),
+        "get-model" => CommandKind::GetModel,
+        "get-option" => CommandKind::GetOption(keyword(arg("a keyword")?)?),
+        "get-proof" => CommandKind::GetProof,
+        "get-unsat-assumptions" => CommandKind::GetUnsatAssumptions,
+        "get-unsat-core" => CommandKind::GetUnsatCore,
+        "get-value" => {
+            let values = term_list(arg("terms")?)?;
+            if values.is_empty() {
+                return Err(Diagnostic::new("missing terms in `get-value`", span));
+            }
+            CommandKind::GetValue(values)
+        }
+        "pop" | "push" => {
+            let levels = match args.next() {
+                Some(levels) => numeral(levels)?,
+                None => 1,
+            };
+
+            match name.name.as_str() {
+                "pop" => CommandKind::Pop(levels),
+                _ => CommandKind::Push(levels),
+            }
+        }
+        "reset" => CommandKind::Reset,
+        "reset-assertions" => CommandKind::ResetAssertions,
+        "set-info" | "set-option" => {
+            let mut attributes = attributes(&mut args)?;
+            if attributes.len() != 1 {
+                return Err(Diagnostic::new(
+                    format!("`{}` expects a single attribute", name.name),
+                    span,
+                ));
+            }
+
+            let attribute = attributes.remove(0);
+            match name.name.as_str() {
+                "set-info" => CommandKind::SetInfo(attribute),
+                _ => CommandKind::SetOption(attribute),
+            }
+        }
+        "set-logic" => CommandKind::SetLogic(symbol(arg("a logic")?)?),
+        _ => {
+            return Ok(Command {
+                kind: CommandKind::Other(name, args.collect()),
+                span,
+            })
+        }
+    };
+
+    no_more_args(&mut args, &name)?;
+    Ok(Command { kind, span })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(text: &str) -> String {
+        parse(text).unwrap_err().summary("t.smt2", text)
+    }
+
+    #[test]
+    fn tokenize_literals_and_spans() {
+        let text = "(echo \"a \"\"b\"\"\") ; comment\n|x y| :named #x0F #b01 1.50 007";
+        let tokens = tokenize(text).unwrap();
+        let kinds: Vec<TokenKind> = tokens.iter().map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::Open,
+                TokenKind::Symbol,
+                TokenKind::String,
+                TokenKind::Close,
+                TokenKind::QuotedSymbol,
+                TokenKind::Keyword,
+                TokenKind::Hexadecimal,
+                TokenKind::Binary,
+                TokenKind::Decimal,
+                TokenKind::Numeral,
+            ]
+        );
+
+        assert_eq!(tokens[2].text, "a \"b\"");
+        assert_eq!(
+            &text[tokens[2].span.start..tokens[2].span.end],
+            "\"a \"\"b\"\"\""
+        );
+        assert_eq!(tokens[4].text, "x y");
+        assert_eq!(tokens[4].to_string(), "|x y|");
+        assert_eq!(tokens[6].text, "#x0F");
+
+        let errors = [
+            ("(echo \"abc)", "t.smt2:1:7: unterminated string literal"),
+            ("(assert |x)", "t.smt2:1:9: unterminated quoted symbol"),
+            ("(assert (> x 12ab))", "t.smt2:1:14: invalid literal `12ab`"),
+            ("(assert (= x #xg))", "t.smt2:1:14: invalid literal `#xg`"),
+            (
+                "(assert\n  (= x {}))",
+                "t.smt2:2:8: unexpected character `{`",
+            ),
+            (
+                "(set-option : true)",
+                "t.smt2:1:13: expected a keyword after `:`",
+            ),
+        ];
+
+        for (text, message) in errors {
+            assert_eq!(parse_error(text), message, "{}", text);
+        }
+    }
+
+    #[test]
+    fn lenient_lexer_reads_invalid_tokens_as_symbols() {
+        let text = "(= x 0b0101) {a} 12ab:c : é|q|";
+        let tokens: Vec<String> = Lexer::lenient(text)
+            .map(|token| token.unwrap().text)
+            .collect();
+        assert_eq!(
+            tokens,
+            ["(", "=", "x", "0b0101", ")", "{a}", "12ab:c", ":", "é", "q"]
+        );
+
+        let mut lexer = Lexer::lenient("(echo \"abc)");
+        assert!(lexer.by_ref().take(2).all(|token| token.is_ok()));
+        assert_eq!(
+            lexer.next().unwrap().unwrap_err().message,
+            "unterminated string literal"
+        );
+    }
+
+    #[test]
+    fn splits_text_in_pieces() {
+        let text = "; comment\n(set-logic QF_BV)\n(assert\n  (= x |a ; b|))(check-sat)\n\
+                    sat ( echo \"hello\n( world\" )";
+        assert_eq!(
+            split_sexprs(text).unwrap(),
+            [
+                "(set-logic QF_BV)",
+                "(assert (= x |a ; b|))",
+                "(check-sat)",
+                "sat",
+                "(echo \"hello\n( world\")"
+            ]
+        );
+
+        // the same, line by line
+        let mut splitter = Splitter::default();
+        let mut sexprs = Vec::new();
+        for line in text.split_inclusive('\n') {
+            splitter.feed(line, &mut sexprs).unwrap();
+        }
+        splitter.finish().unwrap();
+        assert_eq!(sexprs, split_sexprs(text).unwrap());
+
+        let errors = [
+            ("(check-sat))", "unexpected `)`"),
+            (
+                "(assert (= x y)",
+                "unclosed `(`, the text ends before its `)`",
+            ),
+            ("(echo \"abc)", "unterminated string literal"),
+        ];
+        for (text, message) in errors {
+            assert_eq!(split_sexprs(text).unwrap_err().message, message, "{}", text);
+        }
+
+        // nesting depth doesn't matter
+        let deep = format!("{}{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert_eq!(split_sexprs(&deep).unwrap(), [deep]);
+    }
+
+    #[test]
+    fn parses_scripts() {
+        let text = "\
+(set-info :status sat)
+(set-option :produce-models true)
+(set-logic QF_AUFBV)
+(declare-sort U 0)
+(declare-const x (_ BitVec 8))
+(declare-fun a ((_ BitVec 8)) (Array (_ BitVec 8) U))
+(define-fun f ((y (_ BitVec 8))) Bool (bvult y #x10))
+(declare-datatype Pair (par (T) ((mk (fst T) (snd T)))))
+(declare-datatypes () ((Color red green)))
+(push)
+(assert (! (let ((z ((_ extract 3 0) x))) (f (concat z z))) :named c1))
+(assert (forall ((p (Pair Int))) (match p (((mk l r) (= l r))))))
+(check-sat-assuming (c1 (not c1)))
+(get-value (x (as red Color)))
+(minimize x)
+(pop 1)
+(exit)
+";
+        let script = parse(text).unwrap();
+        assert_eq!(script.commands.len(), 17);
+        assert_eq!(script.logic(), Some("QF_AUFBV"));
+
+        let CommandKind::Assert(term) = &script.commands[10].kind else {
+            panic!("expected an assert: {:?}", script.commands[10]);
+        };
+        assert_eq!(
+            term.to_string(),
+            "(! (let ((z ((_ extract 3 0) x))) (f (concat z z))) :named c1)"
+        );
+        assert_eq!(
+            &text[term.span.start..term.span.end],
+            "(! (let ((z ((_ extract 3 0) x))) (f (concat z z))) :named c1)"
+        );
+
+        let CommandKind::DeclareDatatypes(sorts, datatypes) = &script.commands[8].kind else {
+            panic!("expected declare-datatypes: {:?}", script.commands[8]);
+        };
+        assert_eq!(sorts[0].name.name, "Color");
+        assert_eq!(datatypes[0].constructors.len(), 2);
+
+        assert!(matches!(script.commands[9].kind, CommandKind::Push(1)));
+        assert!(
+            matches!(&script.commands[14].kind, CommandKind::Other(name, args)
+            if name.name == "minimize" && args.len() == 1)
+        );
+        assert_eq!(
+            script.commands[11].span,
+            Span::new(
+                text.find("(assert (forall").unwrap(),
+                text.find("\n(check-sat-").unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn reports_errors_where_they_are() {
+        let bad = "this is not a valid smt2 file, it should error out\n";
+        let diagnostic = parse(bad).unwrap_err();
+        assert_eq!(
+            diagnostic.render("bad.smt2", bad),
+            "bad.smt2:1:1: expected a command, found symbol `this`\n  |\n1 | this is not a valid \
+             smt2 file, it should error out\n  | ^^^^"
+        );
+
+        let errors = [
+            ("(check-sat))", "t.smt2:1:12: unexpected `)`"),
+            (
+                "(check-sat)\n(assert (> x 1)",
+                "t.smt2:2:1: unclosed `(`, the script ends before its `)`",
+            ),
+            ("(assert)", "t.smt2:1:8: missing a term in `assert`"),
+            (
+                "(check-sat 1)",
+                "t.smt2:1:12: unexpected argument to `check-sat`",
+            ),
+            ("(assert (f))", "t.smt2:1:9: missing arguments for `f`"),
+            (
+                "(assert (> x :a))",
+                "t.smt2:1:14: expected a term, found keyword `:a`",
+            ),
+            (
+                "(declare-const x ())",
+                "t.smt2:1:18: expected a sort, found `()`",
+            ),
+            (
+                "(declare-const 1 Int)",
+                "t.smt2:1:16: expected a symbol, found literal `1`",
+            ),
+            (
+                "(declare-fun f Int Bool)",
+                "t.smt2:1:16: expected a list of sorts, found symbol `Int`",
+            ),
+            (
+                "(assert ((_ extract) x))",
+                "t.smt2:1:10: missing indices for `extract`",
+            ),
+            (
+                "(push x)",
+                "t.smt2:1:7: expected a numeral, found symbol `x`",
+            ),
+            (
+                "(declare-datatypes ((A 0) (B 0)) (((a))))",
+                "t.smt2:1:20: 2 sort(s) declared, but 1 datatype(s) defined",
+            ),
+            (
+                "(set-option :a 1 :b 2)",
+                "t.smt2:1:1: `set-option` expects a single attribute",
+            ),
+            (
+                "((check-sat))",
+                "t.smt2:1:2: expected a command name, found a list",
+            ),
+            (
+                "(let ((x 1)) x)",
+                "t.smt2:1:2: expected a command name, found symbol `let`",
+            ),
+        ];
+
+        for (text, message) in errors {
+            assert_eq!(parse_error(text), message, "{}", text);
+        }
+    }
+
+    #[test]
+    fn checks_deeply_nested_terms() {
+        let nested = |depth| {
+            format!(
+                "(assert {}true{})",
+                "(not ".repeat(depth),
+                ")".repeat(depth)
+            )
+        };
+
+        assert_eq!(check(&nested(MAX_DEPTH)), Ok(()));
+        assert_eq!(check(&nested(MAX_DEPTH + 1)), Ok(()));
+        // the commands after a deep one are still checked
+        let text = format!("{})", nested(MAX_DEPTH + 1));
+        assert_eq!(
+            check(&text),
+            Err(Diagnostic::new(
+                "unexpected `)`",
+                Span::new(text.len() - 1, text.len())
+            ))
+        );
+
+        let error = with_parser_stack(|| parse(&nested(MAX_DEPTH + 1))).unwrap_err();
+        assert_eq!(
+            error.message,
+            format!("nested more than {} levels deep", MAX_DEPTH)
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! Discovery of solver executables on the PATH, and management of the solver cache
+//! (~/.jsi/cache.json), equivalent to `find_solvers`/`load_solvers`/`save_solvers`.
+//!
+//! The cache is considered stale (and is rebuilt automatically) when:
+//! - solvers.json was modified after the cache was written
+//! - a cached executable is missing or no longer executable
+//! - a cached executable changed (different mtime or inode) since it was cached
+
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+use std::fs;
+use std::io;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::definitions::{invalid_data, Definitions};
+
+/// Key under which jsi records its version in cache.json (see `main` in
+/// src/jsi/cli.py, which ignores a cache stamped by another version).
+const VERSION_KEY: &str = "__version__";
+
+/// Maps executable names to executable paths (the contents of cache.json).
+pub type SolverPaths = HashMap<String, String>;
+
+/// Identifies a particular build of an executable, so that we notice upgrades.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Stamp {
+    mtime: i64,
+    mtime_nsec: i64,
+    inode: u64,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Option<Stamp> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Stamp {
+            mtime: metadata.mtime(),
+            mtime_nsec: metadata.mtime_nsec(),
+            inode: metadata.ino(),
+        })
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+/// Equivalent of `shutil.which`.
+pub fn which(executable: &str) -> Option<PathBuf> {
+    if executable.contains('/') {
+        let path = PathBuf::from(executable);
+        return is_executable(&path).then_some(path);
+    }
+
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(executable))
+        .find(|path| is_executable(path))
+}
+
+/// Scans the PATH for the executable of every definition, reporting progress on stderr.
+pub fn find_solvers(definitions: &Definitions) -> SolverPaths {
+    eprintln!("looking for solvers available on PATH:");
+
+    let executables: BTreeSet<&str> = definitions
+        .values()
+        .map(|definition| definition.executable.as_str())
+        .collect();
+
+    let mut paths = SolverPaths::new();
+    for executable in executables {
+        match which(executable) {
+            Some(path) => {
+                eprintln!("{:>6} {}", "OK", executable);
+                paths.insert(executable.to_string(), path.to_string_lossy().into_owned());
+            }
+            None => eprintln!("{:>6} {}", "N/A", executable),
+        }
+    }
+
+    eprintln!();
+    paths
+}
+
+fn solver_entries(paths: &SolverPaths) -> impl Iterator<Item = (&String, &String)> {
+    paths
+        .iter()
+        .filter(|(name, _)| name.as_str() != VERSION_KEY)
+}
+
+pub fn load_solvers(config: &Config) -> io::Result<SolverPaths> {
+    let path = &config.path_cache;
+    let data = fs::read_to_string(path)?;
+    serde_json::from_str(&data).map_err(|err| invalid_data(path, err))
+}
+
+fn load_stamps(config: &Config) -> HashMap<String, Stamp> {
+    fs::read_to_string(&config.path_stamps)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Writes cache.json (in the same format as jsi) and the fingerprints of the
+/// cached executables.
+///
+/// `version` is jsi's version stamp from the previous cache, if any. jsi rescans
+/// the PATH when the stamp of cache.json isn't its own version, so we carry it over:
+/// dropping it would make the next jsi run rebuild a cache jsif just refreshed.
+pub fn save_solvers(paths: &SolverPaths, version: Option<&str>, config: &Config) -> io::Result<()> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    fs::create_dir_all(&config.jsi_home)?;
+
+    let mut paths = paths.clone();
+    if let Some(version) = version {
+        paths.insert(VERSION_KEY.to_string(), version.to_string());
+    }
+
+    let stamps: HashMap<&String, Stamp> = solver_entries(&paths)
+        .filter_map(|(_, path)| Some((path, Stamp::of(Path::new(path))?)))
+        .collect();
+
+    fs::write(&config.path_cache, serde_json::to_string(&paths)?)?;
+    fs::write(&config.path_stamps, serde_json::to_string(&stamps)?)?;
+    Ok(())
+}
+
+fn modified(path: &Path) -> Option<std::time::SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Returns why the cached paths can't be trusted anymore, or None if they are fine.
+pub fn stale_reason(paths: &SolverPaths, config: &Config) -> Option<String> {
+    if let (Some(definitions), Some(cache)) = (
+        modified(&config.definitions_file),
+        modified(&config.path_cache),
+    ) {
+        if definitions > cache {
+            return Some(format!("{} changed", config.definitions_file.display()));
+        }
+    }
+
+    let stamps = load_stamps(config);
+    for (executable, path) in solver_entries(paths) {
+        if !is_executable(Path::new(path)) {
+            return Some(format!("{} is missing ({})", executable, path));
+        }
+
+        // entries written by jsi have no stamp, we can only check that they exist
+        let Some(stamp) = stamps.get(path) else {
+            continue;
+        };
+
+        if Stamp::of(Path::new(path)).as_ref() != Some(stamp) {
+            return Some(format!("{} changed ({})", executable, path));
+        }
+    }
+
+    None
+}
+
+/// Loads the solver paths from the cache, scanning the PATH (and updating the cache)
+/// if there is no usable cache or if `refresh` is set.
+pub fn find_available_solvers(
+    definitions: &Definitions,
+    config: &Config,
+    refresh: bool,
+) -> io::Result<SolverPaths> {
+    let cached = match load_solvers(config) {
+        Ok(paths) => Some(paths),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => {
+            eprintln!("warning: ignoring invalid solver cache ({})", err);
+            None
+        }
+    };
+
+    if let (Some(paths), false) = (&cached, refresh) {
+        match stale_reason(paths, config) {
+            None if solver_entries(paths).next().is_some() => return Ok(paths.clone()),
+            None => {}
+            Some(reason) => eprintln!("warning: ignoring stale solver cache ({})", reason),
+        }
+    }
+
+    let version = cached
+        .as_ref()
+        .and_then(|paths| paths.get(VERSION_KEY))
+        .map(String::as_str);
+
+    let paths = find_solvers(definitions);
+    save_solvers(&paths, version, config)?;
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_config;
+
+    #[test]
+    fn which_finds_sh() {
+        assert!(which("sh").is_some());
+        assert!(which("/bin/sh").is_some());
+        assert!(which("definitely-not-a-solver").is_none());
+    }
+
+    #[test]
+    fn save_load_and_stale() {
+        let config = temp_config("stale");
+        let sh = which("sh").unwrap().to_string_lossy().into_owned();
+        let paths = SolverPaths::from([("sh".to_string(), sh)]);
+
+        save_solvers(&paths, Some("1.0"), &config).unwrap();
+        let loaded = load_solvers(&config).unwrap();
+        assert_eq!(loaded.get(VERSION_KEY).map(String::as_str), Some("1.0"));
+        assert_eq!(loaded.get("sh"), paths.get("sh"));
+        assert_eq!(stale_reason(&loaded, &config), None);
+
+        // a cached executable that went away
+        let mut missing = loaded.clone();
+        missing.insert("z3".into(), "/nonexistent/z3".into());
+        assert!(stale_reason(&missing, &config)
+            .unwrap()
+            .contains("z3 is missing"));
+
+        // a cached executable that changed since it was cached
+        fs::write(
+            &config.path_stamps,
+            r#"{"/bin/sh":{"mtime":0,"mtime_nsec":0,"inode":0}}"#,
+        )
+        .unwrap();
+        let changed = SolverPaths::from([("sh".to_string(), "/bin/sh".to_string())]);
+        assert!(stale_reason(&changed, &config)
+            .unwrap()
+            .contains("sh changed"));
+
+        fs::remove_dir_all(&config.jsi_home).unwrap();
+    }
+}
@@ -0,0 +1,24 @@
+//! Support for reading the SMT-LIB script from stdin (`jsif -`).
+//!
+//! The daemon receives the script inline, while the local runner writes it to a
+//! private temporary file (see `jsif::input`).
+
+use std::io::{self, Read};
+
+/// File name of the script read from stdin, which the solver outputs are named after.
+pub const STDIN_FILE_NAME: &str = "stdin.smt2";
+
+/// Reads the whole script from stdin.
+pub fn read_script() -> io::Result<String> {
+    let mut script = String::new();
+    io::stdin().read_to_string(&mut script)?;
+
+    if script.trim().is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no input on stdin",
+        ));
+    }
+
+    Ok(script)
+}
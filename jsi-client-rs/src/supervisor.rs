@@ -0,0 +1,391 @@
+//! Process supervision for solvers, the native counterpart of `ProcessController`
+//! in jsi.
+//!
+//! Each child is the leader of its own process group, so signals reach the helpers
+//! it forks too (portfolio solvers, wrapper scripts). Exits are detected from a
+//! single loop: `wait` blocks on the pidfds of all the children at once (on Linux,
+//! short sleeps elsewhere), instead of a monitor thread per child.
+//!
+//! Children are also recorded for `jsif reap` (see `reaper`), and on Linux they get
+//! SIGKILL if the thread that spawned them dies.
+
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, ExitStatus};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::reaper;
+
+/// Default time between SIGTERM and SIGKILL (the fixed value used by jsi).
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+/// How long `wait` sleeps between checks where pidfds are not available.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Handle on a child of a `Supervisor`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChildId(usize);
+
+struct Supervised {
+    child: Child,
+
+    /// becomes readable when the child exits (None if pidfds are not supported)
+    pidfd: Option<OwnedFd>,
+    status: Option<ExitStatus>,
+    exited_at: Option<Instant>,
+}
+
+impl Supervised {
+    fn pgid(&self) -> libc::pid_t {
+        self.child.id() as libc::pid_t
+    }
+
+    /// Signals the process group of the child, unless it has been reaped (the
+    /// group id could then belong to someone else).
+    fn signal(&self, signal: libc::c_int) {
+        if self.status.is_none() {
+            // SAFETY: plain syscall, the leader is not reaped so the group id is ours
+            unsafe { libc::kill(-self.pgid(), signal) };
+        }
+    }
+
+    /// True if the child has exited, without reaping it.
+    fn has_exited(&self) -> io::Result<bool> {
+        // SAFETY: siginfo_t is plain data, and waitid only writes to it
+        let mut info: libc::siginfo_t = unsafe { mem::zeroed() };
+        let flags = libc::WEXITED | libc::WNOHANG | libc::WNOWAIT;
+        if unsafe { libc::waitid(libc::P_PID, self.child.id(), &mut info, flags) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: si_pid is set by waitid (and left to 0 if no child has exited)
+        Ok(unsafe { info.si_pid() } != 0)
+    }
+
+    fn reap(&mut self) -> io::Result<()> {
+        // kill the helpers left behind, while the zombie leader still holds the group id
+        self.signal(libc::SIGKILL);
+        self.status = Some(self.child.wait()?);
+        self.exited_at = Some(Instant::now());
+        self.pidfd = None;
+        reaper::forget(self.pgid());
+        Ok(())
+    }
+}
+
+/// Makes the child get SIGKILL when the thread that spawns it exits (PR_SET_PDEATHSIG
+/// is tied to the thread, so children must not outlive the thread that started them).
+#[cfg(target_os = "linux")]
+fn kill_on_parent_death(command: &mut Command) {
+    let parent = std::process::id() as libc::pid_t;
+
+    // SAFETY: prctl, getppid and raise are async-signal-safe
+    unsafe {
+        command.pre_exec(move || {
+            if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            // the parent died before prctl, so the signal will never come
+            if libc::getppid() != parent {
+                libc::raise(libc::SIGKILL);
+            }
+
+            Ok(())
+        })
+    };
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kill_on_parent_death(_command: &mut Command) {}
+
+#[cfg(target_os = "linux")]
+fn pidfd_open(pid: u32) -> Option<OwnedFd> {
+    use std::os::fd::FromRawFd;
+
+    // SAFETY: plain syscall, and we own the returned fd
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    (fd >= 0).then(|| unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pidfd_open(_pid: u32) -> Option<OwnedFd> {
+    None
+}
+
+/// Owns a set of child processes, each in its own process group.
+///
+/// Children that are still running when the supervisor is dropped are killed
+/// (with their process group) and reaped.
+pub struct Supervisor {
+    grace_period: Duration,
+    children: Vec<Supervised>,
+}
+
+impl Supervisor {
+    pub fn new(grace_period: Duration) -> Self {
+        Supervisor {
+            grace_period,
+            children: Vec::new(),
+        }
+    }
+
+    /// How long `terminate` waits after SIGTERM before sending SIGKILL.
+    pub fn grace_period(&self) -> Duration {
+        self.grace_period
+    }
+
+    /// Spawns `command` as the leader of a new process group.
+    pub fn spawn(&mut self, command: &mut Command) -> io::Result<ChildId> {
+        kill_on_parent_death(command);
+        let child = command.process_group(0).spawn()?;
+        let pidfd = pidfd_open(child.id());
+
+        let name = Path::new(command.get_program())
+            .file_name()
+            .unwrap_or_default();
+        reaper::record(&name.to_string_lossy(), child.id() as i32);
+        self.children.push(Supervised {
+            child,
+            pidfd,
+            status: None,
+            exited_at: None,
+        });
+
+        Ok(ChildId(self.children.len() - 1))
+    }
+
+    pub fn pid(&self, id: ChildId) -> u32 {
+        self.children[id.0].child.id()
+    }
+
+    /// Takes the pipes to the stdin and stdout of the child, if it was spawned with
+    /// `Stdio::piped()` for them.
+    pub fn take_pipes(&mut self, id: ChildId) -> (Option<ChildStdin>, Option<ChildStdout>) {
+        let child = &mut self.children[id.0].child;
+        (child.stdin.take(), child.stdout.take())
+    }
+
+    /// The exit status of the child, once it has been reaped.
+    pub fn status(&self, id: ChildId) -> Option<ExitStatus> {
+        self.children[id.0].status
+    }
+
+    /// When `wait` (or `terminate`) found that the child had exited.
+    pub fn exited_at(&self, id: ChildId) -> Option<Instant> {
+        self.children[id.0].exited_at
+    }
+
+    /// Sends `signal` to the process group of the child, if it is still running.
+    pub fn signal(&self, id: ChildId, signal: libc::c_int) {
+        self.children[id.0].signal(signal);
+    }
+
+    /// Reaps the children that have exited (killing what is left of their process
+    /// group), and returns them.
+    pub fn reap(&mut self) -> io::Result<Vec<ChildId>> {
+        let mut exited = Vec::new();
+        for (i, supervised) in self.children.iter_mut().enumerate() {
+            if supervised.status.is_none() && supervised.has_exited()? {
+                supervised.reap()?;
+                exited.push(ChildId(i));
+            }
+        }
+
+        Ok(exited)
+    }
+
+    /// Waits until a child exits or `timeout` elapses (forever with None), then
+    /// reaps the children that have exited and returns them.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> io::Result<Vec<ChildId>> {
+        let exited = self.reap()?;
+        if !exited.is_empty() || timeout == Some(Duration::ZERO) {
+            return Ok(exited);
+        }
+
+        let running: Vec<&Supervised> = self
+            .children
+            .iter()
+            .filter(|supervised| supervised.status.is_none())
+            .collect();
+
+        if running.is_empty() {
+            return Ok(exited);
+        }
+
+        let pidfds: Option<Vec<libc::pollfd>> = running
+            .iter()
+            .map(|supervised| {
+                supervised.pidfd.as_ref().map(|pidfd| libc::pollfd {
+                    fd: pidfd.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                })
+            })
+            .collect();
+
+        match pidfds {
+            Some(mut pidfds) => {
+                let timeout_ms = timeout.map_or(-1, |timeout| {
+                    timeout.as_micros().div_ceil(1000).min(i32::MAX as u128) as libc::c_int
+                });
+
+                // SAFETY: the pollfd array is valid for the duration of the call
+                let ready =
+                    unsafe { libc::poll(pidfds.as_mut_ptr(), pidfds.len() as _, timeout_ms) };
+                let err = io::Error::last_os_error();
+                if ready < 0 && err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            None => {
+                let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+                while !self.any_exited()? {
+                    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        break;
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                }
+            }
+        }
+
+        self.reap()
+    }
+
+    /// True if one of the running children has exited (and can be reaped).
+    fn any_exited(&self) -> io::Result<bool> {
+        for supervised in self.children.iter().filter(|s| s.status.is_none()) {
+            if supervised.has_exited()? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Sends SIGTERM to the process groups of `ids`, then SIGKILL to the ones that
+    /// are still running after the grace period, and reaps them all.
+    pub fn terminate(&mut self, ids: &[ChildId]) -> io::Result<()> {
+        for &id in ids {
+            self.signal(id, libc::SIGTERM);
+        }
+
+        // a grace period out of reach of an Instant means waiting for SIGTERM only
+        let deadline = Instant::now().checked_add(self.grace_period);
+        while ids.iter().any(|&id| self.status(id).is_none()) {
+            let now = Instant::now();
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                break;
+            }
+
+            self.wait(deadline.map(|deadline| deadline - now))?;
+        }
+
+        for &id in ids {
+            let supervised = &mut self.children[id.0];
+            if supervised.status.is_none() {
+                supervised.reap()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Terminates every child that is still running.
+    pub fn terminate_all(&mut self) -> io::Result<()> {
+        let running: Vec<ChildId> = (0..self.children.len())
+            .map(ChildId)
+            .filter(|&id| self.status(id).is_none())
+            .collect();
+
+        self.terminate(&running)
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        for supervised in self.children.iter_mut() {
+            if supervised.status.is_none() {
+                let _ = supervised.reap();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wait::wait_until;
+    use std::fs;
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::Stdio;
+
+    /// True if the process is gone or a zombie (orphans may not get reaped in containers).
+    fn is_dead(pid: u32) -> bool {
+        match fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) => stat
+                .rsplit(')')
+                .next()
+                .is_some_and(|s| s.trim_start().starts_with('Z')),
+            Err(_) => true,
+        }
+    }
+
+    #[test]
+    fn wait_returns_when_a_child_exits() {
+        let mut supervisor = Supervisor::new(DEFAULT_GRACE_PERIOD);
+        let slow = supervisor.spawn(Command::new("sleep").arg("30")).unwrap();
+        let fast = supervisor.spawn(&mut Command::new("true")).unwrap();
+
+        let start = Instant::now();
+        let mut exited = Vec::new();
+        while exited.is_empty() {
+            exited = supervisor.wait(Some(Duration::from_secs(5))).unwrap();
+        }
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(exited, vec![fast]);
+        assert!(supervisor.status(fast).unwrap().success());
+        assert!(supervisor.status(slow).is_none());
+    }
+
+    #[test]
+    fn terminate_kills_the_process_tree() {
+        let mut supervisor = Supervisor::new(DEFAULT_GRACE_PERIOD);
+        let mut command = Command::new("sh");
+        command
+            .args(["-c", "sleep 30 & echo $!; wait"])
+            .stdout(Stdio::piped());
+        let id = supervisor.spawn(&mut command).unwrap();
+
+        let stdout = supervisor.children[id.0].child.stdout.take().unwrap();
+        let mut line = String::new();
+        BufReader::new(stdout).read_line(&mut line).unwrap();
+        let helper: u32 = line.trim().parse().unwrap();
+
+        supervisor.terminate(&[id]).unwrap();
+        assert_eq!(supervisor.status(id).unwrap().signal(), Some(libc::SIGTERM));
+        assert!(wait_until(Duration::from_secs(1), || is_dead(helper)));
+    }
+
+    #[test]
+    fn terminate_escalates_after_the_grace_period() {
+        let mut supervisor = Supervisor::new(Duration::from_millis(100));
+        let id = supervisor
+            .spawn(Command::new("sh").args(["-c", "trap '' TERM; sleep 30"]))
+            .unwrap();
+
+        // give the shell time to install the trap
+        thread::sleep(Duration::from_millis(100));
+        let start = Instant::now();
+        supervisor.terminate(&[id]).unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(supervisor.status(id).unwrap().signal(), Some(libc::SIGKILL));
+    }
+}
@@ -0,0 +1,15 @@
+//! Helpers shared by the tests of several modules.
+
+use std::env;
+use std::fs;
+use std::process;
+
+use crate::config::Config;
+
+/// A config rooted in a fresh directory (emptied if a previous run left it behind),
+/// unique to the test `name` and to this process.
+pub fn temp_config(name: &str) -> Config {
+    let dir = env::temp_dir().join(format!("jsif-test-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    Config::new(dir)
+}
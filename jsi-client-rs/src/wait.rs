@@ -0,0 +1,26 @@
+//! Polling with backoff, for conditions that have no notification to wait on (a
+//! process going away, a socket starting to accept connections...).
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Polls `done` with exponential backoff (1ms, 2ms, 4ms... capped at 100ms)
+/// until it returns true or the timeout expires. Returns the last value of `done`.
+pub fn wait_until(timeout: Duration, mut done: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    let mut delay = Duration::from_millis(1);
+
+    loop {
+        if done() {
+            return true;
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+
+        thread::sleep(delay.min(deadline - now));
+        delay = (delay * 2).min(Duration::from_millis(100));
+    }
+}
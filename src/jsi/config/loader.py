@@ -178,3 +178,17 @@ def save_solvers(
 
     with open(config.path_cache, "w") as f:
         json.dump(paths, f)
+
+
+def find_available_solvers(
+    solver_definitions: dict[str, SolverDefinition],
+    config: Config,
+) -> dict[str, str]:
+    """Load solver paths from the cache, scanning the PATH if there is no cache."""
+
+    available_solvers = load_solvers(solver_definitions, config)
+    if not available_solvers:
+        available_solvers = find_solvers(solver_definitions, config)
+        save_solvers(available_solvers, config)
+
+    return available_solvers
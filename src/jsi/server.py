@@ -17,7 +17,12 @@ The daemon:
 - it writes its own pid to ~/.jsi/daemon/server.pid
 - it outputs logs to ~/.jsi/daemon/server.{err,out}
 - it listens for requests on a unix domain socket (by default ~/.jsi/daemon/server.sock)
-- each request is a single line of text, the path to a file to solve
+- each request is a single line of text, either:
+    - a JSON object with the path to a file to solve (or the script itself) and
+      per-request options (see `parse_request`), answered with a single line of JSON
+    - or just the path to a file to solve (legacy format, plain text response)
+- while a JSON request is in progress, the client can send a cancel message
+  (`{"version": 1, "cancel": true}`) or close the connection to kill the solvers
 - for each request, it runs the sequence of solvers defined in the config
 - it returns the output of the solvers, based on the config
 - it runs until terminated by the user or another daemon
@@ -25,10 +30,14 @@ The daemon:
 
 import asyncio
 import contextlib
+import copy
+import json
 import os
 import signal
 import sys
+import tempfile
 import threading
+import time
 from pathlib import Path
 
 import daemon  # type: ignore
@@ -43,10 +52,12 @@ from jsi.core import (
     Command,
     ProcessController,
     Task,
+    TaskResult,
+    TaskStatus,
     base_commands,
     set_input_output,
 )
-from jsi.utils import get_console, logger, pid_exists, unexpand_home
+from jsi.utils import file_loc, get_console, logger, pid_exists, unexpand_home
 
 SERVER_HOME = Path.home() / ".jsi" / "daemon"
 SOCKET_PATH = SERVER_HOME / "server.sock"
@@ -54,6 +65,9 @@ STDOUT_PATH = SERVER_HOME / "server.out"
 STDERR_PATH = SERVER_HOME / "server.err"
 PID_PATH = SERVER_HOME / "server.pid"
 CONN_BUFFER_SIZE = 1024
+MAX_REQUEST_SIZE = 256 * 1024 * 1024
+STDERR_EXCERPT_SIZE = 1024
+PROTOCOL_VERSION = 1
 
 
 unexpanded_pid = unexpand_home(PID_PATH)
@@ -74,13 +88,78 @@ server_usage = f"""[bold white]starting daemon...[/]
 - terminate daemon (forcefully, with SIGKILL):
     [green]kill -9 $(cat {unexpanded_pid})[/]
 
+- or use the rust client to check on, stop or restart the daemon:
+    [green]jsif daemon status|stop|restart[/]
+
 (use the commands above to monitor the daemon, this process will exit immediately)"""
 
 
+class BadRequestError(Exception):
+    pass
+
+
+class SolveRequest:
+    """A parsed JSON request (see `parse_request`)."""
+
+    def __init__(
+        self,
+        config: Config,
+        file: str | None = None,
+        script: str | None = None,
+        details: bool = False,
+    ):
+        self.config = config
+        self.file = file
+        self.script = script
+        self.details = details
+
+
+def stderr_excerpt(command: Command) -> str | None:
+    """The end of the stderr of a solver that has exited, where errors usually are."""
+
+    if not command.started():
+        return None
+
+    _, stderr = command.read_io()
+    if not stderr or not stderr.strip():
+        return None
+
+    return stderr[-STDERR_EXCERPT_SIZE:]
+
+
+def solver_report(command: Command) -> dict[str, object]:
+    """The outcome of a single solver, same columns as the results table of the CLI
+    (plus an excerpt of its stderr)."""
+
+    # killed solvers may not have been reaped yet
+    if command.started() and not command.done():
+        command.wait()
+
+    elapsed = command.elapsed()
+    return {
+        "name": command.name,
+        "result": command.result().value,
+        "exit": command.returncode,
+        "elapsed": elapsed,
+        "output_file": file_loc(command.stdout) or None,
+        "size": len(command.stdout_text) if command.stdout_text else 0,
+        "stderr": stderr_excerpt(command),
+    }
+
+
 class ResultListener:
     def __init__(self):
         self.event = threading.Event()
-        self._result: str | None = None
+        self._winner: Command | None = None
+        self.commands: list[Command] = []
+        self.controller: ProcessController | None = None
+        self.task: Task | None = None
+
+        # set when the client went away before the request was done
+        self.cancelled = False
+
+        # time spent solving, set once the request is done
+        self.elapsed: float | None = None
 
     def exit_callback(self, command: Command, task: Task):
         name, result, elapsed = command.name, command.result(), command.elapsed()
@@ -89,16 +168,153 @@ class ResultListener:
         if self.event.is_set():
             return
 
-        if command.done() and command.ok() and (stdout_text := command.stdout_text):
+        if command.done() and command.ok() and command.stdout_text:
+            self._winner = command
             self.event.set()
-            self._result = f"{stdout_text.strip()}\n; (result from {command.name})"
+
+    def wait_for_all(self, controller: ProcessController):
+        """Stop waiting once every solver has exited, even if none of them succeeded."""
+
+        controller.join()
+        self.event.set()
+
+    def cancel(self):
+        """Stop waiting for a result and kill the solvers (blocks until they exit)."""
+
+        self.cancelled = True
+        self.event.set()
+
+        controller = self.controller
+        if controller is not None and controller.task.status != TaskStatus.NOT_STARTED:
+            controller.kill()
 
     @property
-    def result(self) -> str:
+    def winner(self) -> Command | None:
+        """The solver that produced the result, None if no solver succeeded."""
+
         self.event.wait()
+        return self._winner
+
+    def best_result(self) -> TaskResult:
+        """The result when no solver succeeded (timeout, error or unknown)."""
+
+        return self.task.result if self.task is not None else TaskResult.UNKNOWN
+
+    @property
+    def result(self) -> str:
+        winner = self.winner
+        if winner is None:
+            return f"{self.best_result().value}\n; (no solver succeeded)"
+
+        assert winner.stdout_text is not None
+        return f"{winner.stdout_text.strip()}\n; (result from {winner.name})"
+
+    def response(self, details: bool = False) -> dict[str, object]:
+        if self.cancelled:
+            # acknowledges a cancel message, the solvers are gone by now
+            response: dict[str, object] = {
+                "version": PROTOCOL_VERSION,
+                "result": TaskResult.KILLED.value,
+                "cancelled": True,
+                "elapsed": self.elapsed,
+            }
+        elif (winner := self.winner) is None:
+            # all the solvers are done, show what went wrong with each of them
+            response = {
+                "version": PROTOCOL_VERSION,
+                "result": self.best_result().value,
+                "output": "",
+                "elapsed": self.elapsed,
+            }
+            details = True
+        else:
+            response = {
+                "version": PROTOCOL_VERSION,
+                "result": winner.result().value,
+                "solver": winner.name,
+                "output": winner.stdout_text,
+                "elapsed": self.elapsed,
+            }
+
+        if details:
+            response["solvers"] = [solver_report(c) for c in self.commands]
+
+        return response
+
+
+def error_response(message: str) -> dict[str, object]:
+    return {"version": PROTOCOL_VERSION, "result": "error", "error": message}
+
+
+def parse_request(data: bytes, config: Config) -> SolveRequest:
+    """Parse a JSON request into the file to solve (or the inline script to solve)
+    and a per-request config.
 
-        assert self._result is not None
-        return self._result
+    Example request:
+        {"version": 1, "path": "/abs/path/to/file.smt2", "timeout": 2.5,
+         "interval": 0.1, "sequence": ["z3", "yices"], "model": true,
+         "full_run": false, "details": true}
+
+    Instead of `path`, a request can carry the SMT-LIB script itself in `input`.
+    With `details`, the response includes the outcome of every solver.
+
+    Only `version` and either `path` or `input` are required, the other options
+    default to the config the daemon was started with."""
+
+    try:
+        request = json.loads(data)
+    except ValueError as err:
+        raise BadRequestError(f"invalid request: {err}") from err
+
+    if not isinstance(request, dict):
+        raise BadRequestError("invalid request: expected a JSON object")
+
+    version = request.get("version")  # type: ignore
+    if version != PROTOCOL_VERSION:
+        raise BadRequestError(f"unsupported protocol version: {version}")
+
+    if request.get("session"):  # type: ignore
+        raise BadRequestError("sessions are not supported by this daemon, use jsid")
+
+    file = request.get("path")  # type: ignore
+    script = request.get("input")  # type: ignore
+    if file is not None and script is not None:
+        raise BadRequestError("invalid request: both path and input provided")
+
+    if script is not None:
+        if not isinstance(script, str) or not script:
+            raise BadRequestError("invalid request: empty input")
+    elif not isinstance(file, str) or not file:
+        raise BadRequestError("invalid request: missing path")
+
+    config = copy.copy(config)
+
+    try:
+        if (timeout := request.get("timeout")) is not None:  # type: ignore
+            config.timeout_seconds = float(timeout)  # type: ignore
+
+        if (interval := request.get("interval")) is not None:  # type: ignore
+            config.interval_seconds = float(interval)  # type: ignore
+    except (TypeError, ValueError) as err:
+        raise BadRequestError(f"invalid request: {err}") from err
+
+    if config.timeout_seconds < 0:
+        raise BadRequestError(f"invalid timeout value: {config.timeout_seconds}")
+
+    if config.interval_seconds < 0:
+        raise BadRequestError(f"invalid interval value: {config.interval_seconds}")
+
+    if sequence := request.get("sequence"):  # type: ignore
+        config.sequence = [str(solver) for solver in sequence]  # type: ignore
+
+    if "model" in request:
+        config.model = bool(request["model"])  # type: ignore
+
+    if "full_run" in request:
+        config.early_exit = not request["full_run"]  # type: ignore
+
+    details = bool(request.get("details", False))  # type: ignore
+    return SolveRequest(config, file=file, script=script, details=details)
 
 
 class PIDFile:
@@ -141,6 +357,30 @@ class PIDFile:
             os.remove(self.path)
 
 
+def is_cancel(data: bytes) -> bool:
+    """True if `data` is a cancel message: {"version": 1, "cancel": true}"""
+
+    try:
+        message = json.loads(data)
+    except ValueError:
+        return False
+
+    return isinstance(message, dict) and message.get("cancel") is True  # type: ignore
+
+
+async def watch_client(reader: asyncio.StreamReader) -> bool:
+    """Returns True once the client sends a cancel message for the request in
+    progress, or False if it closes its end of the connection first."""
+
+    while line := await reader.readline():
+        if is_cancel(line):
+            return True
+
+        logger.info(f"ignoring unexpected message: {line[:CONN_BUFFER_SIZE]!r}")
+
+    return False
+
+
 def start_logger(command: Command, task: Task):
     logger.info(f"command started: {command.parts()}")
 
@@ -156,8 +396,9 @@ class Server:
         self.available_solvers = find_available_solvers(self.solver_definitions, config)
 
     async def start(self):
+        # requests with inline scripts can be much larger than the default limit
         server = await asyncio.start_unix_server(
-            self.handle_client, path=str(SOCKET_PATH)
+            self.handle_client, path=str(SOCKET_PATH), limit=MAX_REQUEST_SIZE
         )
 
         async with server:
@@ -168,58 +409,133 @@ class Server:
         self, reader: asyncio.StreamReader, writer: asyncio.StreamWriter
     ):
         try:
-            data: bytes = await reader.read(1024)
+            data: bytes = await reader.read(CONN_BUFFER_SIZE)
             if data:
-                message: str = data.decode()
-                print(f"received request: {message}")
-                result = await self.solve(message)
-                writer.write(result.encode())
+                if data.lstrip().startswith(b"{"):
+                    # structured request, make sure we have the full line
+                    if not data.endswith(b"\n"):
+                        data += await reader.readuntil(b"\n")
+
+                    summary = data[:CONN_BUFFER_SIZE].decode(errors="replace")
+                    print(f"received request: {summary.strip()}")
+                    response = await self.handle_request(data, reader)
+                    if reader.at_eof():
+                        print("client disconnected, dropping response")
+                        return
+
+                    writer.write(json.dumps(response).encode() + b"\n")
+                else:
+                    message: str = data.decode()
+                    print(f"received request: {message}")
+                    listener = await self.solve(message, self.config)
+                    writer.write(listener.result.encode())
+
                 await writer.drain()
         except Exception as e:
             logger.info(f"Error handling client: {e}")
         finally:
             writer.close()
-            await writer.wait_closed()
+            with contextlib.suppress(ConnectionError):
+                await writer.wait_closed()
+
+    async def handle_request(
+        self, data: bytes, reader: asyncio.StreamReader | None = None
+    ) -> dict[str, object]:
+        try:
+            request = parse_request(data, self.config)
+            if request.script is None:
+                assert request.file is not None
+                listener = await self.solve(request.file, request.config, reader)
+                return listener.response(request.details)
+
+            # solver outputs are written next to the input, so keep them private
+            with tempfile.TemporaryDirectory(prefix="jsi-") as tmpdir:
+                file = os.path.join(tmpdir, "input.smt2")
+                with open(file, "w") as fd:
+                    fd.write(request.script)
+
+                listener = await self.solve(file, request.config, reader)
+                return listener.response(request.details)
+        except (BadRequestError, RuntimeError) as err:
+            return error_response(str(err))
+
+    async def solve(
+        self, file: str, config: Config, reader: asyncio.StreamReader | None = None
+    ) -> ResultListener:
+        """Solve `file`, killing the solvers if the client cancels the request or
+        disconnects from `reader` before the request is done."""
 
-    async def solve(self, file: str) -> str:
         # Assuming solve is CPU-bound, we use run_in_executor
         loop = asyncio.get_running_loop()
-        result = await loop.run_in_executor(None, self.sync_solve, file)
-        return result
+        listener = ResultListener()
+        solving = loop.run_in_executor(None, self.sync_solve, file, config, listener)
+        if reader is None:
+            return await solving
+
+        watcher = asyncio.ensure_future(watch_client(reader))
+        done, _ = await asyncio.wait(
+            {solving, watcher}, return_when=asyncio.FIRST_COMPLETED
+        )
+
+        if solving not in done:
+            reason = "request cancelled" if watcher.result() else "client disconnected"
+            print(f"{reason}, killing solvers for {file}")
+            await loop.run_in_executor(None, listener.cancel)
+
+        watcher.cancel()
+        return await solving
+
+    def sync_solve(
+        self, file: str, config: Config, listener: ResultListener | None = None
+    ) -> ResultListener:
+        start = time.perf_counter()
 
-    def sync_solve(self, file: str) -> str:
         # initialize the controller
         task = Task(name=str(file))
 
-        # FIXME: don't mutate the config
-        self.config.input_file = file
-        self.config.output_dir = os.path.dirname(file)
+        # work on a copy so that concurrent requests don't step on each other
+        config = copy.copy(config)
+        config.input_file = file
+        config.output_dir = os.path.dirname(file)
 
         defs = self.solver_definitions
         enabled_solvers = [solver for solver in defs if defs[solver].enabled]
 
         commands = base_commands(
-            self.config.sequence or enabled_solvers,
+            config.sequence or enabled_solvers,
             self.solver_definitions,
             self.available_solvers,
-            self.config,
+            config,
         )
-        set_input_output(commands, self.config)
+        set_input_output(commands, config)
 
-        listener = ResultListener()
+        listener = listener or ResultListener()
+        listener.commands = commands
         controller = ProcessController(
             task,
             commands,
-            self.config,
+            config,
             start_callback=start_logger,
             exit_callback=listener.exit_callback,
         )
+        listener.controller = controller
+        listener.task = task
         controller.start()
 
-        result = listener.result
-        controller.kill()
+        # wait for the first result, for all the solvers to be done without one, or
+        # for the request to be cancelled
+        threading.Thread(
+            target=listener.wait_for_all, args=(controller,), daemon=True
+        ).start()
+        listener.event.wait()
+
+        if config.early_exit or listener.cancelled:
+            controller.kill()
+        else:
+            controller.join()
 
-        return result
+        listener.elapsed = time.perf_counter() - start
+        return listener
 
 
 def daemonize(config: Config):